@@ -19,7 +19,8 @@ void main() {
         vec2 dir = -vec2(dFdx(z), dFdy(z)) * resolution;
         vec3 normal = normalize(vec3(dir, 1.0));
         
-        color = vec4(normal, 1.0); // Assign vec3 normal to vec4 color
+        // stored the same way as a normal map image, from 0.0 to 1.0
+        color = vec4(normal * 0.5 + 0.5, 1.0);
     } else {
         discard;
     }
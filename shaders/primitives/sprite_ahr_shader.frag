@@ -1,14 +1,102 @@
 #version 140
 
 in vec2 v_tex_coords;
+in vec2 v_effect_coords;
 out vec4 color;
 
 uniform sampler2D image;
+uniform bool flip_x;
+uniform bool flip_y;
+// normals have to point the other way when the sprite is flipped,
+// they are stored as normal * 0.5 + 0.5 so flipping is 1.0 - x
+uniform bool is_normal_map;
+uniform bool is_albedo;
+// multiplied with the color of the texture
+uniform vec4 tint;
+uniform float opacity;
+
+// the pixels where the noise is below the threshold are removed
+uniform bool use_dissolve;
+uniform sampler2D dissolve_noise;
+uniform float dissolve_threshold;
+uniform float dissolve_edge_width;
+uniform vec4 dissolve_edge_color;
+// only draw the glowing edge, this is added on top of the lit image
+uniform bool is_emissive;
+uniform float dissolve_glow_strength;
+
+// the albedo is looked up in the source palette and swapped with the same column of the target palette
+uniform bool use_palette;
+uniform sampler2D palette_albedo;
+uniform sampler2D source_palette;
+uniform sampler2D target_palette;
+// the row of the target palette to use, 0 is albedo, 1 is height, 2 is roughness, -1 is none
+uniform int palette_row;
+
+bool same_color(vec3 a, vec3 b) {
+    return distance(a, b) < 0.002;
+}
+
+vec4 swap_palette(vec4 original_color) {
+    ivec2 source_size = textureSize(source_palette, 0);
+    ivec2 target_size = textureSize(target_palette, 0);
+    // the target palette doesn't have a row for this channel
+    if (palette_row < 0 || palette_row >= target_size.y) {
+        return original_color;
+    }
+
+    vec3 albedo = texture(palette_albedo, v_tex_coords).rgb;
+    for (int i = 0; i < source_size.x && i < target_size.x; i++) {
+        vec3 source_color = texelFetch(source_palette, ivec2(i, 0), 0).rgb;
+        if (same_color(albedo, source_color)) {
+            // the textures are stored bottom to top, so the first row is the last one
+            vec3 target_color = texelFetch(target_palette, ivec2(i, target_size.y - 1 - palette_row), 0).rgb;
+            return vec4(target_color, original_color.a);
+        }
+    }
+    return original_color;
+}
 
 void main() {
     vec4 new_color = texture(image, v_tex_coords);
+    if (use_palette) {
+        new_color = swap_palette(new_color);
+    }
+    new_color *= tint;
+    new_color.a *= opacity;
     if (new_color.a == 0.0) {
         discard;
     }
+
+    bool on_edge = false;
+    if (use_dissolve) {
+        float noise = texture(dissolve_noise, v_effect_coords).r;
+        if (noise < dissolve_threshold) {
+            discard;
+        }
+        on_edge = dissolve_threshold > 0.0 && noise < dissolve_threshold + dissolve_edge_width;
+    }
+
+    if (is_emissive) {
+        if (!on_edge) {
+            discard;
+        }
+        color = vec4(dissolve_edge_color.rgb * dissolve_glow_strength, dissolve_edge_color.a * new_color.a);
+        return;
+    }
+
+    // the edge is drawn in the albedo too so it still shows when the lighting is off
+    if (on_edge && is_albedo) {
+        new_color.rgb = mix(new_color.rgb, dissolve_edge_color.rgb, dissolve_edge_color.a);
+    }
+
+    if (is_normal_map) {
+        if (flip_x) {
+            new_color.x = 1.0 - new_color.x;
+        }
+        if (flip_y) {
+            new_color.y = 1.0 - new_color.y;
+        }
+    }
     color = new_color;
-}
\ No newline at end of file
+}
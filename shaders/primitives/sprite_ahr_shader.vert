@@ -3,11 +3,25 @@
 in vec2 position;
 in vec2 tex_coords;
 out vec2 v_tex_coords;
+// 0.0 - 1.0 over the drawn sprite, ignores the source rect and flipping so effects stick to the sprite
+out vec2 v_effect_coords;
 
 uniform mat4 matrix;
+// x, y, width, height of the part of the texture to draw in 0.0 - 1.0 texture coordinates
+uniform vec4 source_rect;
+uniform bool flip_x;
+uniform bool flip_y;
 
 void main() {
-    v_tex_coords = tex_coords;
+    vec2 uv = tex_coords;
+    if (flip_x) {
+        uv.x = 1.0 - uv.x;
+    }
+    if (flip_y) {
+        uv.y = 1.0 - uv.y;
+    }
+    v_tex_coords = source_rect.xy + uv * source_rect.zw;
+    v_effect_coords = tex_coords;
 
     gl_Position = matrix * vec4(position, 0.0, 1.0);
-}
\ No newline at end of file
+}
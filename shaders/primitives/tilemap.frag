@@ -0,0 +1,51 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+// one texel per tile, 0 is empty and everything else is the tile index + 1
+// the first row is the top of the map
+uniform usampler2D tiles;
+// the sheet of the channel that is being drawn
+uniform sampler2D tileset;
+// in pixels
+uniform ivec2 tile_size;
+// the number of tiles in a row of the sheet
+uniform int columns;
+// the pixels around the edge of the sheet and between the tiles
+uniform int margin;
+uniform int spacing;
+
+void main() {
+    ivec2 map_size = textureSize(tiles, 0);
+    ivec2 sheet_size = textureSize(tileset, 0);
+
+    vec2 map_position = v_tex_coords * vec2(map_size);
+    ivec2 tile_position = clamp(ivec2(floor(map_position)), ivec2(0), map_size - 1);
+    // the texture coordinates start at the bottom, the map starts at the top
+    tile_position.y = map_size.y - 1 - tile_position.y;
+
+    uint tile = texelFetch(tiles, tile_position, 0).r;
+    if (tile == 0u) {
+        discard;
+    }
+    int index = int(tile) - 1;
+
+    // the pixel inside of the tile, counted from the top left
+    vec2 local = fract(map_position);
+    ivec2 local_pixel = clamp(
+        ivec2(floor(vec2(local.x, 1.0 - local.y) * vec2(tile_size))),
+        ivec2(0),
+        tile_size - 1
+    );
+
+    ivec2 sheet_pixel = ivec2(margin) + ivec2(index % columns, index / columns) * (tile_size + spacing) + local_pixel;
+    // the sheet is stored bottom to top as well
+    sheet_pixel.y = sheet_size.y - 1 - sheet_pixel.y;
+
+    vec4 new_color = texelFetch(tileset, sheet_pixel, 0);
+    if (new_color.a == 0.0) {
+        discard;
+    }
+    color = new_color;
+}
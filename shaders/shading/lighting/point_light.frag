@@ -6,6 +6,8 @@ out vec4 color;
 uniform sampler2D heightmap;
 uniform sampler2D albedomap;
 uniform sampler2D shadow_strength_map;
+uniform sampler2D layer_map;
+uniform uint light_mask;
 uniform vec3 light_pos;
 uniform vec3 light_color;
 uniform float light_intensity;
@@ -121,6 +123,12 @@ void main() {
 		discard;
 	}
 
+	// only light the pixels on the layers in the mask
+	uint layer = uint(texture(layer_map, v_tex_coords).r * 255.0 + 0.5);
+	if ((light_mask & (1u << layer)) == 0u) {
+		discard;
+	}
+
     vec3 new_light_pos = vec3(textureSize(albedomap, 0) * (light_pos.xy), light_pos.z);
     vec3 new_v_tex_coords = vec3(textureSize(albedomap, 0) * v_tex_coords, texture(heightmap, v_tex_coords).r);
     
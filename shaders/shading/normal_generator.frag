@@ -23,5 +23,6 @@ void main() {
     vec2 dir = -vec2(dx, dy)* textureSize(heightmap, 0) * 0.5;
 
     vec3 normal = normalize(vec3(dir, 1.0));
-    color = vec4(normal, 1.0);
+    // stored the same way as a normal map image, from 0.0 to 1.0
+    color = vec4(normal * 0.5 + 0.5, 1.0);
 }
\ No newline at end of file
@@ -160,7 +160,8 @@ void main() {
 	vec3 camera_pos = vec3(0.5, 0.5, camera_z);
 	
 	vec3 incident = normalize(new_v_tex_coords - camera_pos);
-	vec3 normal = normalize(texture(normalmap, v_tex_coords).xyz);
+	// the normals are stored as normal * 0.5 + 0.5
+	vec3 normal = normalize(texture(normalmap, v_tex_coords).xyz * 2.0 - 1.0);
 	vec3 reflected = reflect(incident, normal);
 
 	vec3 scaling = vec3(textureSize(heightmap, 0), 1.0);
@@ -4,7 +4,9 @@ in vec2 v_tex_coords;
 out vec4 color;
 
 uniform sampler2D image;
+// how far the camera is from the pixel it was snapped to, in image pixels
+uniform vec2 offset;
 
 void main() {
-    color = texture(image, v_tex_coords);
+    color = texture(image, v_tex_coords + offset / vec2(textureSize(image, 0)));
 }
\ No newline at end of file
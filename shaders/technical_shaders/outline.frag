@@ -0,0 +1,49 @@
+#version 140
+
+in vec2 v_tex_coords;
+out vec4 color;
+
+// the albedo of the object being outlined
+uniform sampler2D object_albedo;
+// the height of the object being outlined
+uniform sampler2D object_height;
+// the height of the whole scene
+uniform sampler2D scene_height;
+uniform vec4 outline_color;
+uniform int thickness;
+// only draw where something taller is covering the object
+uniform bool occluded_only;
+// fills the covered parts of the object, if the alpha is 0.0 nothing is filled
+uniform vec4 silhouette_color;
+
+bool is_occluded(vec2 coords, float object_height_value) {
+    return texture(scene_height, coords).r > object_height_value + 0.01;
+}
+
+void main() {
+    vec2 pixel_size = 1.0 / textureSize(object_albedo, 0);
+
+    if (texture(object_albedo, v_tex_coords).a > 0.0) {
+        // inside of the object, only the silhouette is drawn here
+        if (occluded_only && silhouette_color.a > 0.0 && is_occluded(v_tex_coords, texture(object_height, v_tex_coords).r)) {
+            color = silhouette_color;
+            return;
+        }
+        discard;
+    }
+
+    // outside of the object, check if any pixel within the thickness is part of the object
+    for (int x = -thickness; x <= thickness; x++) {
+        for (int y = -thickness; y <= thickness; y++) {
+            vec2 neighbor = v_tex_coords + vec2(x, y) * pixel_size;
+            if (texture(object_albedo, neighbor).a > 0.0) {
+                if (!occluded_only || is_occluded(v_tex_coords, texture(object_height, neighbor).r)) {
+                    color = outline_color;
+                    return;
+                }
+            }
+        }
+    }
+
+    discard;
+}
\ No newline at end of file
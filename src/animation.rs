@@ -1,23 +1,69 @@
 use glium::texture;
 
+use crate::clock::Clock;
 use crate::load_image;
-use crate::primitives::{Normal, Sprite, Texture, TextureInput};
+use crate::primitives::{Dissolve, Normal, PaletteSwap, Sprite, Texture, TextureInput};
 use crate::TextureHandle;
 use crate::Transform;
 use crate::{drawable_object::Drawable, LumenpyxProgram};
 use glium::Surface;
+use rustc_hash::FxHashMap;
 use std::iter::zip;
+use std::rc::Rc;
 use std::time::{Duration, Instant};
 
+/// How an animation plays through its frames
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlaybackMode {
+    /// Play the frames once, nothing is drawn after the last frame
+    Once,
+    /// Play the frames over and over
+    Loop,
+    /// Play the frames forwards then backwards, over and over
+    PingPong,
+    /// Play the frames backwards, over and over
+    Reverse,
+    /// Play the frames once and keep showing the last frame
+    HoldLastFrame,
+}
+
+/// the most loops of events that are fired at once when a lot of time passes between polls,
+/// not counting the loop the animation is on
+const MAX_EVENT_LOOPS: u128 = 4;
+
+/// Something that happened while an animation was playing
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnimationEvent {
+    /// A named event added with add_event, fired when its frame started
+    Frame { name: String, frame: usize },
+    /// A non looping animation played its last frame
+    Finished,
+    /// So much time passed between polls that this many loops started without their events being fired,
+    /// only the events of the last few loops are fired
+    SkippedLoops { loops: u128 },
+}
+
 #[derive(Clone)]
 pub struct Animation {
     sprites: Vec<Sprite>,
-    time_between_frames: Duration,
+    /// how long each frame is shown for, one for every sprite
+    frame_durations: Vec<Duration>,
     time: AnimationTimeElapsed,
     shadow_strength: f32,
     pub transform: Transform,
-    /// If true, the animation will loop, if false, the animation will not draw after the last frame
-    loop_animation: bool,
+    playback_mode: PlaybackMode,
+    /// how fast the animation plays, 1.0 is normal speed
+    speed: f32,
+    /// the raw time and the animation time when the speed was last changed, in nanoseconds
+    speed_anchor: (u128, u128),
+    /// the animation time the animation was paused at, None if it is playing
+    paused_at: Option<u128>,
+    /// named events that fire when the frame at the index starts
+    events: Vec<(usize, String)>,
+    /// the animation time events were last polled at, None if they haven't been polled since the start
+    last_event_time: Option<u128>,
+    tint: [f32; 4],
+    opacity: f32,
 }
 
 impl Animation {
@@ -82,15 +128,16 @@ impl Animation {
             sprites.push(sprite);
         }
 
+        let animation = Self::new_from_sprites(
+            sprites,
+            vec![time_between_frames; num_frames],
+            transform,
+            loop_animation,
+            program.get_clock(),
+        );
+
         (
-            Self {
-                sprites,
-                time_between_frames,
-                time: Instant::now().into(),
-                shadow_strength: 0.5,
-                transform,
-                loop_animation,
-            },
+            animation,
             albedo_handles,
             height_handles,
             roughness_handles,
@@ -161,15 +208,16 @@ impl Animation {
             sprites.push(sprite);
         }
 
+        let animation = Self::new_from_sprites(
+            sprites,
+            vec![time_between_frames; num_frames],
+            transform,
+            loop_animation,
+            program.get_clock(),
+        );
+
         (
-            Self {
-                sprites,
-                time_between_frames,
-                time: Instant::now().into(),
-                shadow_strength: 0.5,
-                transform,
-                loop_animation,
-            },
+            animation,
             albedo_handles,
             height_handles,
             roughness_handles,
@@ -200,27 +248,421 @@ impl Animation {
             sprites.push(sprite);
         }
 
+        let frame_durations = vec![time_between_frames; sprites.len()];
+        Self::new_from_sprites(
+            sprites,
+            frame_durations,
+            transform,
+            loop_animation,
+            program.get_clock(),
+        )
+    }
+
+    /// Make an animation out of sprites that are already loaded, each frame is shown for its own duration.
+    /// The animation starts now on the clock, usually `program.get_clock()`
+    pub fn new_from_sprites(
+        sprites: Vec<Sprite>,
+        frame_durations: Vec<Duration>,
+        transform: Transform,
+        loop_animation: bool,
+        clock: Rc<dyn Clock>,
+    ) -> Self {
+        if sprites.len() != frame_durations.len() {
+            panic!("Every frame of an animation needs a duration");
+        }
+
         Self {
             sprites,
-            time_between_frames,
-            time: Instant::now().into(),
+            frame_durations,
+            time: AnimationTimeElapsed::from_clock(clock),
             shadow_strength: 0.5,
             transform,
-            loop_animation,
+            playback_mode: if loop_animation {
+                PlaybackMode::Loop
+            } else {
+                PlaybackMode::Once
+            },
+            speed: 1.0,
+            speed_anchor: (0, 0),
+            paused_at: None,
+            events: vec![],
+            last_event_time: None,
+            tint: [1.0, 1.0, 1.0, 1.0],
+            opacity: 1.0,
         }
     }
 
+    /// Start the animation over from the first frame, using the same clock it was using
     pub fn restart_animation(&mut self) {
-        self.time = Instant::now().into();
+        let time = match &self.time {
+            AnimationTimeElapsed::Clock(clock, _) => {
+                AnimationTimeElapsed::from_clock(clock.clone())
+            }
+            _ => Instant::now().into(),
+        };
+        self.set_time(time);
+    }
+
+    /// Play the animation from the clock starting now, e.g. `program.get_clock()` or a clock used for replays
+    pub fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.set_time(AnimationTimeElapsed::from_clock(clock));
     }
 
     pub fn set_time(&mut self, time: AnimationTimeElapsed) {
         self.time = time;
+        self.speed_anchor = (0, 0);
+        self.last_event_time = None;
+        if self.paused_at.is_some() {
+            self.paused_at = Some(0);
+        }
+    }
+
+    pub fn set_playback_mode(&mut self, playback_mode: PlaybackMode) {
+        self.playback_mode = playback_mode;
+    }
+
+    pub fn get_playback_mode(&self) -> PlaybackMode {
+        self.playback_mode
+    }
+
+    /// Set how fast the animation plays, 1.0 is normal speed, 2.0 is twice as fast and 0.5 is half as fast.
+    /// Changing the speed does not make the animation jump to a different frame
+    pub fn set_speed(&mut self, speed: f32) {
+        if speed < 0.0 {
+            panic!(
+                "animation speed cannot be negative, use PlaybackMode::Reverse to play backwards"
+            );
+        }
+
+        self.speed_anchor = (self.time.as_nanos(), self.get_elapsed_nanos());
+        self.speed = speed;
+    }
+
+    pub fn get_speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Stop the animation on the frame it is on until resume is called
+    pub fn pause(&mut self) {
+        if self.paused_at.is_none() {
+            self.paused_at = Some(self.get_elapsed_nanos());
+        }
+    }
+
+    /// Continue the animation from where it was paused
+    pub fn resume(&mut self) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.speed_anchor = (self.time.as_nanos(), paused_at);
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    pub fn get_frame_count(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn get_frame_durations(&self) -> &[Duration] {
+        &self.frame_durations
+    }
+
+    /// Set how long each frame is shown for, there must be one duration for every frame
+    pub fn set_frame_durations(&mut self, frame_durations: Vec<Duration>) {
+        if frame_durations.len() != self.sprites.len() {
+            panic!("Every frame of an animation needs a duration");
+        }
+
+        self.frame_durations = frame_durations;
+    }
+
+    /// Set how long a single frame is shown for
+    pub fn set_frame_duration(&mut self, frame: usize, duration: Duration) {
+        self.frame_durations[frame] = duration;
+    }
+
+    /// The index of the frame that is drawn right now, None if the animation is over
+    pub fn get_current_frame(&self) -> Option<usize> {
+        let total_time: u128 = self
+            .frame_durations
+            .iter()
+            .map(|duration| duration.as_nanos())
+            .sum();
+        if total_time == 0 {
+            panic!("time between frames on an animation cannot be set to 0");
+        }
+
+        let cycle = self.get_cycle();
+        let cycle_time: u128 = cycle.iter().map(|(_, duration)| duration).sum();
+
+        let mut time = self.get_elapsed_nanos();
+        if time >= total_time {
+            match self.playback_mode {
+                PlaybackMode::Once => return None,
+                PlaybackMode::HoldLastFrame => return Some(self.sprites.len() - 1),
+                PlaybackMode::Loop | PlaybackMode::PingPong | PlaybackMode::Reverse => {
+                    time %= cycle_time;
+                }
+            }
+        }
+
+        // find the frame the time lands in
+        for (frame, duration) in cycle {
+            if time < duration {
+                return Some(frame);
+            }
+            time -= duration;
+        }
+
+        None
+    }
+
+    /// Add a named event that fires every time the frame at the index starts
+    pub fn add_event(&mut self, frame: usize, name: &str) {
+        if frame >= self.sprites.len() {
+            panic!(
+                "Cannot add an event to frame {}, the animation only has {} frames",
+                frame,
+                self.sprites.len()
+            );
+        }
+
+        self.events.push((frame, name.to_string()));
+    }
+
+    /// Remove every event with the name
+    pub fn remove_event(&mut self, name: &str) {
+        self.events.retain(|(_, event_name)| event_name != name);
+    }
+
+    pub fn clear_events(&mut self) {
+        self.events.clear();
+    }
+
+    /// Get the events that fired since the last time this was called, in the order they fired.
+    /// Call this once per update, events from the first frame fire on the first call.
+    /// Only the events of the last 5 loops are fired (the loop it is on and the 4 before it),
+    /// any loops before that are reported with a single SkippedLoops event at the start
+    pub fn poll_events(&mut self) -> Vec<AnimationEvent> {
+        let now = self.get_elapsed_nanos();
+        let mut last = self.last_event_time;
+        self.last_event_time = Some(now);
+
+        // the time went backwards, so start over
+        if last.is_some_and(|last| now < last) {
+            last = None;
+        }
+
+        if last == Some(now) {
+            return vec![];
+        }
+
+        let cycle = self.get_cycle();
+        let cycle_time: u128 = cycle.iter().map(|(_, duration)| duration).sum();
+        if cycle_time == 0 {
+            panic!("time between frames on an animation cannot be set to 0");
+        }
+
+        let looping = match self.playback_mode {
+            PlaybackMode::Once | PlaybackMode::HoldLastFrame => false,
+            PlaybackMode::Loop | PlaybackMode::PingPong | PlaybackMode::Reverse => true,
+        };
+
+        // a frame fires when it starts if last < start <= now, or from the very start if never polled
+        let fires = |start: u128| match last {
+            Some(last) => last < start && start <= now,
+            None => start <= now,
+        };
+
+        let mut events = vec![];
+        let first_cycle = match last {
+            Some(last) if looping => last / cycle_time,
+            _ => 0,
+        };
+        let last_cycle = if looping { now / cycle_time } else { 0 };
+        // if a lot of time passed, don't fire every event of every missed loop
+        let fired_from = first_cycle.max(last_cycle.saturating_sub(MAX_EVENT_LOOPS));
+        // the loop of the last poll already started, so it isn't counted as skipped
+        let skipped_loops = (fired_from - first_cycle).saturating_sub(last.is_some() as u128);
+        let first_cycle = fired_from;
+        if skipped_loops > 0 {
+            events.push(AnimationEvent::SkippedLoops {
+                loops: skipped_loops,
+            });
+        }
+
+        for cycle_index in first_cycle..=last_cycle {
+            let mut start = cycle_index * cycle_time;
+            for (frame, duration) in &cycle {
+                if fires(start) {
+                    for (event_frame, name) in &self.events {
+                        if event_frame == frame {
+                            events.push(AnimationEvent::Frame {
+                                name: name.clone(),
+                                frame: *frame,
+                            });
+                        }
+                    }
+                }
+                start += duration;
+            }
+        }
+
+        if !looping && fires(cycle_time) {
+            events.push(AnimationEvent::Finished);
+        }
+
+        events
+    }
+
+    /// Call the callback for every event that fired since the last time events were polled
+    pub fn process_events<F: FnMut(&AnimationEvent)>(&mut self, mut callback: F) {
+        for event in self.poll_events() {
+            callback(&event);
+        }
+    }
+
+    /// Whether a non looping animation has played all its frames
+    pub fn is_finished(&self) -> bool {
+        match self.playback_mode {
+            PlaybackMode::Once | PlaybackMode::HoldLastFrame => {
+                let total_time: u128 = self
+                    .frame_durations
+                    .iter()
+                    .map(|duration| duration.as_nanos())
+                    .sum();
+                self.get_elapsed_nanos() >= total_time
+            }
+            PlaybackMode::Loop | PlaybackMode::PingPong | PlaybackMode::Reverse => false,
+        }
+    }
+
+    /// how far into the animation we are in nanoseconds, with the speed and pausing taken into account
+    fn get_elapsed_nanos(&self) -> u128 {
+        if let Some(paused_at) = self.paused_at {
+            return paused_at;
+        }
+
+        let (anchor_raw, anchor_time) = self.speed_anchor;
+        let raw = self.time.as_nanos();
+        if raw < anchor_raw {
+            // the time went backwards, e.g. set by hand, so the anchor is no longer valid
+            return (raw as f64 * self.speed as f64) as u128;
+        }
+
+        anchor_time + ((raw - anchor_raw) as f64 * self.speed as f64) as u128
+    }
+
+    /// the frames and their durations in nanoseconds in the order they are played in one cycle of the animation
+    fn get_cycle(&self) -> Vec<(usize, u128)> {
+        let forward = self
+            .frame_durations
+            .iter()
+            .map(|duration| duration.as_nanos())
+            .enumerate();
+
+        match self.playback_mode {
+            PlaybackMode::Once | PlaybackMode::Loop | PlaybackMode::HoldLastFrame => {
+                forward.collect()
+            }
+            PlaybackMode::Reverse => forward.rev().collect(),
+            PlaybackMode::PingPong => {
+                let forward: Vec<(usize, u128)> = forward.collect();
+                let mut cycle = forward.clone();
+                // don't repeat the first and last frames when turning around
+                if forward.len() > 2 {
+                    cycle.extend(forward[1..forward.len() - 1].iter().rev());
+                }
+                cycle
+            }
+        }
     }
 
     pub fn set_shadow_strength(&mut self, strength: f32) {
         self.shadow_strength = strength;
     }
+
+    /// Set the color the albedo of every frame is multiplied by, [1.0, 1.0, 1.0, 1.0] leaves the animation unchanged
+    pub fn set_tint(&mut self, tint: [f32; 4]) {
+        self.tint = tint;
+        for sprite in &mut self.sprites {
+            sprite.set_tint(tint);
+        }
+    }
+
+    pub fn get_tint(&self) -> [f32; 4] {
+        self.tint
+    }
+
+    /// Set the opacity of every frame, 0.0 is invisible and 1.0 is fully visible.
+    /// The shadow strength is scaled by the opacity as well
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity;
+        for sprite in &mut self.sprites {
+            sprite.set_opacity(opacity);
+        }
+    }
+
+    pub fn get_opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    /// Set the part of every frame that is drawn in 0.0 - 1.0 texture coordinates.
+    /// The rect is x, y, width, height with (0.0, 0.0) being the top left of the frame
+    pub fn set_source_rect(&mut self, source_rect: [f32; 4]) {
+        for sprite in &mut self.sprites {
+            sprite.set_source_rect(source_rect);
+        }
+    }
+
+    /// Mirror every frame horizontally
+    pub fn set_flip_x(&mut self, flip_x: bool) {
+        for sprite in &mut self.sprites {
+            sprite.set_flip_x(flip_x);
+        }
+    }
+
+    /// Mirror every frame vertically
+    pub fn set_flip_y(&mut self, flip_y: bool) {
+        for sprite in &mut self.sprites {
+            sprite.set_flip_y(flip_y);
+        }
+    }
+
+    /// Set the point that every frame rotates around.
+    /// [0.0, 0.0] is the bottom left of the frame, [1.0, 1.0] is the top right, the default is [0.5, 0.5]
+    pub fn set_pivot(&mut self, pivot: [f32; 2]) {
+        for sprite in &mut self.sprites {
+            sprite.set_pivot(pivot);
+        }
+    }
+
+    /// Set the dissolve of every frame, None turns the effect off
+    pub fn set_dissolve(&mut self, dissolve: Option<Dissolve>) {
+        for sprite in &mut self.sprites {
+            sprite.set_dissolve(dissolve);
+        }
+    }
+
+    /// Set how far every frame is dissolved, 0.0 is fully visible and 1.0 is gone
+    pub fn set_dissolve_threshold(&mut self, threshold: f32) {
+        for sprite in &mut self.sprites {
+            sprite.set_dissolve_threshold(threshold);
+        }
+    }
+
+    /// Set the palette swap of every frame, None turns the effect off
+    pub fn set_palette_swap(&mut self, palette_swap: Option<PaletteSwap>) {
+        for sprite in &mut self.sprites {
+            sprite.set_palette_swap(palette_swap);
+        }
+    }
+
+    /// the sprite of the frame that should be drawn right now, None if the animation is over
+    fn get_current_sprite(&self) -> Option<&Sprite> {
+        self.get_current_frame().map(|frame| &self.sprites[frame])
+    }
 }
 
 #[derive(Clone)]
@@ -228,9 +670,17 @@ pub enum AnimationTimeElapsed {
     Time(Duration),
     SecondsSinceStart(f32),
     TimeSinceInstant(Instant),
+    /// The time on the clock since the time it was at when the animation started
+    Clock(Rc<dyn Clock>, Duration),
 }
 
 impl AnimationTimeElapsed {
+    /// Count the time on the clock from now
+    pub fn from_clock(clock: Rc<dyn Clock>) -> Self {
+        let start = clock.now();
+        AnimationTimeElapsed::Clock(clock, start)
+    }
+
     pub fn as_nanos(&self) -> u128 {
         match self {
             AnimationTimeElapsed::Time(time) => time.as_nanos(),
@@ -238,6 +688,9 @@ impl AnimationTimeElapsed {
                 (*seconds as f64 * 1_000_000_000.0) as u128
             }
             AnimationTimeElapsed::TimeSinceInstant(instant) => instant.elapsed().as_nanos(),
+            AnimationTimeElapsed::Clock(clock, start) => {
+                clock.now().saturating_sub(*start).as_nanos()
+            }
         }
     }
 
@@ -246,6 +699,9 @@ impl AnimationTimeElapsed {
             AnimationTimeElapsed::Time(time) => time.as_secs_f32(),
             AnimationTimeElapsed::SecondsSinceStart(seconds) => *seconds,
             AnimationTimeElapsed::TimeSinceInstant(instant) => instant.elapsed().as_secs_f32(),
+            AnimationTimeElapsed::Clock(clock, start) => {
+                clock.now().saturating_sub(*start).as_secs_f32()
+            }
         }
     }
 }
@@ -275,21 +731,9 @@ impl Drawable for Animation {
         transform: &Transform,
         albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
     ) {
-        let mut current_frame_num = self
-            .time
-            .as_nanos()
-            .checked_div(self.time_between_frames.as_nanos())
-            .expect("time between frames on an animation cannot be set to 0");
-
-        if current_frame_num as usize >= self.sprites.len() {
-            if self.loop_animation {
-                current_frame_num = current_frame_num % self.sprites.len() as u128;
-            } else {
-                return;
-            }
-        }
-
-        let current_frame = &self.sprites[current_frame_num as usize];
+        let Some(current_frame) = self.get_current_sprite() else {
+            return;
+        };
 
         current_frame.draw_albedo(program, transform, albedo_framebuffer);
     }
@@ -300,21 +744,9 @@ impl Drawable for Animation {
         transform: &Transform,
         height_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
     ) {
-        let mut current_frame_num = self
-            .time
-            .as_nanos()
-            .checked_div(self.time_between_frames.as_nanos())
-            .expect("time between frames on an animation cannot be set to 0");
-
-        if current_frame_num as usize >= self.sprites.len() {
-            if self.loop_animation {
-                current_frame_num = current_frame_num % self.sprites.len() as u128;
-            } else {
-                return;
-            }
-        }
-
-        let current_frame = &self.sprites[current_frame_num as usize];
+        let Some(current_frame) = self.get_current_sprite() else {
+            return;
+        };
 
         current_frame.draw_height(program, transform, height_framebuffer);
     }
@@ -325,21 +757,9 @@ impl Drawable for Animation {
         transform: &Transform,
         roughness_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
     ) {
-        let mut current_frame_num = self
-            .time
-            .as_nanos()
-            .checked_div(self.time_between_frames.as_nanos())
-            .expect("time between frames on an animation cannot be set to 0");
-
-        if current_frame_num as usize >= self.sprites.len() {
-            if self.loop_animation {
-                current_frame_num = current_frame_num % self.sprites.len() as u128;
-            } else {
-                return;
-            }
-        }
-
-        let current_frame = &self.sprites[current_frame_num as usize];
+        let Some(current_frame) = self.get_current_sprite() else {
+            return;
+        };
 
         current_frame.draw_roughness(program, transform, roughness_framebuffer);
     }
@@ -350,25 +770,26 @@ impl Drawable for Animation {
         transform: &Transform,
         normal_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
     ) {
-        let mut current_frame_num = self
-            .time
-            .as_nanos()
-            .checked_div(self.time_between_frames.as_nanos())
-            .expect("time between frames on an animation cannot be set to 0");
-
-        if current_frame_num as usize >= self.sprites.len() {
-            if self.loop_animation {
-                current_frame_num = current_frame_num % self.sprites.len() as u128;
-            } else {
-                return;
-            }
-        }
-
-        let current_frame = &self.sprites[current_frame_num as usize];
+        let Some(current_frame) = self.get_current_sprite() else {
+            return;
+        };
 
         current_frame.draw_normal(program, transform, normal_framebuffer);
     }
 
+    fn draw_emissive(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        emissive_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        let Some(current_frame) = self.get_current_sprite() else {
+            return;
+        };
+
+        current_frame.draw_emissive(program, transform, emissive_framebuffer);
+    }
+
     fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
         for sprite in &self.sprites {
             sprite.try_load_shaders(program);
@@ -380,7 +801,7 @@ impl Drawable for Animation {
     }
 
     fn get_recieve_shadows_strength(&self) -> f32 {
-        self.shadow_strength
+        self.shadow_strength * self.opacity
     }
 
     fn set_transform(&mut self, transform: Transform) {
@@ -632,10 +1053,121 @@ fn load_tex_from_images_normal(
     }
 }
 
+/// A value the user sets on a state machine that transitions can check
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnimationParameter {
+    Bool(bool),
+    Float(f32),
+    /// Like a bool, but it's reset to false after a transition uses it
+    Trigger(bool),
+}
+
+/// Something that has to be true for a transition to happen
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransitionCondition {
+    /// The bool parameter is equal to the value
+    Bool(String, bool),
+    /// The float parameter is greater than the value
+    FloatGreater(String, f32),
+    /// The float parameter is less than the value
+    FloatLess(String, f32),
+    /// The trigger parameter is set, it is reset when the transition happens
+    Trigger(String),
+}
+
+/// A rule for moving from one animation in a state machine to another
+#[derive(Clone, Debug)]
+pub struct AnimationTransition {
+    /// None means the transition can happen from any animation
+    from: Option<String>,
+    to: String,
+    conditions: Vec<TransitionCondition>,
+    when_finished: bool,
+    crossfade: Duration,
+}
+
+impl AnimationTransition {
+    /// Make a transition between the animations with these names.
+    /// A transition from an animation to itself restarts it, so it only happens if it has a trigger condition
+    pub fn new(from: &str, to: &str) -> Self {
+        Self {
+            from: Some(from.to_string()),
+            to: to.to_string(),
+            conditions: vec![],
+            when_finished: false,
+            crossfade: Duration::ZERO,
+        }
+    }
+
+    /// Make a transition that can happen from any animation other than the one it goes to
+    pub fn from_any(to: &str) -> Self {
+        Self {
+            from: None,
+            to: to.to_string(),
+            conditions: vec![],
+            when_finished: false,
+            crossfade: Duration::ZERO,
+        }
+    }
+
+    /// Add a condition, every condition has to be true for the transition to happen
+    pub fn with_condition(mut self, condition: TransitionCondition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    /// Only transition once the animation it comes from has finished.
+    /// Looping animations never finish
+    pub fn with_when_finished(mut self, when_finished: bool) -> Self {
+        self.when_finished = when_finished;
+        self
+    }
+
+    /// Fade between the two animations over this long instead of switching instantly
+    pub fn with_crossfade(mut self, crossfade: Duration) -> Self {
+        self.crossfade = crossfade;
+        self
+    }
+
+    pub fn get_from(&self) -> Option<&str> {
+        self.from.as_deref()
+    }
+
+    pub fn get_to(&self) -> &str {
+        &self.to
+    }
+
+    pub fn get_conditions(&self) -> &[TransitionCondition] {
+        &self.conditions
+    }
+
+    pub fn get_when_finished(&self) -> bool {
+        self.when_finished
+    }
+
+    pub fn get_crossfade(&self) -> Duration {
+        self.crossfade
+    }
+}
+
+/// a fade from the animation we left to the current one
+struct Crossfade {
+    from: usize,
+    duration: Duration,
+    /// the opacities of the two animations before the fade started
+    from_opacity: f32,
+    to_opacity: f32,
+}
+
 pub struct AnimationStateMachine {
     transform: Transform, // not the most efficient way to do this, but it works
     animations: Vec<Animation>,
     current_animation: usize,
+    /// the index of every animation that has a name
+    names: FxHashMap<String, usize>,
+    parameters: FxHashMap<String, AnimationParameter>,
+    transitions: Vec<AnimationTransition>,
+    crossfade: Option<Crossfade>,
 }
 
 impl AnimationStateMachine {
@@ -644,13 +1176,239 @@ impl AnimationStateMachine {
             transform: Transform::default(),
             animations,
             current_animation: 0,
+            names: FxHashMap::default(),
+            parameters: FxHashMap::default(),
+            transitions: vec![],
+            crossfade: None,
+        }
+    }
+
+    /// Make a state machine where every animation can be found by its name
+    pub fn new_named(animations: Vec<(String, Animation)>) -> Self {
+        let mut state_machine = Self::new(vec![]);
+        for (name, animation) in animations {
+            state_machine.add_named_animation(&name, animation);
         }
+        state_machine
+    }
+
+    /// Add an animation to the end of the state machine, returns its index
+    pub fn add_named_animation(&mut self, name: &str, animation: Animation) -> usize {
+        self.animations.push(animation);
+        let index = self.animations.len() - 1;
+        self.names.insert(name.to_string(), index);
+        index
+    }
+
+    /// Get the index of the animation with this name
+    pub fn get_animation_index(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
     }
 
     pub fn set_current_animation(&mut self, index: usize) {
+        self.end_crossfade();
         self.current_animation = index;
     }
 
+    /// Switch to the animation with this name, panics if there isn't one
+    pub fn set_current_animation_by_name(&mut self, name: &str) {
+        let index = self
+            .get_animation_index(name)
+            .unwrap_or_else(|| panic!("No animation named {}", name));
+        self.set_current_animation(index);
+    }
+
+    pub fn get_current_animation(&self) -> usize {
+        self.current_animation
+    }
+
+    /// Get the name of the current animation, None if it doesn't have one
+    pub fn get_current_animation_name(&self) -> Option<&str> {
+        self.names
+            .iter()
+            .find(|(_, index)| **index == self.current_animation)
+            .map(|(name, _)| name.as_str())
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.parameters
+            .insert(name.to_string(), AnimationParameter::Bool(value));
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.parameters
+            .insert(name.to_string(), AnimationParameter::Float(value));
+    }
+
+    /// Set a trigger, it stays set until a transition uses it or reset_trigger is called
+    pub fn set_trigger(&mut self, name: &str) {
+        self.parameters
+            .insert(name.to_string(), AnimationParameter::Trigger(true));
+    }
+
+    pub fn reset_trigger(&mut self, name: &str) {
+        self.parameters
+            .insert(name.to_string(), AnimationParameter::Trigger(false));
+    }
+
+    pub fn get_parameter(&self, name: &str) -> Option<AnimationParameter> {
+        self.parameters.get(name).copied()
+    }
+
+    /// Add a transition, transitions are checked in the order they were added when update is called.
+    /// Panics if an animation it uses doesn't exist
+    pub fn add_transition(&mut self, transition: AnimationTransition) {
+        if let Some(from) = &transition.from {
+            if self.get_animation_index(from).is_none() {
+                panic!("No animation named {}", from);
+            }
+        }
+        if self.get_animation_index(&transition.to).is_none() {
+            panic!("No animation named {}", transition.to);
+        }
+
+        self.transitions.push(transition);
+    }
+
+    pub fn get_transitions(&self) -> &[AnimationTransition] {
+        &self.transitions
+    }
+
+    pub fn clear_transitions(&mut self) {
+        self.transitions.clear();
+    }
+
+    /// Check the transitions and move to another animation if one of them can happen, and update any crossfade.
+    /// Call this once per update, returns true if the animation changed
+    pub fn update(&mut self) -> bool {
+        let transition = self
+            .transitions
+            .iter()
+            .find(|transition| self.can_transition(transition))
+            .cloned();
+
+        let mut transitioned = false;
+        if let Some(transition) = transition {
+            for condition in &transition.conditions {
+                if let TransitionCondition::Trigger(name) = condition {
+                    self.reset_trigger(name);
+                }
+            }
+
+            let to = self
+                .get_animation_index(&transition.to)
+                .expect("Transition to an animation that doesn't exist");
+            let from = self.current_animation;
+            self.set_current_animation(to);
+            self.animations[to].restart_animation();
+
+            if !transition.crossfade.is_zero() && from != to {
+                self.crossfade = Some(Crossfade {
+                    from,
+                    duration: transition.crossfade,
+                    from_opacity: self.animations[from].get_opacity(),
+                    to_opacity: self.animations[to].get_opacity(),
+                });
+            }
+            transitioned = true;
+        }
+
+        self.update_crossfade();
+        transitioned
+    }
+
+    fn can_transition(&self, transition: &AnimationTransition) -> bool {
+        let current = self.current_animation;
+        let to = self.get_animation_index(&transition.to);
+        match &transition.from {
+            Some(from) => {
+                if self.get_animation_index(from) != Some(current) {
+                    return false;
+                }
+            }
+            None => {
+                if to == Some(current) {
+                    return false;
+                }
+            }
+        }
+
+        // going back into the same animation restarts it, so only do that when a trigger asks for it,
+        // otherwise a condition that stays true would restart it every update
+        let has_trigger = transition
+            .conditions
+            .iter()
+            .any(|condition| matches!(condition, TransitionCondition::Trigger(_)));
+        if to == Some(current) && !has_trigger {
+            return false;
+        }
+
+        if transition.when_finished && !self.animations[current].is_finished() {
+            return false;
+        }
+
+        transition.conditions.iter().all(|condition| match condition {
+            TransitionCondition::Bool(name, value) => {
+                self.get_parameter(name) == Some(AnimationParameter::Bool(*value))
+            }
+            TransitionCondition::FloatGreater(name, value) => {
+                matches!(self.get_parameter(name), Some(AnimationParameter::Float(v)) if v > *value)
+            }
+            TransitionCondition::FloatLess(name, value) => {
+                matches!(self.get_parameter(name), Some(AnimationParameter::Float(v)) if v < *value)
+            }
+            TransitionCondition::Trigger(name) => {
+                self.get_parameter(name) == Some(AnimationParameter::Trigger(true))
+            }
+        })
+    }
+
+    /// set the opacities of the two animations for how far the crossfade is
+    fn update_crossfade(&mut self) {
+        let Some(crossfade) = &self.crossfade else {
+            return;
+        };
+
+        let elapsed = self.animations[self.current_animation].get_elapsed_nanos() as f64;
+        let progress = (elapsed / crossfade.duration.as_nanos() as f64).min(1.0) as f32;
+        if progress >= 1.0 {
+            self.end_crossfade();
+            return;
+        }
+
+        let (from, from_opacity, to_opacity) =
+            (crossfade.from, crossfade.from_opacity, crossfade.to_opacity);
+        self.animations[from].set_opacity(from_opacity * (1.0 - progress));
+        self.animations[self.current_animation].set_opacity(to_opacity * progress);
+    }
+
+    /// stop the crossfade and put the opacities back to what they were
+    fn end_crossfade(&mut self) {
+        if let Some(crossfade) = self.crossfade.take() {
+            self.animations[crossfade.from].set_opacity(crossfade.from_opacity);
+            self.animations[self.current_animation].set_opacity(crossfade.to_opacity);
+        }
+    }
+
+    /// the animations to draw, the one being faded out first
+    fn get_drawn_animations(&self) -> Vec<&Animation> {
+        match &self.crossfade {
+            Some(crossfade) if crossfade.from != self.current_animation => vec![
+                &self.animations[crossfade.from],
+                &self.animations[self.current_animation],
+            ],
+            _ => vec![&self.animations[self.current_animation]],
+        }
+    }
+
+    pub fn get_animation(&self, index: usize) -> Option<&Animation> {
+        self.animations.get(index)
+    }
+
+    pub fn get_animation_mut(&mut self, index: usize) -> Option<&mut Animation> {
+        self.animations.get_mut(index)
+    }
+
     /// sets the time of all animations in the state machine
     pub fn set_time(&mut self, time: AnimationTimeElapsed) {
         for animation in &mut self.animations {
@@ -662,6 +1420,18 @@ impl AnimationStateMachine {
         self.animations[self.current_animation].restart_animation();
     }
 
+    /// Play all animations in the state machine from the clock starting now
+    pub fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        for animation in &mut self.animations {
+            animation.set_clock(clock.clone());
+        }
+    }
+
+    /// Get the events of the current animation that fired since it was last polled
+    pub fn poll_events(&mut self) -> Vec<AnimationEvent> {
+        self.animations[self.current_animation].poll_events()
+    }
+
     pub fn set_shadow_strength(&mut self, strength: f32) {
         for animation in &mut self.animations {
             animation.set_shadow_strength(strength);
@@ -676,7 +1446,9 @@ impl Drawable for AnimationStateMachine {
         transform: &Transform,
         albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
     ) {
-        self.animations[self.current_animation].draw_albedo(program, transform, albedo_framebuffer);
+        for animation in self.get_drawn_animations() {
+            animation.draw_albedo(program, transform, albedo_framebuffer);
+        }
     }
 
     fn draw_height(
@@ -685,7 +1457,9 @@ impl Drawable for AnimationStateMachine {
         transform: &Transform,
         height_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
     ) {
-        self.animations[self.current_animation].draw_height(program, transform, height_framebuffer);
+        for animation in self.get_drawn_animations() {
+            animation.draw_height(program, transform, height_framebuffer);
+        }
     }
 
     fn draw_roughness(
@@ -694,11 +1468,9 @@ impl Drawable for AnimationStateMachine {
         transform: &Transform,
         roughness_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
     ) {
-        self.animations[self.current_animation].draw_roughness(
-            program,
-            transform,
-            roughness_framebuffer,
-        );
+        for animation in self.get_drawn_animations() {
+            animation.draw_roughness(program, transform, roughness_framebuffer);
+        }
     }
 
     fn draw_normal(
@@ -707,7 +1479,20 @@ impl Drawable for AnimationStateMachine {
         transform: &Transform,
         normal_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
     ) {
-        self.animations[self.current_animation].draw_normal(program, transform, normal_framebuffer);
+        for animation in self.get_drawn_animations() {
+            animation.draw_normal(program, transform, normal_framebuffer);
+        }
+    }
+
+    fn draw_emissive(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        emissive_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        for animation in self.get_drawn_animations() {
+            animation.draw_emissive(program, transform, emissive_framebuffer);
+        }
     }
 
     fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
@@ -728,3 +1513,406 @@ impl Drawable for AnimationStateMachine {
         self.transform = transform;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{FixedStepClock, ManualClock};
+
+    /// an animation with a frame for every duration in milliseconds, driven by a manual clock
+    fn test_animation(
+        durations: &[u64],
+        playback_mode: PlaybackMode,
+    ) -> (Animation, Rc<ManualClock>) {
+        let sprites = durations
+            .iter()
+            .map(|_| Sprite::new_untextured(Transform::default()))
+            .collect();
+        let durations = durations
+            .iter()
+            .map(|duration| Duration::from_millis(*duration))
+            .collect();
+
+        let clock = Rc::new(ManualClock::new());
+        let mut animation = Animation::new_from_sprites(
+            sprites,
+            durations,
+            Transform::default(),
+            true,
+            clock.clone(),
+        );
+        animation.set_playback_mode(playback_mode);
+        (animation, clock)
+    }
+
+    #[test]
+    fn fixed_step_clock_plays_frame_exact() {
+        let clock = Rc::new(FixedStepClock::new(Duration::from_millis(10)));
+        let mut animation = Animation::new_from_sprites(
+            vec![Sprite::new_untextured(Transform::default()); 2],
+            vec![Duration::from_millis(30), Duration::from_millis(20)],
+            Transform::default(),
+            false,
+            clock.clone(),
+        );
+        animation.set_playback_mode(PlaybackMode::Loop);
+
+        let mut frames = vec![];
+        for _ in 0..8 {
+            frames.push(animation.get_current_frame());
+            clock.tick();
+        }
+        assert_eq!(
+            frames,
+            [0, 0, 0, 1, 1, 0, 0, 0]
+                .into_iter()
+                .map(Some)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn animation_starts_at_the_clock_time_it_was_made() {
+        let clock = Rc::new(ManualClock::new());
+        clock.set_time(Duration::from_secs(10));
+        let (mut animation, _) = test_animation(&[100, 100], PlaybackMode::Once);
+        animation.set_clock(clock.clone());
+
+        assert_eq!(animation.get_current_frame(), Some(0));
+        clock.advance(Duration::from_millis(100));
+        assert_eq!(animation.get_current_frame(), Some(1));
+    }
+
+    /// the frame at every time in milliseconds
+    fn frames_at(animation: &Animation, clock: &ManualClock, times: &[u64]) -> Vec<Option<usize>> {
+        times
+            .iter()
+            .map(|time| {
+                clock.set_time(Duration::from_millis(*time));
+                animation.get_current_frame()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loop_uses_every_frame_duration() {
+        let (animation, clock) = test_animation(&[100, 200, 100], PlaybackMode::Loop);
+        assert_eq!(
+            frames_at(&animation, &clock, &[0, 99, 100, 299, 300, 399, 400, 500]),
+            vec![
+                Some(0),
+                Some(0),
+                Some(1),
+                Some(1),
+                Some(2),
+                Some(2),
+                Some(0),
+                Some(1)
+            ]
+        );
+        assert!(!animation.is_finished());
+    }
+
+    #[test]
+    fn once_is_over_after_the_last_frame() {
+        let (animation, clock) = test_animation(&[100, 200, 100], PlaybackMode::Once);
+        assert_eq!(
+            frames_at(&animation, &clock, &[0, 399, 400, 1000]),
+            vec![Some(0), Some(2), None, None]
+        );
+        assert!(animation.is_finished());
+    }
+
+    #[test]
+    fn hold_last_frame_keeps_the_last_frame() {
+        let (animation, clock) = test_animation(&[100, 200, 100], PlaybackMode::HoldLastFrame);
+        assert_eq!(
+            frames_at(&animation, &clock, &[0, 399, 400, 10_000]),
+            vec![Some(0), Some(2), Some(2), Some(2)]
+        );
+        assert!(animation.is_finished());
+    }
+
+    #[test]
+    fn reverse_starts_on_the_last_frame() {
+        let (animation, clock) = test_animation(&[100, 100, 50], PlaybackMode::Reverse);
+        assert_eq!(
+            frames_at(&animation, &clock, &[0, 49, 50, 150, 250, 300]),
+            vec![Some(2), Some(2), Some(1), Some(0), Some(2), Some(1)]
+        );
+    }
+
+    #[test]
+    fn ping_pong_does_not_repeat_the_ends() {
+        let (animation, clock) = test_animation(&[100, 100, 100, 100], PlaybackMode::PingPong);
+        let times: Vec<u64> = (0..13).map(|frame| frame * 100).collect();
+        assert_eq!(
+            frames_at(&animation, &clock, &times),
+            [0, 1, 2, 3, 2, 1, 0, 1, 2, 3, 2, 1, 0]
+                .into_iter()
+                .map(Some)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn ping_pong_with_two_frames_alternates() {
+        let (animation, clock) = test_animation(&[100, 100], PlaybackMode::PingPong);
+        assert_eq!(
+            frames_at(&animation, &clock, &[0, 100, 200, 300]),
+            vec![Some(0), Some(1), Some(0), Some(1)]
+        );
+    }
+
+    #[test]
+    fn pause_holds_the_frame_until_resumed() {
+        let (mut animation, clock) = test_animation(&[100, 100, 100], PlaybackMode::Loop);
+        clock.set_time(Duration::from_millis(150));
+        animation.pause();
+        assert!(animation.is_paused());
+
+        clock.set_time(Duration::from_millis(1150));
+        assert_eq!(animation.get_current_frame(), Some(1));
+
+        // carries on from 150ms into the animation
+        animation.resume();
+        assert_eq!(animation.get_current_frame(), Some(1));
+        clock.advance(Duration::from_millis(50));
+        assert_eq!(animation.get_current_frame(), Some(2));
+        clock.advance(Duration::from_millis(100));
+        assert_eq!(animation.get_current_frame(), Some(0));
+    }
+
+    #[test]
+    fn speed_change_mid_cycle_does_not_jump() {
+        let (mut animation, clock) = test_animation(&[100, 100, 100], PlaybackMode::Loop);
+        clock.set_time(Duration::from_millis(150));
+        animation.set_speed(2.0);
+        assert_eq!(animation.get_current_frame(), Some(1));
+
+        // 25ms at double speed is 50ms of animation
+        clock.advance(Duration::from_millis(25));
+        assert_eq!(animation.get_current_frame(), Some(2));
+        clock.advance(Duration::from_millis(50));
+        assert_eq!(animation.get_current_frame(), Some(0));
+
+        // and slowing down from 300ms into the animation
+        animation.set_speed(0.5);
+        clock.advance(Duration::from_millis(199));
+        assert_eq!(animation.get_current_frame(), Some(0));
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(animation.get_current_frame(), Some(1));
+    }
+
+    fn frame_event(name: &str, frame: usize) -> AnimationEvent {
+        AnimationEvent::Frame {
+            name: name.to_string(),
+            frame,
+        }
+    }
+
+    #[test]
+    fn events_fire_on_the_frame_boundary() {
+        let (mut animation, clock) = test_animation(&[100, 100, 100], PlaybackMode::Loop);
+        animation.add_event(0, "start");
+        animation.add_event(1, "step");
+
+        assert_eq!(animation.poll_events(), vec![frame_event("start", 0)]);
+
+        clock.set_time(Duration::from_millis(99));
+        assert_eq!(animation.poll_events(), vec![]);
+
+        // the frame starts exactly now, so it fires now and not on the next poll
+        clock.set_time(Duration::from_millis(100));
+        assert_eq!(animation.poll_events(), vec![frame_event("step", 1)]);
+        assert_eq!(animation.poll_events(), vec![]);
+        clock.set_time(Duration::from_millis(101));
+        assert_eq!(animation.poll_events(), vec![]);
+    }
+
+    #[test]
+    fn events_fire_across_a_wrap() {
+        let (mut animation, clock) = test_animation(&[100, 100, 100], PlaybackMode::Loop);
+        animation.add_event(0, "start");
+        animation.add_event(1, "step");
+        animation.add_event(2, "end");
+
+        clock.set_time(Duration::from_millis(250));
+        animation.poll_events();
+
+        clock.set_time(Duration::from_millis(420));
+        assert_eq!(
+            animation.poll_events(),
+            vec![frame_event("start", 0), frame_event("step", 1)]
+        );
+    }
+
+    #[test]
+    fn ping_pong_events_fire_on_the_way_back() {
+        let (mut animation, clock) = test_animation(&[100, 100, 100], PlaybackMode::PingPong);
+        animation.add_event(1, "middle");
+
+        clock.set_time(Duration::from_millis(350));
+        assert_eq!(
+            animation.poll_events(),
+            vec![frame_event("middle", 1), frame_event("middle", 1)]
+        );
+    }
+
+    #[test]
+    fn too_many_loops_are_reported() {
+        let (mut animation, clock) = test_animation(&[100, 100], PlaybackMode::Loop);
+        animation.add_event(0, "start");
+        animation.poll_events();
+
+        // loops 1 to 10 started, only the last 5 fire
+        clock.set_time(Duration::from_millis(2050));
+        let events = animation.poll_events();
+        assert_eq!(events[0], AnimationEvent::SkippedLoops { loops: 5 });
+        assert_eq!(events[1..], vec![frame_event("start", 0); 5]);
+    }
+
+    #[test]
+    fn finished_fires_once() {
+        let (mut animation, clock) = test_animation(&[100, 100], PlaybackMode::Once);
+        animation.add_event(1, "last");
+        animation.poll_events();
+
+        clock.set_time(Duration::from_millis(200));
+        assert_eq!(
+            animation.poll_events(),
+            vec![frame_event("last", 1), AnimationEvent::Finished]
+        );
+        clock.set_time(Duration::from_millis(500));
+        assert_eq!(animation.poll_events(), vec![]);
+    }
+
+    /// a state machine with looping animations of two 100ms frames named idle, walk and jump
+    fn test_state_machine() -> (AnimationStateMachine, Rc<ManualClock>) {
+        let clock = Rc::new(ManualClock::new());
+        let animations = ["idle", "walk", "jump"]
+            .into_iter()
+            .map(|name| {
+                let (animation, _) = test_animation(&[100, 100], PlaybackMode::Loop);
+                (name.to_string(), animation)
+            })
+            .collect();
+        let mut state_machine = AnimationStateMachine::new_named(animations);
+        state_machine.set_clock(clock.clone());
+        (state_machine, clock)
+    }
+
+    #[test]
+    fn first_transition_added_wins() {
+        let (mut state_machine, _) = test_state_machine();
+        state_machine.add_transition(
+            AnimationTransition::new("idle", "jump")
+                .with_condition(TransitionCondition::Bool("moving".to_string(), true)),
+        );
+        state_machine.add_transition(
+            AnimationTransition::new("idle", "walk")
+                .with_condition(TransitionCondition::Bool("moving".to_string(), true)),
+        );
+
+        assert!(!state_machine.update());
+        state_machine.set_bool("moving", true);
+        assert!(state_machine.update());
+        assert_eq!(state_machine.get_current_animation_name(), Some("jump"));
+    }
+
+    #[test]
+    fn triggers_are_used_up_by_transitions() {
+        let (mut state_machine, _) = test_state_machine();
+        state_machine.add_transition(
+            AnimationTransition::new("idle", "jump")
+                .with_condition(TransitionCondition::Trigger("jump".to_string()))
+                .with_condition(TransitionCondition::Bool("grounded".to_string(), true)),
+        );
+        state_machine.add_transition(
+            AnimationTransition::new("jump", "idle")
+                .with_condition(TransitionCondition::Bool("grounded".to_string(), true)),
+        );
+
+        // the trigger waits until the transition can happen
+        state_machine.set_trigger("jump");
+        assert!(!state_machine.update());
+        assert_eq!(
+            state_machine.get_parameter("jump"),
+            Some(AnimationParameter::Trigger(true))
+        );
+
+        state_machine.set_bool("grounded", true);
+        assert!(state_machine.update());
+        assert_eq!(state_machine.get_current_animation_name(), Some("jump"));
+        assert_eq!(
+            state_machine.get_parameter("jump"),
+            Some(AnimationParameter::Trigger(false))
+        );
+
+        // back to idle, and the trigger doesn't jump again
+        assert!(state_machine.update());
+        assert!(!state_machine.update());
+        assert_eq!(state_machine.get_current_animation_name(), Some("idle"));
+    }
+
+    #[test]
+    fn transition_to_itself_needs_a_trigger() {
+        let (mut state_machine, clock) = test_state_machine();
+        state_machine.add_transition(
+            AnimationTransition::new("idle", "idle")
+                .with_condition(TransitionCondition::Bool("moving".to_string(), false)),
+        );
+        state_machine.add_transition(
+            AnimationTransition::new("idle", "idle")
+                .with_condition(TransitionCondition::Trigger("restart".to_string())),
+        );
+        state_machine.set_bool("moving", false);
+
+        clock.set_time(Duration::from_millis(150));
+        assert!(!state_machine.update());
+        assert_eq!(
+            state_machine.get_animation(0).unwrap().get_current_frame(),
+            Some(1)
+        );
+
+        state_machine.set_trigger("restart");
+        assert!(state_machine.update());
+        assert_eq!(
+            state_machine.get_animation(0).unwrap().get_current_frame(),
+            Some(0)
+        );
+        assert!(!state_machine.update());
+    }
+
+    #[test]
+    fn crossfade_weights_follow_the_new_animation() {
+        let (mut state_machine, clock) = test_state_machine();
+        state_machine.add_transition(
+            AnimationTransition::new("idle", "walk")
+                .with_condition(TransitionCondition::Bool("moving".to_string(), true))
+                .with_crossfade(Duration::from_millis(100)),
+        );
+
+        clock.set_time(Duration::from_millis(500));
+        state_machine.set_bool("moving", true);
+        assert!(state_machine.update());
+        let opacities = |state_machine: &AnimationStateMachine| {
+            (
+                state_machine.get_animation(0).unwrap().get_opacity(),
+                state_machine.get_animation(1).unwrap().get_opacity(),
+            )
+        };
+        assert_eq!(opacities(&state_machine), (1.0, 0.0));
+        assert_eq!(state_machine.get_drawn_animations().len(), 2);
+
+        clock.advance(Duration::from_millis(25));
+        state_machine.update();
+        assert_eq!(opacities(&state_machine), (0.75, 0.25));
+
+        // the opacities go back to what they were once the crossfade is done
+        clock.advance(Duration::from_millis(75));
+        state_machine.update();
+        assert_eq!(opacities(&state_machine), (1.0, 1.0));
+        assert_eq!(state_machine.get_drawn_animations().len(), 1);
+    }
+}
@@ -0,0 +1,1049 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::animation::{Animation, AnimationStateMachine, PlaybackMode};
+use crate::drawable_object::Drawable;
+use crate::primitives::{Normal, NormalInput, Sprite, Texture, TextureInput};
+use crate::texture_packer::{get_frame_entries, read_packed_frame};
+use crate::LumenpyxProgram;
+use crate::Transform;
+
+const HEADER_MAGIC: u16 = 0xA5E0;
+const FRAME_MAGIC: u16 = 0xF1FA;
+const LAYER_CHUNK: u16 = 0x2004;
+const CEL_CHUNK: u16 = 0x2005;
+const TAGS_CHUNK: u16 = 0x2018;
+const PALETTE_CHUNK: u16 = 0x2019;
+
+const CHANNEL_NAMES: [&str; 4] = ["albedo", "height", "roughness", "normal"];
+
+/// The name of the animation when the file doesn't have any tags
+pub const DEFAULT_ANIMATION_NAME: &str = "default";
+
+/// Why an Aseprite file couldn't be loaded
+#[derive(Debug)]
+pub enum AsepriteError {
+    /// The file couldn't be read
+    Io(PathBuf, std::io::Error),
+    /// The sheet of a json export couldn't be loaded
+    Image(PathBuf, image::ImageError),
+    /// The file isn't a valid Aseprite file or export, the string says what is wrong with it
+    Invalid(PathBuf, String),
+    /// The file uses something that isn't supported, the string says what
+    Unsupported(PathBuf, String),
+}
+
+impl std::fmt::Display for AsepriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AsepriteError::Io(path, error) => {
+                write!(f, "Failed to read {}: {}", path.to_string_lossy(), error)
+            }
+            AsepriteError::Image(path, error) => {
+                write!(
+                    f,
+                    "Failed to load image {}: {}",
+                    path.to_string_lossy(),
+                    error
+                )
+            }
+            AsepriteError::Invalid(path, message) => {
+                write!(
+                    f,
+                    "Invalid Aseprite file {}: {}",
+                    path.to_string_lossy(),
+                    message
+                )
+            }
+            AsepriteError::Unsupported(path, message) => {
+                write!(
+                    f,
+                    "Unsupported Aseprite file {}: {}",
+                    path.to_string_lossy(),
+                    message
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for AsepriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AsepriteError::Io(_, error) => Some(error),
+            AsepriteError::Image(_, error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+fn invalid(path: &Path, message: impl Into<String>) -> AsepriteError {
+    AsepriteError::Invalid(path.to_path_buf(), message.into())
+}
+
+fn unsupported(path: &Path, message: impl Into<String>) -> AsepriteError {
+    AsepriteError::Unsupported(path.to_path_buf(), message.into())
+}
+
+/// Load an Aseprite file as an animation state machine.
+/// Takes a .aseprite or .ase file, or the .json from Aseprite's sprite sheet export with the png next to it.
+///
+/// Every tag is an animation with the name of the tag, if there are no tags every frame goes in one animation named `default`.
+/// The direction of a tag sets the playback mode of its animation, so forward loops, reverse plays backwards and ping pong turns around at the ends.
+/// The layers named `albedo`, `height`, `roughness` and `normal` are used for the matching channel.
+/// If there is no `albedo` layer, every visible layer that isn't a channel is drawn together as the albedo.
+/// A missing height is 0.0, a missing roughness is 1.0 and a missing normal is generated from the height.
+///
+/// For the json export, split the layers and keep `(layer)` in the item filename, which is what Aseprite does by default
+pub fn load_aseprite(
+    path: &str,
+    transform: Transform,
+    program: &mut LumenpyxProgram,
+) -> Result<AnimationStateMachine, AsepriteError> {
+    let path = Path::new(path);
+    let document = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => parse_json_export(path)?,
+        _ => parse_aseprite_file(path)?,
+    };
+
+    build_state_machine(document, transform, program)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TagDirection {
+    Forward,
+    Reverse,
+    PingPong,
+    PingPongReverse,
+}
+
+struct AsepriteTag {
+    name: String,
+    from: usize,
+    to: usize,
+    direction: TagDirection,
+}
+
+/// the channels of one frame, straight rgba in bytes with the top row first
+struct AsepriteFrame {
+    duration: Duration,
+    channels: [Option<Vec<u8>>; 4],
+}
+
+struct AsepriteDocument {
+    width: u32,
+    height: u32,
+    frames: Vec<AsepriteFrame>,
+    tags: Vec<AsepriteTag>,
+    /// the file, for the errors
+    path: PathBuf,
+}
+
+/// the frames of a tag in the order the animation stores them
+struct TagAnimation {
+    frames: Vec<usize>,
+    durations: Vec<Duration>,
+    playback_mode: PlaybackMode,
+}
+
+fn get_tag_animation(
+    tag: &AsepriteTag,
+    frames: &[AsepriteFrame],
+    path: &Path,
+) -> Result<TagAnimation, AsepriteError> {
+    if frames.is_empty() {
+        return Err(invalid(path, "the file has no frames"));
+    }
+    if tag.from > tag.to {
+        return Err(invalid(
+            path,
+            format!("the tag {} starts after it ends", tag.name),
+        ));
+    }
+    if tag.to >= frames.len() {
+        return Err(invalid(
+            path,
+            format!("the tag {} goes past the last frame", tag.name),
+        ));
+    }
+
+    let mut tag_frames: Vec<usize> = (tag.from..=tag.to).collect();
+
+    // the direction of the tag is played by the animation, so every frame is only stored once
+    let playback_mode = match tag.direction {
+        TagDirection::Forward => PlaybackMode::Loop,
+        TagDirection::Reverse => PlaybackMode::Reverse,
+        TagDirection::PingPong => PlaybackMode::PingPong,
+        TagDirection::PingPongReverse => {
+            // starts on the last frame, so go backwards first
+            tag_frames.reverse();
+            PlaybackMode::PingPong
+        }
+    };
+
+    Ok(TagAnimation {
+        durations: tag_frames
+            .iter()
+            .map(|frame| frames[*frame].duration)
+            .collect(),
+        frames: tag_frames,
+        playback_mode,
+    })
+}
+
+fn build_state_machine(
+    document: AsepriteDocument,
+    transform: Transform,
+    program: &mut LumenpyxProgram,
+) -> Result<AnimationStateMachine, AsepriteError> {
+    let mut tags = document.tags;
+    if tags.is_empty() {
+        tags.push(AsepriteTag {
+            name: DEFAULT_ANIMATION_NAME.to_string(),
+            from: 0,
+            to: document.frames.len().saturating_sub(1),
+            direction: TagDirection::Forward,
+        });
+    }
+
+    // check every tag before making any sprites
+    let mut tag_animations = vec![];
+    for tag in tags {
+        let tag_animation = get_tag_animation(&tag, &document.frames, &document.path)?;
+        tag_animations.push((tag.name, tag_animation));
+    }
+
+    let size = (document.width, document.height);
+
+    // every frame is loaded once, the animations share the sprites
+    let mut sprites = vec![];
+    for frame in &document.frames {
+        let [albedo, height, roughness, normal] = &frame.channels;
+        let albedo = albedo
+            .as_ref()
+            .expect("Aseprite frame is missing its albedo");
+
+        let to_texture = |data: &Vec<u8>| {
+            let image = glium::texture::RawImage2d::from_raw_rgba_reversed(data, size);
+            glium::texture::Texture2d::new(&program.display, image)
+                .expect("Failed to create texture from Aseprite frame")
+        };
+
+        let channel = |data: &Option<Vec<u8>>, default: f32| -> TextureInput {
+            match data {
+                Some(data) => Texture::Texture(to_texture(data)).into(),
+                None => default.into(),
+            }
+        };
+
+        let albedo = Texture::Texture(to_texture(albedo)).into();
+        let height = channel(height, 0.0);
+        let roughness = channel(roughness, 1.0);
+        let normal = match normal {
+            Some(data) => NormalInput::Normal(Normal::Texture(to_texture(data))),
+            None => NormalInput::Normal(Normal::AutoGenerated),
+        };
+
+        let (sprite, _, _, _, _) =
+            Sprite::new(albedo, height, roughness, normal, program, transform);
+        sprites.push(sprite);
+    }
+
+    let mut animations = vec![];
+    for (name, tag_animation) in tag_animations {
+        let mut animation = Animation::new_from_sprites(
+            tag_animation
+                .frames
+                .iter()
+                .map(|frame| sprites[*frame])
+                .collect(),
+            tag_animation.durations,
+            transform,
+            true,
+            program.get_clock(),
+        );
+        animation.set_playback_mode(tag_animation.playback_mode);
+        animations.push((name, animation));
+    }
+
+    let mut state_machine = AnimationStateMachine::new_named(animations);
+    state_machine.set_clock(program.get_clock());
+    state_machine.set_transform(transform);
+    Ok(state_machine)
+}
+
+fn channel_index(layer_name: &str) -> Option<usize> {
+    CHANNEL_NAMES
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(layer_name.trim()))
+}
+
+/// draw the source over the destination, both are straight rgba
+fn blend_over(destination: &mut [u8], source: [u8; 4], opacity: f32) {
+    let source_alpha = source[3] as f32 / 255.0 * opacity;
+    let destination_alpha = destination[3] as f32 / 255.0;
+    let alpha = source_alpha + destination_alpha * (1.0 - source_alpha);
+    if alpha == 0.0 {
+        return;
+    }
+
+    for (destination, source) in destination.iter_mut().zip(source).take(3) {
+        let color = (source as f32 * source_alpha
+            + *destination as f32 * destination_alpha * (1.0 - source_alpha))
+            / alpha;
+        *destination = color.round() as u8;
+    }
+    destination[3] = (alpha * 255.0).round() as u8;
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+    /// the file being read, for the errors
+    path: &'a Path,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], path: &'a Path) -> Self {
+        Self {
+            data,
+            position: 0,
+            path,
+        }
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], AsepriteError> {
+        let bytes = self
+            .position
+            .checked_add(count)
+            .and_then(|end| self.data.get(self.position..end))
+            .ok_or_else(|| invalid(self.path, "the file ended early"))?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, AsepriteError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn word(&mut self) -> Result<u16, AsepriteError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn short(&mut self) -> Result<i16, AsepriteError> {
+        Ok(self.word()? as i16)
+    }
+
+    fn dword(&mut self) -> Result<u32, AsepriteError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn string(&mut self) -> Result<String, AsepriteError> {
+        let length = self.word()? as usize;
+        Ok(String::from_utf8_lossy(self.bytes(length)?).to_string())
+    }
+}
+
+struct AsepriteLayer {
+    name: String,
+    visible: bool,
+    opacity: f32,
+    is_image: bool,
+}
+
+struct AsepriteCel {
+    layer: usize,
+    x: i32,
+    y: i32,
+    opacity: f32,
+    width: u32,
+    height: u32,
+    /// straight rgba
+    pixels: Vec<u8>,
+}
+
+fn parse_aseprite_file(path: &Path) -> Result<AsepriteDocument, AsepriteError> {
+    let data = std::fs::read(path).map_err(|error| AsepriteError::Io(path.to_path_buf(), error))?;
+    parse_aseprite_data(&data, path)
+}
+
+fn parse_aseprite_data(data: &[u8], path: &Path) -> Result<AsepriteDocument, AsepriteError> {
+    let mut reader = Reader::new(data, path);
+
+    // header
+    reader.dword()?;
+    if reader.word()? != HEADER_MAGIC {
+        return Err(invalid(path, "the file is not an Aseprite file"));
+    }
+    let frame_count = reader.word()? as usize;
+    let width = reader.word()? as u32;
+    let height = reader.word()? as u32;
+    let color_depth = reader.word()?;
+    reader.dword()?;
+    reader.word()?;
+    reader.dword()?;
+    reader.dword()?;
+    let transparent_index = reader.byte()?;
+    reader.bytes(3)?;
+    reader.word()?;
+    reader.bytes(128 - reader.position)?;
+
+    let mut layers: Vec<AsepriteLayer> = vec![];
+    let mut tags = vec![];
+    let mut palette = vec![[0u8; 4]; 256];
+    // the cels of every frame, linked cels are copied from the frame they point to
+    let mut frame_cels: Vec<Vec<AsepriteCel>> = vec![];
+    let mut durations = vec![];
+
+    for _ in 0..frame_count {
+        let frame_start = reader.position;
+        let frame_size = reader.dword()? as usize;
+        if reader.word()? != FRAME_MAGIC {
+            return Err(invalid(path, "a frame is corrupted"));
+        }
+        let old_chunk_count = reader.word()? as usize;
+        durations.push(Duration::from_millis(reader.word()? as u64));
+        reader.bytes(2)?;
+        let chunk_count = match reader.dword()? as usize {
+            0 => old_chunk_count,
+            chunk_count => chunk_count,
+        };
+
+        let mut cels = vec![];
+        for _ in 0..chunk_count {
+            let chunk_start = reader.position;
+            let chunk_size = reader.dword()? as usize;
+            let chunk_type = reader.word()?;
+
+            match chunk_type {
+                LAYER_CHUNK => {
+                    let flags = reader.word()?;
+                    let layer_type = reader.word()?;
+                    reader.word()?;
+                    reader.word()?;
+                    reader.word()?;
+                    reader.word()?;
+                    let opacity = reader.byte()? as f32 / 255.0;
+                    reader.bytes(3)?;
+                    let name = reader.string()?;
+                    layers.push(AsepriteLayer {
+                        name,
+                        visible: flags & 1 != 0,
+                        opacity,
+                        is_image: layer_type == 0,
+                    });
+                }
+                CEL_CHUNK => {
+                    let layer = reader.word()? as usize;
+                    let x = reader.short()? as i32;
+                    let y = reader.short()? as i32;
+                    let opacity = reader.byte()? as f32 / 255.0;
+                    let cel_type = reader.word()?;
+                    reader.short()?;
+                    reader.bytes(5)?;
+
+                    match cel_type {
+                        0 | 2 => {
+                            let cel_width = reader.word()? as u32;
+                            let cel_height = reader.word()? as u32;
+                            let remaining = (chunk_start + chunk_size)
+                                .checked_sub(reader.position)
+                                .ok_or_else(|| invalid(path, "a cel is corrupted"))?;
+                            let raw = reader.bytes(remaining)?;
+                            let raw = if cel_type == 2 {
+                                let mut decompressed = vec![];
+                                flate2::read::ZlibDecoder::new(raw)
+                                    .read_to_end(&mut decompressed)
+                                    .map_err(|error| {
+                                        invalid(
+                                            path,
+                                            format!("failed to decompress a cel: {}", error),
+                                        )
+                                    })?;
+                                decompressed
+                            } else {
+                                raw.to_vec()
+                            };
+
+                            let pixels =
+                                to_rgba(&raw, color_depth, &palette, transparent_index, path)?;
+                            if pixels.len() < cel_width as usize * cel_height as usize * 4 {
+                                return Err(invalid(path, "a cel has fewer pixels than its size"));
+                            }
+                            cels.push(AsepriteCel {
+                                layer,
+                                x,
+                                y,
+                                opacity,
+                                width: cel_width,
+                                height: cel_height,
+                                pixels,
+                            });
+                        }
+                        1 => {
+                            let linked_frame = reader.word()? as usize;
+                            if let Some(linked) =
+                                frame_cels.get(linked_frame).and_then(|linked_cels| {
+                                    linked_cels.iter().find(|cel| cel.layer == layer)
+                                })
+                            {
+                                cels.push(AsepriteCel {
+                                    layer,
+                                    x: linked.x,
+                                    y: linked.y,
+                                    opacity: linked.opacity,
+                                    width: linked.width,
+                                    height: linked.height,
+                                    pixels: linked.pixels.clone(),
+                                });
+                            }
+                        }
+                        // tilemap cels aren't supported
+                        _ => {}
+                    }
+                }
+                TAGS_CHUNK => {
+                    let tag_count = reader.word()?;
+                    reader.bytes(8)?;
+                    for _ in 0..tag_count {
+                        let from = reader.word()? as usize;
+                        let to = reader.word()? as usize;
+                        let direction = match reader.byte()? {
+                            1 => TagDirection::Reverse,
+                            2 => TagDirection::PingPong,
+                            3 => TagDirection::PingPongReverse,
+                            _ => TagDirection::Forward,
+                        };
+                        reader.word()?;
+                        reader.bytes(6)?;
+                        reader.bytes(3)?;
+                        reader.byte()?;
+                        let name = reader.string()?;
+                        tags.push(AsepriteTag {
+                            name,
+                            from,
+                            to,
+                            direction,
+                        });
+                    }
+                }
+                PALETTE_CHUNK => {
+                    reader.dword()?;
+                    let first = reader.dword()? as usize;
+                    let last = reader.dword()? as usize;
+                    reader.bytes(8)?;
+                    for entry in first..=last {
+                        let flags = reader.word()?;
+                        let color = reader.bytes(4)?;
+                        // grown as the entries are read, so a broken size can't make a huge palette
+                        if entry >= palette.len() {
+                            palette.resize(entry + 1, [0, 0, 0, 0]);
+                        }
+                        palette[entry] = [color[0], color[1], color[2], color[3]];
+                        if flags & 1 != 0 {
+                            reader.string()?;
+                        }
+                    }
+                }
+                _ => {}
+            }
+
+            reader.position = chunk_start + chunk_size;
+        }
+
+        frame_cels.push(cels);
+        reader.position = frame_start + frame_size;
+    }
+
+    let has_albedo_layer = layers
+        .iter()
+        .any(|layer| channel_index(&layer.name) == Some(0));
+
+    let mut frames = vec![];
+    for (cels, duration) in frame_cels.iter().zip(durations) {
+        let mut channels: [Option<Vec<u8>>; 4] = [None, None, None, None];
+
+        // cels are stored from the bottom layer up, so drawing them in order stacks them correctly
+        for cel in cels {
+            let Some(layer) = layers.get(cel.layer) else {
+                continue;
+            };
+            if !layer.is_image {
+                continue;
+            }
+
+            let channel = match channel_index(&layer.name) {
+                Some(channel) => channel,
+                None if !has_albedo_layer && layer.visible => 0,
+                None => continue,
+            };
+
+            let canvas =
+                channels[channel].get_or_insert_with(|| vec![0; (width * height * 4) as usize]);
+            // the material channels are copied as they are, only the albedo is blended
+            let opacity = if channel == 0 {
+                layer.opacity * cel.opacity
+            } else {
+                1.0
+            };
+
+            for cel_y in 0..cel.height as i32 {
+                for cel_x in 0..cel.width as i32 {
+                    let x = cel.x + cel_x;
+                    let y = cel.y + cel_y;
+                    if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+                        continue;
+                    }
+
+                    let source_index = ((cel_y * cel.width as i32 + cel_x) * 4) as usize;
+                    let pixel = [
+                        cel.pixels[source_index],
+                        cel.pixels[source_index + 1],
+                        cel.pixels[source_index + 2],
+                        cel.pixels[source_index + 3],
+                    ];
+                    let destination_index = ((y * width as i32 + x) * 4) as usize;
+                    let destination = &mut canvas[destination_index..destination_index + 4];
+                    if channel == 0 {
+                        blend_over(destination, pixel, opacity);
+                    } else if pixel[3] != 0 {
+                        destination.copy_from_slice(&pixel);
+                    }
+                }
+            }
+        }
+
+        if channels[0].is_none() {
+            channels[0] = Some(vec![0; (width * height * 4) as usize]);
+        }
+
+        frames.push(AsepriteFrame { duration, channels });
+    }
+
+    Ok(AsepriteDocument {
+        width,
+        height,
+        frames,
+        tags,
+        path: path.to_path_buf(),
+    })
+}
+
+/// turn the pixels of a cel into straight rgba
+fn to_rgba(
+    raw: &[u8],
+    color_depth: u16,
+    palette: &[[u8; 4]],
+    transparent_index: u8,
+    path: &Path,
+) -> Result<Vec<u8>, AsepriteError> {
+    Ok(match color_depth {
+        32 => raw.to_vec(),
+        16 => raw
+            .chunks_exact(2)
+            .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+            .collect(),
+        8 => raw
+            .iter()
+            .flat_map(|index| {
+                if *index == transparent_index {
+                    [0, 0, 0, 0]
+                } else {
+                    palette
+                        .get(*index as usize)
+                        .copied()
+                        .unwrap_or([0, 0, 0, 0])
+                }
+            })
+            .collect(),
+        _ => {
+            return Err(unsupported(
+                path,
+                format!("the color depth {} is not supported", color_depth),
+            ))
+        }
+    })
+}
+
+fn parse_json_export(path: &Path) -> Result<AsepriteDocument, AsepriteError> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|error| AsepriteError::Io(path.to_path_buf(), error))?;
+    let json: Value =
+        serde_json::from_str(&text).map_err(|error| invalid(path, error.to_string()))?;
+
+    let directory = path
+        .parent()
+        .map(|parent| parent.to_path_buf())
+        .unwrap_or_default();
+    let image_path = directory.join(
+        json["meta"]["image"]
+            .as_str()
+            .ok_or_else(|| invalid(path, "the json is missing the image"))?,
+    );
+    let sheet = image::open(&image_path)
+        .map_err(|error| AsepriteError::Image(image_path.clone(), error))?
+        .to_rgba8();
+
+    if !json["frames"].is_array() && !json["frames"].is_object() {
+        return Err(invalid(path, "the json is missing the frames"));
+    }
+    let entries = get_frame_entries(&json);
+
+    // the layer is in the filename as "(layer)", without it every frame is the albedo
+    let layer_of = |filename: &str| -> Option<usize> {
+        let start = filename.rfind('(')?;
+        let end = filename[start..].find(')')? + start;
+        channel_index(&filename[start + 1..end])
+    };
+    let is_split = entries
+        .iter()
+        .any(|(filename, _)| layer_of(filename).is_some());
+
+    let mut size = None;
+    let mut channel_frames: [Vec<(Vec<u8>, Duration)>; 4] = [vec![], vec![], vec![], vec![]];
+    for (filename, frame) in &entries {
+        let channel = if is_split {
+            match layer_of(filename) {
+                Some(channel) => channel,
+                None => continue,
+            }
+        } else {
+            0
+        };
+
+        let (pixels, frame_width, frame_height) =
+            read_packed_frame(&sheet, frame).ok_or_else(|| {
+                invalid(
+                    path,
+                    format!("the frame {} is outside of the sheet", filename),
+                )
+            })?;
+        if *size.get_or_insert((frame_width, frame_height)) != (frame_width, frame_height) {
+            return Err(invalid(path, "every frame must be the same size"));
+        }
+
+        let duration = Duration::from_millis(frame["duration"].as_u64().unwrap_or(100));
+        channel_frames[channel].push((pixels, duration));
+    }
+
+    let frame_count = channel_frames[0].len();
+    if frame_count == 0 {
+        return Err(invalid(path, "the json has no albedo frames"));
+    }
+    for frames in channel_frames.iter().skip(1) {
+        if !frames.is_empty() && frames.len() != frame_count {
+            return Err(invalid(
+                path,
+                "every layer must have the same number of frames",
+            ));
+        }
+    }
+    let (width, height) = size.unwrap_or_default();
+
+    let [albedo, height_frames, roughness, normal] = channel_frames;
+    let mut height_frames = height_frames.into_iter();
+    let mut roughness = roughness.into_iter();
+    let mut normal = normal.into_iter();
+    let frames = albedo
+        .into_iter()
+        .map(|(pixels, duration)| AsepriteFrame {
+            duration,
+            channels: [
+                Some(pixels),
+                height_frames.next().map(|(pixels, _)| pixels),
+                roughness.next().map(|(pixels, _)| pixels),
+                normal.next().map(|(pixels, _)| pixels),
+            ],
+        })
+        .collect();
+
+    let mut tags = vec![];
+    for tag in json["meta"]["frameTags"].as_array().into_iter().flatten() {
+        tags.push(AsepriteTag {
+            name: tag["name"].as_str().unwrap_or_default().to_string(),
+            from: tag["from"].as_u64().unwrap_or(0) as usize,
+            to: tag["to"].as_u64().unwrap_or(0) as usize,
+            direction: match tag["direction"].as_str() {
+                Some("reverse") => TagDirection::Reverse,
+                Some("pingpong") => TagDirection::PingPong,
+                Some("pingpong_reverse") => TagDirection::PingPongReverse,
+                _ => TagDirection::Forward,
+            },
+        });
+    }
+
+    Ok(AsepriteDocument {
+        width,
+        height,
+        frames,
+        tags,
+        path: path.to_path_buf(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(text: &str) -> Vec<u8> {
+        let mut bytes = (text.len() as u16).to_le_bytes().to_vec();
+        bytes.extend(text.as_bytes());
+        bytes
+    }
+
+    fn chunk(chunk_type: u16, body: &[u8]) -> Vec<u8> {
+        let mut bytes = ((body.len() + 6) as u32).to_le_bytes().to_vec();
+        bytes.extend(chunk_type.to_le_bytes());
+        bytes.extend(body);
+        bytes
+    }
+
+    fn layer_chunk(name: &str) -> Vec<u8> {
+        // visible image layer at full opacity
+        let mut body = [1u16.to_le_bytes(), 0u16.to_le_bytes()].concat();
+        body.extend([0; 8]);
+        body.push(255);
+        body.extend([0; 3]);
+        body.extend(string(name));
+        chunk(LAYER_CHUNK, &body)
+    }
+
+    fn cel_header(layer: u16, cel_type: u16) -> Vec<u8> {
+        let mut body = layer.to_le_bytes().to_vec();
+        body.extend([0; 4]);
+        body.push(255);
+        body.extend(cel_type.to_le_bytes());
+        body.extend([0; 7]);
+        body
+    }
+
+    /// a 1x1 cel at the top left
+    fn raw_cel_chunk(layer: u16, pixel: [u8; 4]) -> Vec<u8> {
+        let mut body = cel_header(layer, 0);
+        body.extend([1u16.to_le_bytes(), 1u16.to_le_bytes()].concat());
+        body.extend(pixel);
+        chunk(CEL_CHUNK, &body)
+    }
+
+    fn linked_cel_chunk(layer: u16, frame: u16) -> Vec<u8> {
+        let mut body = cel_header(layer, 1);
+        body.extend(frame.to_le_bytes());
+        chunk(CEL_CHUNK, &body)
+    }
+
+    fn tags_chunk(tags: &[(&str, u16, u16, u8)]) -> Vec<u8> {
+        let mut body = (tags.len() as u16).to_le_bytes().to_vec();
+        body.extend([0; 8]);
+        for (name, from, to, direction) in tags {
+            body.extend(from.to_le_bytes());
+            body.extend(to.to_le_bytes());
+            body.push(*direction);
+            body.extend([0; 12]);
+            body.extend(string(name));
+        }
+        chunk(TAGS_CHUNK, &body)
+    }
+
+    fn frame(duration: u16, chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body = chunks.concat();
+        let mut bytes = ((body.len() + 16) as u32).to_le_bytes().to_vec();
+        bytes.extend(FRAME_MAGIC.to_le_bytes());
+        bytes.extend((chunks.len() as u16).to_le_bytes());
+        bytes.extend(duration.to_le_bytes());
+        bytes.extend([0; 2]);
+        bytes.extend((chunks.len() as u32).to_le_bytes());
+        bytes.extend(body);
+        bytes
+    }
+
+    /// a 1x1 rgba file
+    fn file(frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = vec![0; 4];
+        bytes.extend(HEADER_MAGIC.to_le_bytes());
+        bytes.extend((frames.len() as u16).to_le_bytes());
+        bytes.extend(1u16.to_le_bytes());
+        bytes.extend(1u16.to_le_bytes());
+        bytes.extend(32u16.to_le_bytes());
+        bytes.resize(128, 0);
+        bytes.extend(frames.concat());
+        bytes
+    }
+
+    fn test_frames(durations: &[u64]) -> Vec<AsepriteFrame> {
+        durations
+            .iter()
+            .map(|duration| AsepriteFrame {
+                duration: Duration::from_millis(*duration),
+                channels: [None, None, None, None],
+            })
+            .collect()
+    }
+
+    fn tag(from: usize, to: usize, direction: TagDirection) -> AsepriteTag {
+        AsepriteTag {
+            name: "walk".to_string(),
+            from,
+            to,
+            direction,
+        }
+    }
+
+    #[test]
+    fn reads_little_endian_values() {
+        let path = Path::new("test.aseprite");
+        let data = [
+            0x34, 0x12, 0xFE, 0xFF, 0x78, 0x56, 0x34, 0x12, 2, 0, b'h', b'i',
+        ];
+        let mut reader = Reader::new(&data, path);
+
+        assert_eq!(reader.word().unwrap(), 0x1234);
+        assert_eq!(reader.short().unwrap(), -2);
+        assert_eq!(reader.dword().unwrap(), 0x12345678);
+        assert_eq!(reader.string().unwrap(), "hi");
+        assert!(matches!(reader.byte(), Err(AsepriteError::Invalid(..))));
+    }
+
+    #[test]
+    fn parses_frames_and_tags() {
+        let path = Path::new("test.aseprite");
+        let data = file(&[
+            frame(
+                100,
+                &[
+                    layer_chunk("albedo"),
+                    layer_chunk("height"),
+                    raw_cel_chunk(0, [255, 0, 0, 255]),
+                    raw_cel_chunk(1, [64, 64, 64, 255]),
+                    tags_chunk(&[("walk", 0, 1, 2), ("idle", 1, 1, 0)]),
+                ],
+            ),
+            frame(250, &[linked_cel_chunk(0, 0)]),
+        ]);
+
+        let document = parse_aseprite_data(&data, path).unwrap();
+        assert_eq!((document.width, document.height), (1, 1));
+        assert_eq!(
+            document
+                .frames
+                .iter()
+                .map(|frame| frame.duration)
+                .collect::<Vec<_>>(),
+            [Duration::from_millis(100), Duration::from_millis(250)]
+        );
+        assert_eq!(document.frames[0].channels[0], Some(vec![255, 0, 0, 255]));
+        assert_eq!(document.frames[0].channels[1], Some(vec![64, 64, 64, 255]));
+        // the linked cel is a copy of the first frame, and the second frame has no height
+        assert_eq!(document.frames[1].channels[0], Some(vec![255, 0, 0, 255]));
+        assert_eq!(document.frames[1].channels[1], None);
+
+        let tags: Vec<_> = document
+            .tags
+            .iter()
+            .map(|tag| (tag.name.as_str(), tag.from, tag.to, tag.direction))
+            .collect();
+        assert_eq!(
+            tags,
+            [
+                ("walk", 0, 1, TagDirection::PingPong),
+                ("idle", 1, 1, TagDirection::Forward)
+            ]
+        );
+    }
+
+    #[test]
+    fn broken_files_are_an_error() {
+        let path = Path::new("test.aseprite");
+        let data = file(&[frame(100, &[raw_cel_chunk(0, [255, 0, 0, 255])])]);
+
+        assert!(matches!(
+            parse_aseprite_data(&data[..data.len() - 3], path),
+            Err(AsepriteError::Invalid(..))
+        ));
+        assert!(matches!(
+            parse_aseprite_data(&data[..64], path),
+            Err(AsepriteError::Invalid(..))
+        ));
+
+        let mut not_aseprite = data.clone();
+        not_aseprite[4] = 0;
+        assert!(matches!(
+            parse_aseprite_data(&not_aseprite, path),
+            Err(AsepriteError::Invalid(..))
+        ));
+
+        let mut indexed = data;
+        indexed[12] = 24;
+        assert!(matches!(
+            parse_aseprite_data(&indexed, path),
+            Err(AsepriteError::Unsupported(..))
+        ));
+    }
+
+    #[test]
+    fn tag_directions_set_the_playback_mode() {
+        let path = Path::new("test.aseprite");
+        let frames = test_frames(&[100, 200, 300, 400]);
+
+        let animation =
+            get_tag_animation(&tag(1, 3, TagDirection::Forward), &frames, path).unwrap();
+        assert_eq!(animation.frames, [1, 2, 3]);
+        assert_eq!(animation.playback_mode, PlaybackMode::Loop);
+
+        let animation =
+            get_tag_animation(&tag(1, 3, TagDirection::Reverse), &frames, path).unwrap();
+        assert_eq!(animation.frames, [1, 2, 3]);
+        assert_eq!(animation.playback_mode, PlaybackMode::Reverse);
+
+        let animation =
+            get_tag_animation(&tag(1, 3, TagDirection::PingPong), &frames, path).unwrap();
+        assert_eq!(animation.frames, [1, 2, 3]);
+        assert_eq!(animation.playback_mode, PlaybackMode::PingPong);
+
+        // starts on the last frame
+        let animation =
+            get_tag_animation(&tag(1, 3, TagDirection::PingPongReverse), &frames, path).unwrap();
+        assert_eq!(animation.frames, [3, 2, 1]);
+        assert_eq!(animation.playback_mode, PlaybackMode::PingPong);
+    }
+
+    #[test]
+    fn durations_follow_the_frames() {
+        let path = Path::new("test.aseprite");
+        let frames = test_frames(&[100, 200, 300, 400]);
+
+        let animation =
+            get_tag_animation(&tag(0, 2, TagDirection::Forward), &frames, path).unwrap();
+        assert_eq!(
+            animation.durations,
+            [100, 200, 300].map(Duration::from_millis)
+        );
+
+        let animation =
+            get_tag_animation(&tag(2, 3, TagDirection::PingPongReverse), &frames, path).unwrap();
+        assert_eq!(animation.durations, [400, 300].map(Duration::from_millis));
+    }
+
+    #[test]
+    fn broken_tags_are_an_error() {
+        let path = Path::new("test.aseprite");
+        let frames = test_frames(&[100, 200]);
+
+        assert!(matches!(
+            get_tag_animation(&tag(0, 0, TagDirection::Forward), &[], path),
+            Err(AsepriteError::Invalid(..))
+        ));
+        assert!(matches!(
+            get_tag_animation(&tag(1, 0, TagDirection::Forward), &frames, path),
+            Err(AsepriteError::Invalid(..))
+        ));
+        assert!(matches!(
+            get_tag_animation(&tag(0, 2, TagDirection::Forward), &frames, path),
+            Err(AsepriteError::Invalid(..))
+        ));
+    }
+}
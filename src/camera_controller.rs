@@ -0,0 +1,321 @@
+use std::time::Duration;
+
+use crate::light_modifiers::value_noise;
+use crate::Camera;
+
+/// Moves a camera to follow a target, with a dead zone, look ahead, world bounds and screen shake.
+/// Set the target and call update every frame, then draw with the camera from get_camera
+pub struct CameraController {
+    camera: Camera,
+    target: [f32; 2],
+    last_target: Option<[f32; 2]>,
+    /// the point the camera is following before look ahead, it only moves when the target leaves the dead zone
+    focus: [f32; 2],
+    look_ahead_offset: [f32; 2],
+    follow_speed: f32,
+    dead_zone: [f32; 2],
+    bounds: Option<[f32; 4]>,
+    look_ahead_time: f32,
+    max_look_ahead: f32,
+    trauma: f32,
+    trauma_decay: f32,
+    max_shake_offset: f32,
+    max_shake_rotation: f32,
+    shake_frequency: f32,
+    seed: u64,
+    /// how long the controller has been updating in seconds, drives the shake
+    time: f32,
+}
+
+impl CameraController {
+    /// Make a controller that follows from where the camera is
+    pub fn new(camera: Camera) -> Self {
+        let position = [camera.position[0], camera.position[1]];
+        Self {
+            camera,
+            target: position,
+            last_target: None,
+            focus: position,
+            look_ahead_offset: [0.0, 0.0],
+            follow_speed: 5.0,
+            dead_zone: [0.0, 0.0],
+            bounds: None,
+            look_ahead_time: 0.0,
+            max_look_ahead: 0.0,
+            trauma: 0.0,
+            trauma_decay: 1.0,
+            max_shake_offset: 8.0,
+            max_shake_rotation: 0.05,
+            shake_frequency: 15.0,
+            seed: 0,
+            time: 0.0,
+        }
+    }
+
+    /// How quickly the camera catches up to the target, higher is faster, 0.0 snaps to it instantly
+    pub fn with_follow_speed(mut self, follow_speed: f32) -> Self {
+        self.follow_speed = follow_speed;
+        self
+    }
+
+    /// The width and height in pixels of the box in the middle of the screen the target can move in without the camera moving
+    pub fn with_dead_zone(mut self, dead_zone: [f32; 2]) -> Self {
+        self.dead_zone = dead_zone;
+        self
+    }
+
+    /// Keep everything the camera sees inside min x, min y, max x, max y in world pixels
+    pub fn with_bounds(mut self, bounds: [f32; 4]) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    /// Look ahead of the target in the direction it's moving, by where it will be in time seconds,
+    /// never more than max_distance pixels
+    pub fn with_look_ahead(mut self, time: f32, max_distance: f32) -> Self {
+        self.look_ahead_time = time;
+        self.max_look_ahead = max_distance;
+        self
+    }
+
+    /// Set how the camera shakes at full trauma, the offset is in pixels and the rotation in radians.
+    /// frequency is how many times a second the shake changes direction
+    pub fn with_shake(mut self, max_offset: f32, max_rotation: f32, frequency: f32) -> Self {
+        self.max_shake_offset = max_offset;
+        self.max_shake_rotation = max_rotation;
+        self.shake_frequency = frequency;
+        self
+    }
+
+    /// How much trauma goes away every second
+    pub fn with_trauma_decay(mut self, trauma_decay: f32) -> Self {
+        self.trauma_decay = trauma_decay;
+        self
+    }
+
+    /// The seed of the shake, cameras with the same seed shake the same way
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Set the point in world pixels the camera follows
+    pub fn set_target(&mut self, target: [f32; 2]) {
+        self.target = target;
+    }
+
+    pub fn get_target(&self) -> [f32; 2] {
+        self.target
+    }
+
+    pub fn set_bounds(&mut self, bounds: Option<[f32; 4]>) {
+        self.bounds = bounds;
+    }
+
+    pub fn get_bounds(&self) -> Option<[f32; 4]> {
+        self.bounds
+    }
+
+    /// Move the camera straight to the target without smoothing, e.g. when the level changes
+    pub fn snap_to_target(&mut self) {
+        self.focus = self.target;
+        self.look_ahead_offset = [0.0, 0.0];
+        self.last_target = Some(self.target);
+        self.camera.position[0] = self.target[0];
+        self.camera.position[1] = self.target[1];
+    }
+
+    /// Add trauma from 0.0 to 1.0, the shake gets stronger with the square of the trauma.
+    /// A small hit might add 0.2 and an explosion 0.6, it's capped at 1.0
+    pub fn add_trauma(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).clamp(0.0, 1.0);
+    }
+
+    pub fn get_trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// Move the camera towards the target and update the shake, call this once per frame.
+    /// The resolution is how many pixels the camera renders, used to know how much of the world it can see for the bounds.
+    /// Use program.get_dimensions() when drawing to the window, or the resolution of the viewport the camera is in
+    pub fn update(&mut self, delta_time: Duration, resolution: [u32; 2]) {
+        let delta = delta_time.as_secs_f32();
+        self.time += delta;
+        self.trauma = (self.trauma - self.trauma_decay * delta).max(0.0);
+
+        // only move the focus when the target leaves the dead zone
+        for axis in 0..2 {
+            let half_dead_zone = self.dead_zone[axis] / 2.0;
+            let distance = self.target[axis] - self.focus[axis];
+            if distance > half_dead_zone {
+                self.focus[axis] = self.target[axis] - half_dead_zone;
+            } else if distance < -half_dead_zone {
+                self.focus[axis] = self.target[axis] + half_dead_zone;
+            }
+        }
+
+        let smoothing = if self.follow_speed <= 0.0 {
+            1.0
+        } else {
+            1.0 - (-self.follow_speed * delta).exp()
+        };
+
+        // look ahead by how fast the target is moving
+        let mut look_ahead = [0.0, 0.0];
+        if let Some(last_target) = self.last_target {
+            if delta > 0.0 {
+                for axis in 0..2 {
+                    let velocity = (self.target[axis] - last_target[axis]) / delta;
+                    look_ahead[axis] = (velocity * self.look_ahead_time)
+                        .clamp(-self.max_look_ahead, self.max_look_ahead);
+                }
+            }
+        }
+        self.last_target = Some(self.target);
+
+        let visible_half_size = self.get_visible_half_size(resolution);
+        for axis in 0..2 {
+            self.look_ahead_offset[axis] +=
+                (look_ahead[axis] - self.look_ahead_offset[axis]) * smoothing;
+
+            let desired = self.focus[axis] + self.look_ahead_offset[axis];
+            let mut position =
+                self.camera.position[axis] + (desired - self.camera.position[axis]) * smoothing;
+
+            if let Some(bounds) = self.bounds {
+                let (min, max) = (bounds[axis], bounds[axis + 2]);
+                let (low, high) = (min + visible_half_size[axis], max - visible_half_size[axis]);
+                // if the bounds are smaller than the screen, center on them
+                position = if low > high {
+                    (min + max) / 2.0
+                } else {
+                    position.clamp(low, high)
+                };
+            }
+
+            self.camera.position[axis] = position;
+        }
+    }
+
+    /// Get the camera to draw with, with the shake added
+    pub fn get_camera(&self) -> Camera {
+        let mut camera = self.camera;
+        let shake = self.trauma * self.trauma;
+        if shake <= 0.0 {
+            return camera;
+        }
+
+        // each part of the shake gets its own noise so they don't move together
+        let noise = |index: u64| {
+            value_noise(
+                self.seed.wrapping_mul(3).wrapping_add(index),
+                self.time * self.shake_frequency,
+            ) * 2.0
+                - 1.0
+        };
+        camera.position[0] += self.max_shake_offset * shake * noise(0);
+        camera.position[1] += self.max_shake_offset * shake * noise(1);
+        camera.set_rotation(camera.get_rotation() + self.max_shake_rotation * shake * noise(2));
+        camera
+    }
+
+    /// Get the camera without the shake, e.g. to change the zoom
+    pub fn get_camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    /// how far the camera can see from its center in world pixels
+    fn get_visible_half_size(&self, resolution: [u32; 2]) -> [f32; 2] {
+        [
+            resolution[0] as f32 / 2.0 / self.camera.get_zoom(),
+            resolution[1] as f32 / 2.0 / self.camera.get_zoom(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESOLUTION: [u32; 2] = [200, 100];
+    const SECOND: Duration = Duration::from_secs(1);
+
+    /// a controller that snaps to where it wants to be, so every update can be checked exactly
+    fn test_controller() -> CameraController {
+        CameraController::new(Camera::new([0.0, 0.0, 0.0])).with_follow_speed(0.0)
+    }
+
+    fn position(controller: &CameraController) -> [f32; 2] {
+        let camera = controller.get_camera();
+        [camera.position[0], camera.position[1]]
+    }
+
+    fn move_to(controller: &mut CameraController, target: [f32; 2]) -> [f32; 2] {
+        controller.set_target(target);
+        controller.update(SECOND, RESOLUTION);
+        position(controller)
+    }
+
+    #[test]
+    fn only_follows_out_of_the_dead_zone() {
+        let mut controller = test_controller().with_dead_zone([20.0, 10.0]);
+
+        assert_eq!(move_to(&mut controller, [8.0, -4.0]), [0.0, 0.0]);
+        // the target drags the edge of the dead zone behind it
+        assert_eq!(move_to(&mut controller, [30.0, -15.0]), [20.0, -10.0]);
+        assert_eq!(move_to(&mut controller, [25.0, -10.0]), [20.0, -10.0]);
+        assert_eq!(move_to(&mut controller, [0.0, 0.0]), [10.0, -5.0]);
+    }
+
+    #[test]
+    fn keeps_the_view_in_the_bounds() {
+        let mut controller = test_controller().with_bounds([0.0, 0.0, 1000.0, 1000.0]);
+
+        // the camera sees 100 pixels to each side and 50 up and down
+        assert_eq!(move_to(&mut controller, [-500.0, 2000.0]), [100.0, 950.0]);
+        assert_eq!(move_to(&mut controller, [400.0, 300.0]), [400.0, 300.0]);
+
+        // zooming in lets the camera get closer to the edge
+        controller.get_camera_mut().set_zoom(2.0);
+        assert_eq!(move_to(&mut controller, [-500.0, 2000.0]), [50.0, 975.0]);
+    }
+
+    #[test]
+    fn centers_on_bounds_smaller_than_the_view() {
+        let mut controller = test_controller().with_bounds([0.0, 0.0, 150.0, 400.0]);
+
+        // too narrow for the 200 pixel wide view, but tall enough to follow up and down
+        assert_eq!(move_to(&mut controller, [500.0, 200.0]), [75.0, 200.0]);
+        assert_eq!(move_to(&mut controller, [-500.0, 1000.0]), [75.0, 350.0]);
+    }
+
+    #[test]
+    fn clamps_the_look_ahead() {
+        let mut controller = test_controller().with_look_ahead(0.5, 10.0);
+        controller.update(SECOND, RESOLUTION);
+
+        // moving 10 pixels a second looks 5 pixels ahead
+        assert_eq!(move_to(&mut controller, [10.0, 0.0]), [15.0, 0.0]);
+        // moving 100 pixels a second would look 50 ahead, but it stops at 10
+        assert_eq!(move_to(&mut controller, [110.0, -100.0]), [120.0, -110.0]);
+        // standing still brings it back
+        assert_eq!(move_to(&mut controller, [110.0, -100.0]), [110.0, -100.0]);
+    }
+
+    #[test]
+    fn trauma_decays_and_stops_the_shake() {
+        let mut controller = test_controller().with_trauma_decay(0.5);
+
+        controller.add_trauma(2.0);
+        assert_eq!(controller.get_trauma(), 1.0);
+
+        controller.update(SECOND, RESOLUTION);
+        assert_eq!(controller.get_trauma(), 0.5);
+        assert_ne!(position(&controller), [0.0, 0.0]);
+
+        controller.update(SECOND * 2, RESOLUTION);
+        assert_eq!(controller.get_trauma(), 0.0);
+        assert_eq!(position(&controller), [0.0, 0.0]);
+        assert_eq!(controller.get_camera().get_rotation(), 0.0);
+    }
+}
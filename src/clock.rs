@@ -0,0 +1,103 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+/// Something that tells animations and other time driven objects what time it is.
+/// The time is how long it's been since the clock started
+pub trait Clock {
+    fn now(&self) -> Duration;
+}
+
+/// A clock that follows the real time, this is what the program uses by default
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// A clock that only moves when you tell it to, useful for replays and tests
+#[derive(Default)]
+pub struct ManualClock {
+    time: Cell<Duration>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_time(&self, time: Duration) {
+        self.time.set(time);
+    }
+
+    /// Move the clock forward by the amount of time
+    pub fn advance(&self, time: Duration) {
+        self.time.set(self.time.get() + time);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.time.get()
+    }
+}
+
+/// A clock that moves forward by the same step every time it's ticked, e.g. once per frame
+pub struct FixedStepClock {
+    step: Duration,
+    ticks: Cell<u64>,
+}
+
+impl FixedStepClock {
+    pub fn new(step: Duration) -> Self {
+        Self {
+            step,
+            ticks: Cell::new(0),
+        }
+    }
+
+    /// Make a clock that steps once per frame at the frame rate
+    pub fn from_fps(fps: f32) -> Self {
+        Self::new(Duration::from_secs_f32(1.0 / fps))
+    }
+
+    /// Move the clock forward by one step
+    pub fn tick(&self) {
+        self.ticks.set(self.ticks.get() + 1);
+    }
+
+    pub fn get_ticks(&self) -> u64 {
+        self.ticks.get()
+    }
+
+    pub fn set_ticks(&self, ticks: u64) {
+        self.ticks.set(ticks);
+    }
+
+    pub fn get_step(&self) -> Duration {
+        self.step
+    }
+}
+
+impl Clock for FixedStepClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.step.as_nanos() as u64 * self.ticks.get())
+    }
+}
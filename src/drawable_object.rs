@@ -1,3 +1,4 @@
+use crate::layers::DEFAULT_LAYER;
 use crate::load_image;
 use crate::shaders::draw_generate_normals;
 use crate::shaders::FULL_SCREEN_QUAD;
@@ -45,6 +46,29 @@ pub trait Drawable {
     ) {
     }
 
+    /// Draw on top of the albedo after every object has drawn its albedo and height.
+    /// The scene height contains the height of every object,
+    /// this is for effects that need to know what is in front of the object.
+    /// The scene height is only drawn if shadows or reflections are on, so this is only called then
+    fn draw_albedo_overlay(
+        &self,
+        _program: &LumenpyxProgram,
+        _transform: &Transform,
+        _albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+        _scene_height: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    ) {
+    }
+
+    /// Draw light that the object gives off on its own, this is drawn on top of the lit scene.
+    /// Only drawn if shadows are on, otherwise the albedo is shown as is
+    fn draw_emissive(
+        &self,
+        _program: &LumenpyxProgram,
+        _transform: &Transform,
+        _emissive_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+    }
+
     /// Load the shaders for the object
     /// This is called every frame, so make sure to check
     /// if the shader is already loaded or your performance will suffer
@@ -56,5 +80,21 @@ pub trait Drawable {
         0.5
     }
 
+    /// The id written to the pick buffer where the object is drawn, None can't be picked.
+    /// Wrap an object in a Pickable to give it an id
+    fn get_pick_id(&self) -> Option<u32> {
+        None
+    }
+
+    /// The name of the layer the object is on, wrap an object in a Layered to put it on another layer
+    fn get_layer(&self) -> &str {
+        DEFAULT_LAYER
+    }
+
+    /// Objects with a higher z index are drawn on top of objects with a lower one on the same layer
+    fn get_z_index(&self) -> i32 {
+        0
+    }
+
     fn set_transform(&mut self, transform: Transform);
 }
@@ -0,0 +1,352 @@
+use std::cmp::Ordering;
+
+use crate::Drawable;
+use crate::LumenpyxProgram;
+use crate::Transform;
+
+/// The layer drawables are on if they don't say otherwise
+pub const DEFAULT_LAYER: &str = "default";
+
+/// The settings of a named layer, set them with `LumenpyxProgram::set_layer`.
+/// Layers that were never set use the default settings
+#[derive(Copy, Clone, Debug)]
+pub struct Layer {
+    order: i32,
+    y_sort: bool,
+    lit: bool,
+    reflections: bool,
+    /// the bit of the layer masks of the lights that decides if they light this layer
+    light_layer: u32,
+}
+
+impl Default for Layer {
+    fn default() -> Self {
+        Layer {
+            order: 0,
+            y_sort: false,
+            lit: true,
+            reflections: true,
+            light_layer: 0,
+        }
+    }
+}
+
+impl Layer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Layers with a higher order are drawn on top of layers with a lower one, the default is 0
+    pub fn with_order(mut self, order: i32) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Draw the objects with the same z index lower on the screen on top, for top down games
+    pub fn with_y_sort(mut self, y_sort: bool) -> Self {
+        self.y_sort = y_sort;
+        self
+    }
+
+    /// If false, lights don't affect the layer and it's drawn at its full color.
+    /// Unlit layers are drawn on top of the lit scene, so they should be above the lit layers
+    pub fn with_lit(mut self, lit: bool) -> Self {
+        self.lit = lit;
+        self
+    }
+
+    /// If false, the layer doesn't reflect anything
+    pub fn with_reflections(mut self, reflections: bool) -> Self {
+        self.reflections = reflections;
+        self
+    }
+
+    /// Which of the 32 light layers from 0 to 31 the layer is on, lights only light the layers in their layer mask.
+    /// The default is 0
+    pub fn with_light_layer(mut self, light_layer: u32) -> Self {
+        if light_layer >= 32 {
+            panic!("Light layer must be from 0 to 31");
+        }
+        self.light_layer = light_layer;
+        self
+    }
+
+    pub fn get_order(&self) -> i32 {
+        self.order
+    }
+
+    pub fn get_y_sort(&self) -> bool {
+        self.y_sort
+    }
+
+    pub fn get_lit(&self) -> bool {
+        self.lit
+    }
+
+    pub fn get_reflections(&self) -> bool {
+        self.reflections
+    }
+
+    pub fn get_light_layer(&self) -> u32 {
+        self.light_layer
+    }
+}
+
+/// Puts an object on a layer with a z index.
+/// Put this in the drawables instead of the object itself
+pub struct Layered<'a, T>
+where
+    T: Drawable + ?Sized,
+{
+    object: &'a T,
+    /// added on top of the object's transform
+    transform: Transform,
+    layer: String,
+    z_index: i32,
+}
+
+impl<'a, T> Layered<'a, T>
+where
+    T: Drawable + ?Sized,
+{
+    /// Objects with a higher z index are drawn on top of objects with a lower one on the same layer
+    pub fn new(object: &'a T, layer: &str, z_index: i32) -> Self {
+        Self {
+            object,
+            transform: Transform::new([0.0, 0.0, 0.0]),
+            layer: layer.to_string(),
+            z_index,
+        }
+    }
+
+    pub fn set_layer(&mut self, layer: &str) {
+        self.layer = layer.to_string();
+    }
+
+    pub fn set_z_index(&mut self, z_index: i32) {
+        self.z_index = z_index;
+    }
+
+    /// Set the transform added on top of the object's transform
+    pub fn set_offset(&mut self, offset: Transform) {
+        self.transform = offset;
+    }
+
+    pub fn get_offset(&self) -> Transform {
+        self.transform
+    }
+}
+
+impl<'a, T> Drawable for Layered<'a, T>
+where
+    T: Drawable + ?Sized,
+{
+    fn draw_albedo(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_albedo(program, transform, albedo_framebuffer);
+    }
+
+    fn draw_height(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        height_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_height(program, transform, height_framebuffer);
+    }
+
+    fn draw_roughness(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        roughness_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_roughness(program, transform, roughness_framebuffer);
+    }
+
+    fn draw_normal(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        normal_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_normal(program, transform, normal_framebuffer);
+    }
+
+    fn draw_albedo_overlay(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+        scene_height: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    ) {
+        self.object
+            .draw_albedo_overlay(program, transform, albedo_framebuffer, scene_height);
+    }
+
+    fn draw_emissive(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        emissive_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_emissive(program, transform, emissive_framebuffer);
+    }
+
+    fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
+        self.object.try_load_shaders(program);
+    }
+
+    fn get_transform(&self) -> Transform {
+        self.object.get_transform().add_parent(&self.transform)
+    }
+
+    fn get_recieve_shadows_strength(&self) -> f32 {
+        self.object.get_recieve_shadows_strength()
+    }
+
+    fn get_pick_id(&self) -> Option<u32> {
+        self.object.get_pick_id()
+    }
+
+    fn get_layer(&self) -> &str {
+        &self.layer
+    }
+
+    fn get_z_index(&self) -> i32 {
+        self.z_index
+    }
+
+    /// Moves the object to the transform by changing the offset, so get_transform returns the transform afterwards
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = crate::get_wrapper_offset(&self.object.get_transform(), &transform);
+    }
+}
+
+/// sort the drawables by the order of their layers, then their z index, then their y if the layer is y sorted.
+/// The sort is stable, so drawables that are the same stay in the order they were given
+pub(crate) fn sort_drawables(drawables: &mut [&dyn Drawable], program: &LumenpyxProgram) {
+    sort_drawables_by_layers(drawables, |name| program.get_layer(name));
+}
+
+/// sort_drawables with the settings of the layers from get_layer, split out so it can be tested without a window
+fn sort_drawables_by_layers(drawables: &mut [&dyn Drawable], get_layer: impl Fn(&str) -> Layer) {
+    drawables.sort_by(|a, b| {
+        let (layer_a, layer_b) = (get_layer(a.get_layer()), get_layer(b.get_layer()));
+
+        let ordering = layer_a
+            .order
+            .cmp(&layer_b.order)
+            .then(a.get_z_index().cmp(&b.get_z_index()));
+        if ordering != Ordering::Equal || !(layer_a.y_sort && layer_b.y_sort) {
+            return ordering;
+        }
+
+        // higher up is further away, so it's drawn first
+        let (y_a, y_b) = (a.get_transform().get_y(), b.get_transform().get_y());
+        y_b.total_cmp(&y_a)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::Sprite;
+
+    fn sprite(y: f32) -> Sprite {
+        Sprite::new_untextured(Transform::new([0.0, y, 0.0]))
+    }
+
+    /// "sorted" is y sorted and drawn after the default layer, "flat" isn't y sorted
+    fn get_test_layer(name: &str) -> Layer {
+        match name {
+            "sorted" => Layer::new().with_order(1).with_y_sort(true),
+            "flat" => Layer::new().with_order(1),
+            _ => Layer::default(),
+        }
+    }
+
+    /// sort the drawables and give back where each of them ended up, by their index in the vec
+    fn sort(drawables: &[&dyn Drawable]) -> Vec<usize> {
+        let mut sorted = drawables.to_vec();
+        sort_drawables_by_layers(&mut sorted, get_test_layer);
+        sorted
+            .iter()
+            .map(|drawable| {
+                drawables
+                    .iter()
+                    .position(|other| std::ptr::addr_eq(*other, *drawable))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn equal_keys_keep_their_order() {
+        let sprites = [sprite(3.0), sprite(1.0), sprite(2.0)];
+        let layered: Vec<_> = sprites.iter().map(|s| Layered::new(s, "flat", 0)).collect();
+        let drawables: Vec<&dyn Drawable> = layered.iter().map(|l| l as &dyn Drawable).collect();
+
+        assert_eq!(sort(&drawables), [0, 1, 2]);
+    }
+
+    #[test]
+    fn lower_z_index_is_drawn_first() {
+        let sprites = [sprite(0.0), sprite(0.0), sprite(0.0)];
+        let layered = [
+            Layered::new(&sprites[0], DEFAULT_LAYER, 2),
+            Layered::new(&sprites[1], DEFAULT_LAYER, -1),
+            Layered::new(&sprites[2], DEFAULT_LAYER, 0),
+        ];
+        let drawables: Vec<&dyn Drawable> = layered.iter().map(|l| l as &dyn Drawable).collect();
+
+        assert_eq!(sort(&drawables), [1, 2, 0]);
+    }
+
+    #[test]
+    fn layer_order_comes_before_z_index() {
+        let sprites = [sprite(0.0), sprite(0.0)];
+        let layered = [
+            Layered::new(&sprites[0], "flat", -5),
+            Layered::new(&sprites[1], DEFAULT_LAYER, 5),
+        ];
+        let drawables: Vec<&dyn Drawable> = layered.iter().map(|l| l as &dyn Drawable).collect();
+
+        assert_eq!(sort(&drawables), [1, 0]);
+    }
+
+    #[test]
+    fn y_sort_only_applies_in_y_sorted_layers() {
+        let sprites = [sprite(1.0), sprite(3.0), sprite(2.0)];
+
+        // higher up is drawn first
+        let sorted: Vec<_> = sprites
+            .iter()
+            .map(|s| Layered::new(s, "sorted", 0))
+            .collect();
+        let drawables: Vec<&dyn Drawable> = sorted.iter().map(|l| l as &dyn Drawable).collect();
+        assert_eq!(sort(&drawables), [1, 2, 0]);
+
+        let flat: Vec<_> = sprites.iter().map(|s| Layered::new(s, "flat", 0)).collect();
+        let drawables: Vec<&dyn Drawable> = flat.iter().map(|l| l as &dyn Drawable).collect();
+        assert_eq!(sort(&drawables), [0, 1, 2]);
+
+        // the z index still comes before the y
+        let layered = [
+            Layered::new(&sprites[0], "sorted", 0),
+            Layered::new(&sprites[1], "sorted", 1),
+            Layered::new(&sprites[2], "sorted", 0),
+        ];
+        let drawables: Vec<&dyn Drawable> = layered.iter().map(|l| l as &dyn Drawable).collect();
+        assert_eq!(sort(&drawables), [2, 0, 1]);
+    }
+}
@@ -0,0 +1,938 @@
+use std::path::{Path, PathBuf};
+
+use rustc_hash::FxHashMap;
+use serde_json::Value;
+
+use crate::lights::{AreaLight, DirectionalLight, PointLight};
+use crate::primitives::{Normal, NormalInput, TextureInput};
+use crate::tilemap::{Tilemap, Tileset};
+use crate::LumenpyxProgram;
+use crate::Transform;
+
+const DEFAULT_LIGHT_HEIGHT: f32 = 1.0;
+const DEFAULT_LIGHT_INTENSITY: f32 = 1.0;
+const DEFAULT_LIGHT_FALLOFF: f32 = 0.02;
+
+/// Why an LDtk project couldn't be loaded
+#[derive(Debug)]
+pub enum LdtkError {
+    /// A file of the project couldn't be read
+    Io(PathBuf, std::io::Error),
+    /// An image of a tileset or a material atlas couldn't be loaded
+    Image(PathBuf, image::ImageError),
+    /// The file isn't a valid LDtk file, the string says what is wrong with it
+    Invalid(PathBuf, String),
+    /// The project uses something that isn't supported, the string says what
+    Unsupported(PathBuf, String),
+}
+
+impl std::fmt::Display for LdtkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LdtkError::Io(path, error) => {
+                write!(f, "Failed to read {}: {}", path.to_string_lossy(), error)
+            }
+            LdtkError::Image(path, error) => {
+                write!(
+                    f,
+                    "Failed to load image {}: {}",
+                    path.to_string_lossy(),
+                    error
+                )
+            }
+            LdtkError::Invalid(path, message) => {
+                write!(
+                    f,
+                    "Invalid LDtk file {}: {}",
+                    path.to_string_lossy(),
+                    message
+                )
+            }
+            LdtkError::Unsupported(path, message) => {
+                write!(
+                    f,
+                    "Unsupported LDtk file {}: {}",
+                    path.to_string_lossy(),
+                    message
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for LdtkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LdtkError::Io(_, error) => Some(error),
+            LdtkError::Image(_, error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+fn invalid(path: &Path, message: impl Into<String>) -> LdtkError {
+    LdtkError::Invalid(path.to_path_buf(), message.into())
+}
+
+fn unsupported(path: &Path, message: impl Into<String>) -> LdtkError {
+    LdtkError::Unsupported(path.to_path_buf(), message.into())
+}
+
+/// The material of every tile in a layer.
+/// The atlases line up with the layer's tileset image, if there is no atlas the value fills every tile.
+/// The atlas paths are relative to the project file, the same as the tileset images
+#[derive(Debug, Clone)]
+pub struct LdtkLayerMaterial {
+    height_atlas: Option<String>,
+    roughness_atlas: Option<String>,
+    normal_atlas: Option<String>,
+    height: f32,
+    roughness: f32,
+    shadow_strength: f32,
+}
+
+impl Default for LdtkLayerMaterial {
+    fn default() -> Self {
+        Self {
+            height_atlas: None,
+            roughness_atlas: None,
+            normal_atlas: None,
+            height: 0.0,
+            roughness: 1.0,
+            shadow_strength: 0.5,
+        }
+    }
+}
+
+impl LdtkLayerMaterial {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_height_atlas(mut self, path: &str) -> Self {
+        self.height_atlas = Some(path.to_string());
+        self
+    }
+
+    pub fn with_roughness_atlas(mut self, path: &str) -> Self {
+        self.roughness_atlas = Some(path.to_string());
+        self
+    }
+
+    /// If there is no normal atlas the normals are generated from the height
+    pub fn with_normal_atlas(mut self, path: &str) -> Self {
+        self.normal_atlas = Some(path.to_string());
+        self
+    }
+
+    pub fn with_height(mut self, height: f32) -> Self {
+        self.height = height;
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: f32) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    pub fn with_shadow_strength(mut self, shadow_strength: f32) -> Self {
+        self.shadow_strength = shadow_strength;
+        self
+    }
+}
+
+/// A layer of tiles from a Tiles, AutoLayer or IntGrid layer.
+/// LDtk can stack more than one tile in a cell, each extra tile goes in the next tilemap
+pub struct LdtkTileLayer {
+    pub name: String,
+    pub tilemaps: Vec<Tilemap>,
+}
+
+/// The values of an IntGrid layer, row by row with the first row at the top, 0 is empty
+pub struct LdtkIntGrid {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub values: Vec<i64>,
+}
+
+/// A level of an LDtk project.
+/// The level is centered on (0, 0), one pixel in LDtk is one unit in lumenpyx
+pub struct LdtkLevel {
+    pub identifier: String,
+    /// the center of the level in the world, add this to every transform to lay the levels out like in LDtk
+    pub world_position: [f32; 2],
+    /// in pixels
+    pub size: [u32; 2],
+    /// the bottom layer is first, so drawing them in order works
+    pub tile_layers: Vec<LdtkTileLayer>,
+    pub int_grids: Vec<LdtkIntGrid>,
+    pub point_lights: Vec<PointLight>,
+    pub area_lights: Vec<AreaLight>,
+    pub directional_lights: Vec<DirectionalLight>,
+}
+
+impl LdtkLevel {
+    /// Every tilemap in the level from the bottom to the top
+    pub fn get_tilemaps(&self) -> Vec<&Tilemap> {
+        self.tile_layers
+            .iter()
+            .flat_map(|layer| &layer.tilemaps)
+            .collect()
+    }
+}
+
+/// Loads the levels of an LDtk project.
+///
+/// Entities become lights when they have the tag `PointLight`, `AreaLight` or `DirectionalLight`,
+/// an entity with the tag `Light` is a point light. The fields of the entity are used for the light:
+/// - `color` (color), `intensity`, `height` (float) for every light
+/// - `falloff` (float) for point and area lights, the size of an area light is the size of the entity
+/// - `direction_x`, `direction_y`, `direction_z`, `angular_falloff` and `distance_falloff` (float) for directional lights
+///
+/// Flipped tiles are drawn without the flip
+#[derive(Default)]
+pub struct LdtkLoader {
+    materials: FxHashMap<String, LdtkLayerMaterial>,
+    default_material: LdtkLayerMaterial,
+}
+
+/// what every level of a project shares while it is loading
+struct LdtkProject<'a> {
+    tilesets: FxHashMap<i64, &'a Value>,
+    /// layers with the same material and tileset share the textures
+    tileset_cache: FxHashMap<(String, i64), Tileset>,
+    directory: PathBuf,
+    /// the project file, for the errors in the tileset definitions
+    path: &'a Path,
+}
+
+impl LdtkLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the material of every layer with this identifier
+    pub fn with_layer_material(mut self, layer: &str, material: LdtkLayerMaterial) -> Self {
+        self.materials.insert(layer.to_string(), material);
+        self
+    }
+
+    /// Set the material of the layers that don't have their own
+    pub fn with_default_material(mut self, material: LdtkLayerMaterial) -> Self {
+        self.default_material = material;
+        self
+    }
+
+    /// Load every level in the project, levels saved in separate files are loaded as well
+    pub fn load(
+        &self,
+        path: &str,
+        program: &mut LumenpyxProgram,
+    ) -> Result<Vec<LdtkLevel>, LdtkError> {
+        let path = Path::new(path);
+        let project_json = read_json(path)?;
+
+        let mut project = LdtkProject {
+            tilesets: FxHashMap::default(),
+            tileset_cache: FxHashMap::default(),
+            directory: path
+                .parent()
+                .map(|parent| parent.to_path_buf())
+                .unwrap_or_default(),
+            path,
+        };
+        for tileset in project_json["defs"]["tilesets"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            project
+                .tilesets
+                .insert(get_i64(tileset, "uid", path)?, tileset);
+        }
+
+        let mut levels = vec![];
+        for level in project_json["levels"].as_array().into_iter().flatten() {
+            let external_level;
+            let (level, level_path) = match level["externalRelPath"].as_str() {
+                Some(relative_path) if level["layerInstances"].is_null() => {
+                    let level_path = project.directory.join(relative_path);
+                    external_level = read_json(&level_path)?;
+                    (&external_level, level_path)
+                }
+                _ => (level, path.to_path_buf()),
+            };
+
+            levels.push(self.load_level(level, &level_path, &mut project, program)?);
+        }
+
+        Ok(levels)
+    }
+
+    fn load_level(
+        &self,
+        level: &Value,
+        path: &Path,
+        project: &mut LdtkProject,
+        program: &mut LumenpyxProgram,
+    ) -> Result<LdtkLevel, LdtkError> {
+        let size = [
+            get_i64(level, "pxWid", path)? as u32,
+            get_i64(level, "pxHei", path)? as u32,
+        ];
+
+        let mut tile_layers = vec![];
+        let mut int_grids = vec![];
+        let mut point_lights = vec![];
+        let mut area_lights = vec![];
+        let mut directional_lights = vec![];
+
+        // ldtk puts the top layer first
+        for layer in level["layerInstances"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .rev()
+        {
+            if layer["visible"].as_bool() == Some(false) {
+                continue;
+            }
+
+            let name = layer["__identifier"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            let offset = [
+                get_f32(layer, "__pxTotalOffsetX"),
+                get_f32(layer, "__pxTotalOffsetY"),
+            ];
+
+            match layer["__type"].as_str() {
+                Some("Entities") => {
+                    for entity in layer["entityInstances"].as_array().into_iter().flatten() {
+                        add_light(
+                            entity,
+                            offset,
+                            size,
+                            path,
+                            &mut point_lights,
+                            &mut area_lights,
+                            &mut directional_lights,
+                        )?;
+                    }
+                }
+                Some("IntGrid") | Some("AutoLayer") | Some("Tiles") => {
+                    if layer["__type"].as_str() == Some("IntGrid") {
+                        int_grids.push(LdtkIntGrid {
+                            name: name.clone(),
+                            width: get_i64(layer, "__cWid", path)? as u32,
+                            height: get_i64(layer, "__cHei", path)? as u32,
+                            values: layer["intGridCsv"]
+                                .as_array()
+                                .into_iter()
+                                .flatten()
+                                .map(|value| value.as_i64().unwrap_or(0))
+                                .collect(),
+                        });
+                    }
+
+                    let tilemaps =
+                        self.load_tile_layer(layer, &name, size, path, project, program)?;
+
+                    if !tilemaps.is_empty() {
+                        tile_layers.push(LdtkTileLayer { name, tilemaps });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let world_x = get_f32(level, "worldX");
+        let world_y = get_f32(level, "worldY");
+
+        Ok(LdtkLevel {
+            identifier: level["identifier"].as_str().unwrap_or_default().to_string(),
+            world_position: [
+                world_x + size[0] as f32 / 2.0,
+                -(world_y + size[1] as f32 / 2.0),
+            ],
+            size,
+            tile_layers,
+            int_grids,
+            point_lights,
+            area_lights,
+            directional_lights,
+        })
+    }
+
+    fn load_tile_layer(
+        &self,
+        layer: &Value,
+        name: &str,
+        level_size: [u32; 2],
+        path: &Path,
+        project: &mut LdtkProject,
+        program: &mut LumenpyxProgram,
+    ) -> Result<Vec<Tilemap>, LdtkError> {
+        let tileset_uid = match layer["__tilesetDefUid"].as_i64() {
+            Some(uid) => uid,
+            // an IntGrid layer without auto tiles has nothing to draw
+            None => return Ok(vec![]),
+        };
+        let tileset_json = *project.tilesets.get(&tileset_uid).ok_or_else(|| {
+            invalid(
+                path,
+                format!(
+                    "layer {} uses the tileset {} that isn't defined",
+                    name, tileset_uid
+                ),
+            )
+        })?;
+
+        let grid_size = get_i64(layer, "__gridSize", path)? as u32;
+        let tile_size = get_i64(tileset_json, "tileGridSize", project.path)? as u32;
+        if grid_size != tile_size {
+            return Err(unsupported(
+                path,
+                format!(
+                    "layer {} has a grid size of {} but its tileset has a grid size of {}, they must be the same",
+                    name, grid_size, tile_size
+                ),
+            ));
+        }
+        if get_i64(tileset_json, "spacing", project.path)? != 0
+            || get_i64(tileset_json, "padding", project.path)? != 0
+        {
+            return Err(unsupported(
+                project.path,
+                "tilesets with spacing or padding are not supported",
+            ));
+        }
+
+        let columns = get_i64(tileset_json, "__cWid", project.path)? as u32;
+        let grids = stack_tiles(layer, grid_size, columns, path)?;
+        if grids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let material = self.materials.get(name).unwrap_or(&self.default_material);
+
+        let key = (name.to_string(), tileset_uid);
+        let tileset = match project.tileset_cache.get(&key) {
+            Some(tileset) => *tileset,
+            None => {
+                let tileset = build_tileset(
+                    tileset_json,
+                    material,
+                    tile_size,
+                    &project.directory,
+                    project.path,
+                    program,
+                )?;
+                project.tileset_cache.insert(key, tileset);
+                tileset
+            }
+        };
+
+        if let Some(tile) = grids
+            .iter()
+            .flatten()
+            .flatten()
+            .flatten()
+            .find(|tile| **tile >= tileset.get_tile_count())
+        {
+            return Err(invalid(
+                path,
+                format!("tile {} in layer {} is outside of its tileset", tile, name),
+            ));
+        }
+
+        // ldtk counts from the top left, lumenpyx from the center going up
+        let offset = [
+            get_f32(layer, "__pxTotalOffsetX"),
+            get_f32(layer, "__pxTotalOffsetY"),
+        ];
+        let layer_size = [
+            (grids[0][0].len() as u32 * grid_size) as f32,
+            (grids[0].len() as u32 * grid_size) as f32,
+        ];
+        let x = offset[0] + layer_size[0] / 2.0 - level_size[0] as f32 / 2.0;
+        let y = level_size[1] as f32 / 2.0 - (offset[1] + layer_size[1] / 2.0);
+
+        Ok(grids
+            .into_iter()
+            .map(|grid| {
+                let mut tilemap = Tilemap::new(tileset, grid, Transform::new([x, y, 0.0]), program);
+                tilemap.set_shadow_strength(material.shadow_strength);
+                tilemap
+            })
+            .collect())
+    }
+}
+
+/// put the tiles of a layer on grids of tile ids.
+/// A cell can have more than one tile, every extra tile goes on the next grid
+fn stack_tiles(
+    layer: &Value,
+    grid_size: u32,
+    columns: u32,
+    path: &Path,
+) -> Result<Vec<Vec<Vec<Option<u32>>>>, LdtkError> {
+    if grid_size == 0 {
+        return Err(invalid(path, "layers must have a grid size above 0"));
+    }
+
+    let width = get_i64(layer, "__cWid", path)? as usize;
+    let height = get_i64(layer, "__cHei", path)? as usize;
+
+    let mut grids: Vec<Vec<Vec<Option<u32>>>> = vec![];
+    let tiles = layer["gridTiles"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .chain(layer["autoLayerTiles"].as_array().into_iter().flatten());
+    for tile in tiles {
+        let px = &tile["px"];
+        let x = px[0].as_i64().unwrap_or(0) as usize / grid_size as usize;
+        let y = px[1].as_i64().unwrap_or(0) as usize / grid_size as usize;
+        if x >= width || y >= height {
+            continue;
+        }
+
+        let tile_id = match tile["t"].as_i64() {
+            Some(tile_id) => u32::try_from(tile_id).map_err(|_| {
+                invalid(path, format!("tile {} is outside of its tileset", tile_id))
+            })?,
+            None => {
+                let src = &tile["src"];
+                let src_x = src[0].as_i64().unwrap_or(0) as u32 / grid_size;
+                let src_y = src[1].as_i64().unwrap_or(0) as u32 / grid_size;
+                if src_x >= columns {
+                    return Err(invalid(
+                        path,
+                        format!(
+                            "the tile at ({}, {}) in the tileset is outside of it",
+                            src_x, src_y
+                        ),
+                    ));
+                }
+                src_y * columns + src_x
+            }
+        };
+
+        let grid_index = match grids.iter().position(|grid| grid[y][x].is_none()) {
+            Some(grid_index) => grid_index,
+            None => {
+                grids.push(vec![vec![None; width]; height]);
+                grids.len() - 1
+            }
+        };
+        grids[grid_index][y][x] = Some(tile_id);
+    }
+
+    Ok(grids)
+}
+
+fn build_tileset(
+    tileset_json: &Value,
+    material: &LdtkLayerMaterial,
+    tile_size: u32,
+    directory: &Path,
+    path: &Path,
+    program: &mut LumenpyxProgram,
+) -> Result<Tileset, LdtkError> {
+    let image = tileset_json["relPath"]
+        .as_str()
+        .ok_or_else(|| unsupported(path, "tilesets without an image are not supported"))?;
+    let albedo = load_texture(&directory.join(image), program)?;
+    let albedo_size = albedo.dimensions();
+
+    let load_atlas = |atlas: &str| -> Result<glium::texture::Texture2d, LdtkError> {
+        let texture = load_texture(&directory.join(atlas), program)?;
+        if texture.dimensions() != albedo_size {
+            return Err(invalid(
+                path,
+                format!(
+                    "the atlas {} must be the same size as the tileset image",
+                    atlas
+                ),
+            ));
+        }
+        Ok(texture)
+    };
+
+    let channel = |atlas: &Option<String>, value: f32| -> Result<TextureInput, LdtkError> {
+        Ok(match atlas {
+            Some(atlas) => load_atlas(atlas)?.into(),
+            None => value.into(),
+        })
+    };
+
+    let height = channel(&material.height_atlas, material.height)?;
+    let roughness = channel(&material.roughness_atlas, material.roughness)?;
+    let normal = match &material.normal_atlas {
+        Some(atlas) => Normal::Texture(load_atlas(atlas)?),
+        None => Normal::AutoGenerated,
+    };
+
+    Ok(Tileset::new(
+        albedo.into(),
+        height,
+        roughness,
+        NormalInput::Normal(normal),
+        [tile_size, tile_size],
+        program,
+    ))
+}
+
+/// load an image the same way sprites do, but return the error instead of panicking
+fn load_texture(
+    path: &Path,
+    program: &LumenpyxProgram,
+) -> Result<glium::texture::Texture2d, LdtkError> {
+    let image = image::open(path)
+        .map_err(|error| LdtkError::Image(path.to_path_buf(), error))?
+        .to_rgba32f();
+    let dimensions = image.dimensions();
+    let raw_image = glium::texture::RawImage2d::from_raw_rgba_reversed(&image, dimensions);
+
+    Ok(glium::texture::Texture2d::new(&program.display, raw_image)
+        .expect("Failed to create tileset texture"))
+}
+
+fn add_light(
+    entity: &Value,
+    offset: [f32; 2],
+    level_size: [u32; 2],
+    path: &Path,
+    point_lights: &mut Vec<PointLight>,
+    area_lights: &mut Vec<AreaLight>,
+    directional_lights: &mut Vec<DirectionalLight>,
+) -> Result<(), LdtkError> {
+    let tags: Vec<String> = entity["__tags"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|tag| tag.as_str())
+        .map(|tag| tag.to_lowercase())
+        .collect();
+    let has_tag = |name: &str| tags.iter().any(|tag| tag == name);
+
+    let mut fields = FxHashMap::default();
+    for field in entity["fieldInstances"].as_array().into_iter().flatten() {
+        if let Some(name) = field["__identifier"].as_str() {
+            fields.insert(name.to_string(), &field["__value"]);
+        }
+    }
+    let get_field = |name: &str, default: f32| -> f32 {
+        fields
+            .get(name)
+            .and_then(|value| value.as_f64())
+            .map(|value| value as f32)
+            .unwrap_or(default)
+    };
+
+    let color = match fields.get("color").and_then(|value| value.as_str()) {
+        Some(color) => parse_color(color, path)?,
+        None => [1.0, 1.0, 1.0],
+    };
+    let intensity = get_field("intensity", DEFAULT_LIGHT_INTENSITY);
+    let z = get_field("height", DEFAULT_LIGHT_HEIGHT);
+
+    // px is the pivot of the entity, the light goes in the center
+    let width = get_f32(entity, "width");
+    let height = get_f32(entity, "height");
+    let pivot = &entity["__pivot"];
+    let px = &entity["px"];
+    let center_x = px[0].as_f64().unwrap_or(0.0) as f32
+        + (0.5 - pivot[0].as_f64().unwrap_or(0.0) as f32) * width;
+    let center_y = px[1].as_f64().unwrap_or(0.0) as f32
+        + (0.5 - pivot[1].as_f64().unwrap_or(0.0) as f32) * height;
+    let x = offset[0] + center_x - level_size[0] as f32 / 2.0;
+    let y = level_size[1] as f32 / 2.0 - (offset[1] + center_y);
+
+    if has_tag("pointlight") || has_tag("light") {
+        point_lights.push(PointLight::new(
+            [x, y, z],
+            color,
+            intensity,
+            get_field("falloff", DEFAULT_LIGHT_FALLOFF),
+        ));
+    } else if has_tag("arealight") {
+        area_lights.push(AreaLight::new(
+            [x, y, z],
+            color,
+            intensity,
+            get_field("falloff", DEFAULT_LIGHT_FALLOFF),
+            width,
+            height,
+        ));
+    } else if has_tag("directionallight") {
+        let default = DirectionalLight::default();
+        let default_direction = default.get_direction();
+        directional_lights.push(DirectionalLight::new(
+            [x, y, z],
+            [
+                get_field("direction_x", default_direction[0]),
+                // ldtk's y goes down
+                -get_field("direction_y", -default_direction[1]),
+                get_field("direction_z", default_direction[2]),
+            ],
+            color,
+            intensity,
+            get_field("angular_falloff", default.get_angular_falloff()),
+            get_field("distance_falloff", default.get_distance_falloff()),
+        ));
+    }
+
+    Ok(())
+}
+
+/// ldtk colors are #RRGGBB
+fn parse_color(value: &str, path: &Path) -> Result<[f32; 3], LdtkError> {
+    let hex = value.trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid(
+            path,
+            format!("the color {} must be in the format #RRGGBB", value),
+        ));
+    }
+
+    // every character is a hex digit, so this can't fail
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap() as f32 / 255.0;
+
+    Ok([channel(0), channel(2), channel(4)])
+}
+
+fn read_json(path: &Path) -> Result<Value, LdtkError> {
+    let text =
+        std::fs::read_to_string(path).map_err(|error| LdtkError::Io(path.to_path_buf(), error))?;
+    serde_json::from_str(&text).map_err(|error| invalid(path, error.to_string()))
+}
+
+fn get_i64(json: &Value, name: &str, path: &Path) -> Result<i64, LdtkError> {
+    json[name]
+        .as_i64()
+        .ok_or_else(|| invalid(path, format!("{} is missing", name)))
+}
+
+fn get_f32(json: &Value, name: &str) -> f32 {
+    json[name].as_f64().unwrap_or(0.0) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const LEVEL_SIZE: [u32; 2] = [64, 32];
+
+    fn load_lights(
+        entity: Value,
+        offset: [f32; 2],
+    ) -> (Vec<PointLight>, Vec<AreaLight>, Vec<DirectionalLight>) {
+        let mut point_lights = vec![];
+        let mut area_lights = vec![];
+        let mut directional_lights = vec![];
+        add_light(
+            &entity,
+            offset,
+            LEVEL_SIZE,
+            Path::new("level.ldtkl"),
+            &mut point_lights,
+            &mut area_lights,
+            &mut directional_lights,
+        )
+        .unwrap();
+
+        (point_lights, area_lights, directional_lights)
+    }
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        for i in 0..3 {
+            assert!((a[i] - b[i]).abs() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn parses_colors() {
+        let path = Path::new("project.ldtk");
+
+        assert_close(
+            parse_color("#FF8000", path).unwrap(),
+            [1.0, 128.0 / 255.0, 0.0],
+        );
+        assert_close(parse_color("00ff00", path).unwrap(), [0.0, 1.0, 0.0]);
+        assert!(matches!(
+            parse_color("#FFF", path),
+            Err(LdtkError::Invalid(..))
+        ));
+        assert!(matches!(
+            parse_color("#GG0000", path),
+            Err(LdtkError::Invalid(..))
+        ));
+        assert!(matches!(
+            parse_color("#ééé", path),
+            Err(LdtkError::Invalid(..))
+        ));
+    }
+
+    #[test]
+    fn places_lights_in_the_center_of_the_entity() {
+        // a 16x8 entity with its pivot in the bottom right, so its center is at (24, 12) in the layer
+        let entity = json!({
+            "__tags": ["PointLight"],
+            "__pivot": [1.0, 1.0],
+            "px": [32, 16],
+            "width": 16,
+            "height": 8,
+            "fieldInstances": [
+                { "__identifier": "color", "__value": "#FF0000" },
+                { "__identifier": "intensity", "__value": 2.0 },
+                { "__identifier": "height", "__value": 3.0 },
+            ],
+        });
+
+        let (point_lights, _, _) = load_lights(entity.clone(), [0.0, 0.0]);
+        assert_eq!(point_lights.len(), 1);
+        assert_close(
+            point_lights[0].get_position(),
+            [24.0 - 32.0, 16.0 - 12.0, 3.0],
+        );
+        assert_close(point_lights[0].get_color(), [1.0, 0.0, 0.0]);
+        assert_eq!(point_lights[0].get_intensity(), 2.0);
+
+        // the layer offset moves it right and down
+        let (point_lights, _, _) = load_lights(entity, [4.0, 2.0]);
+        assert_close(
+            point_lights[0].get_position(),
+            [24.0 - 28.0, 16.0 - 14.0, 3.0],
+        );
+    }
+
+    #[test]
+    fn picks_the_light_from_the_tags() {
+        let entity = |tag: &str| {
+            json!({
+                "__tags": [tag],
+                "__pivot": [0.0, 0.0],
+                "px": [0, 0],
+                "width": 8,
+                "height": 8,
+            })
+        };
+
+        let (point_lights, area_lights, directional_lights) =
+            load_lights(entity("Light"), [0.0; 2]);
+        assert_eq!(
+            (
+                point_lights.len(),
+                area_lights.len(),
+                directional_lights.len()
+            ),
+            (1, 0, 0)
+        );
+        let (point_lights, area_lights, directional_lights) =
+            load_lights(entity("AreaLight"), [0.0; 2]);
+        assert_eq!(
+            (
+                point_lights.len(),
+                area_lights.len(),
+                directional_lights.len()
+            ),
+            (0, 1, 0)
+        );
+        assert_close(area_lights[0].get_position(), [4.0 - 32.0, 16.0 - 4.0, 1.0]);
+        let (point_lights, area_lights, directional_lights) =
+            load_lights(entity("Enemy"), [0.0; 2]);
+        assert_eq!(
+            (
+                point_lights.len(),
+                area_lights.len(),
+                directional_lights.len()
+            ),
+            (0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn flips_the_y_of_directional_lights() {
+        let entity = json!({
+            "__tags": ["DirectionalLight"],
+            "__pivot": [0.5, 0.5],
+            "px": [32, 16],
+            "width": 8,
+            "height": 8,
+            "fieldInstances": [
+                { "__identifier": "direction_x", "__value": 0.25 },
+                { "__identifier": "direction_y", "__value": 0.5 },
+                { "__identifier": "direction_z", "__value": -1.0 },
+            ],
+        });
+
+        let (_, _, directional_lights) = load_lights(entity, [0.0; 2]);
+        assert_close(directional_lights[0].get_direction(), [0.25, -0.5, -1.0]);
+
+        // without the fields it is the default direction, not the flipped one
+        let entity = json!({ "__tags": ["DirectionalLight"] });
+        let (_, _, directional_lights) = load_lights(entity, [0.0; 2]);
+        assert_close(
+            directional_lights[0].get_direction(),
+            DirectionalLight::default().get_direction(),
+        );
+    }
+
+    #[test]
+    fn stacks_tiles_in_the_same_cell() {
+        let path = Path::new("level.ldtkl");
+        let layer = json!({
+            "__cWid": 2,
+            "__cHei": 2,
+            "gridTiles": [
+                { "px": [0, 0], "t": 1 },
+                { "px": [8, 8], "src": [16, 8] },
+                { "px": [0, 0], "t": 2 },
+            ],
+            "autoLayerTiles": [
+                { "px": [0, 0], "t": 3 },
+                // outside of the layer, so it is skipped
+                { "px": [16, 0], "t": 4 },
+            ],
+        });
+
+        let grids = stack_tiles(&layer, 8, 4, path).unwrap();
+        assert_eq!(
+            grids,
+            vec![
+                vec![vec![Some(1), None], vec![None, Some(6)]],
+                vec![vec![Some(2), None], vec![None, None]],
+                vec![vec![Some(3), None], vec![None, None]],
+            ]
+        );
+    }
+
+    #[test]
+    fn tiles_outside_of_the_tileset_are_an_error() {
+        let path = Path::new("level.ldtkl");
+        let layer = |tile: Value| json!({ "__cWid": 1, "__cHei": 1, "gridTiles": [tile] });
+
+        assert!(matches!(
+            stack_tiles(&layer(json!({ "px": [0, 0], "src": [32, 0] })), 8, 4, path),
+            Err(LdtkError::Invalid(..))
+        ));
+        assert!(matches!(
+            stack_tiles(&layer(json!({ "px": [0, 0], "t": -1 })), 8, 4, path),
+            Err(LdtkError::Invalid(..))
+        ));
+        assert!(matches!(
+            stack_tiles(&layer(json!({ "px": [0, 0], "t": 0 })), 0, 4, path),
+            Err(LdtkError::Invalid(..))
+        ));
+        assert!(matches!(
+            stack_tiles(&json!({ "__cWid": 1 }), 8, 4, path),
+            Err(LdtkError::Invalid(..))
+        ));
+    }
+}
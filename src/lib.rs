@@ -19,14 +19,46 @@ use shaders::*;
 /// This module contains all the objects that can be drawn in the program
 /// As well as containing the trait that all drawable objects must implement
 pub mod drawable_object;
+use clock::{Clock, SystemClock};
 use drawable_object::*;
 use rustc_hash::FxHashMap;
+use std::rc::Rc;
 pub mod animation;
+/// This module contains the importer for Aseprite files
+pub mod aseprite;
 pub mod blending;
+/// This module contains the camera controller for following, bounds and screen shake
+pub mod camera_controller;
+/// This module contains the clocks that drive animations and other time based objects
+pub mod clock;
+/// This module contains layers, for sorting drawables and choosing how they are lit
+pub mod layers;
+/// This module contains the loader for LDtk projects
+pub mod ldtk;
+/// This module contains the modifiers that make lights flicker, pulse, strobe and change color
+pub mod light_modifiers;
 /// This module contains all the lights that can be used in the program
 /// As well as containing the trait that all lights must implement
 pub mod lights;
+/// This module contains the outline and x-ray silhouette effect for drawables
+pub mod outline;
+/// This module contains the wrapper that gives drawables an id for picking them with the mouse
+pub mod picking;
+/// This module contains render targets, textures a scene can be drawn to instead of the window
+pub mod render_target;
+/// This module contains the scene, a tree of drawables and lights that is kept between frames
+pub mod scene;
 pub mod text;
+/// This module contains the importer for sheets packed by TexturePacker
+pub mod texture_packer;
+/// This module contains the importer for maps made in Tiled
+pub mod tiled;
+/// This module contains the tilemap, a grid of tiles drawn from a tileset
+pub mod tilemap;
+/// This module contains tweens, values that move between other values over time
+pub mod tween;
+/// This module contains viewports, for drawing several cameras into one window like split-screen
+pub mod viewport;
 
 const HANDLE_STRING_ID: &str = "wdAYG8&DWtyiwDhukhjwda";
 
@@ -96,6 +128,14 @@ pub struct LumenpyxProgram {
     font_context: Option<FontContext>,
     scale_context: Option<ScaleContext>,
     layout_context: Option<LayoutContext>,
+    clock: Rc<dyn Clock>,
+    /// put in front of the names of the textures the pipeline draws to, so every viewport has its own
+    pipeline_prefix: String,
+    /// the resolution of the viewport being drawn, None when drawing to the window
+    viewport_resolution: Option<[u32; 2]>,
+    /// the subpixel offset of the camera last drawn to the window, picking needs it to find the pixel
+    last_subpixel_offset: [f32; 2],
+    layers: FxHashMap<String, layers::Layer>,
 }
 
 impl LumenpyxProgram {
@@ -119,10 +159,16 @@ impl LumenpyxProgram {
                 render_resolution: None,
                 blur_reflections: false,
                 blur_strength: 0.01,
+                picking: false,
             },
             font_context: None,
             scale_context: None,
             layout_context: None,
+            clock: Rc::new(SystemClock::new()),
+            pipeline_prefix: String::new(),
+            viewport_resolution: None,
+            last_subpixel_offset: [0.0, 0.0],
+            layers: FxHashMap::default(),
         };
 
         program.set_name(name);
@@ -161,6 +207,21 @@ impl LumenpyxProgram {
             .get_texture(&format!("{}_{}", HANDLE_STRING_ID, handle.id))
     }
 
+    /// Set the settings of the layer with the name, drawables are put on layers with Layered
+    pub fn set_layer(&mut self, name: &str, layer: layers::Layer) {
+        self.layers.insert(name.to_string(), layer);
+    }
+
+    /// Get the settings of the layer with the name, the default settings if it was never set
+    pub fn get_layer(&self, name: &str) -> layers::Layer {
+        self.layers.get(name).copied().unwrap_or_default()
+    }
+
+    /// Go back to the default settings for the layer
+    pub fn remove_layer(&mut self, name: &str) {
+        self.layers.remove(name);
+    }
+
     /// Remove a shader from the program
     pub fn remove_shader(&mut self, name: &str) {
         self.shaders.remove(name);
@@ -176,6 +237,15 @@ impl LumenpyxProgram {
         self.debug = debug;
     }
 
+    /// Set the clock that new animations and other time based objects use, by default this is the real time
+    pub fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn get_clock(&self) -> Rc<dyn Clock> {
+        self.clock.clone()
+    }
+
     /// Set the render settings of the program
     pub fn set_render_settings(&mut self, settings: RenderSettings) {
         self.render_settings = settings;
@@ -225,11 +295,227 @@ impl LumenpyxProgram {
     }
 
     pub(crate) fn get_render_resolution(&self) -> [u32; 2] {
+        if let Some(viewport_resolution) = self.viewport_resolution {
+            return viewport_resolution;
+        }
+
         self.render_settings
             .render_resolution
             .unwrap_or(self.dimensions)
     }
 
+    /// the name of a texture the pipeline draws to for the viewport being drawn
+    pub(crate) fn get_pipeline_texture_name(&self, name: &str) -> String {
+        format!("{}{}", self.pipeline_prefix, name)
+    }
+
+    /// get a texture the pipeline draws to for the viewport being drawn
+    pub(crate) fn get_pipeline_texture(&self, name: &str) -> Option<&glium::texture::Texture2d> {
+        self.cache
+            .get_texture(&self.get_pipeline_texture_name(name))
+    }
+
+    /// draw to the textures of a viewport instead of the window's until end_viewport is called
+    pub(crate) fn begin_viewport(&mut self, name: &str, resolution: [u32; 2]) {
+        self.pipeline_prefix = get_viewport_prefix(name);
+        self.viewport_resolution = Some(resolution);
+    }
+
+    pub(crate) fn end_viewport(&mut self) {
+        self.pipeline_prefix = String::new();
+        self.viewport_resolution = None;
+    }
+
+    /// Turn a position in physical pixels on the window, like the mouse position from CursorMoved,
+    /// into the position in the world the camera sees there. This works with any window size and render resolution
+    pub fn screen_to_world(&self, camera: &Camera, physical_position: [f32; 2]) -> [f32; 2] {
+        let window_size = self.window.inner_size();
+        let pixel_scale = self.get_screen_pixel_scale();
+
+        // pixels from the center of the image with y going up
+        let screen_position = [
+            (physical_position[0] - window_size.width as f32 / 2.0) / pixel_scale,
+            (window_size.height as f32 / 2.0 - physical_position[1]) / pixel_scale,
+        ];
+
+        screen_offset_to_world(camera, screen_position)
+    }
+
+    /// Turn a position in physical pixels on the window into the position in the world the camera of the viewport sees there,
+    /// for viewports drawn with draw_viewports. None if the position isn't in the viewport
+    pub fn screen_to_world_in_viewport(
+        &self,
+        viewport: &viewport::Viewport,
+        physical_position: [f32; 2],
+    ) -> Option<[f32; 2]> {
+        let window_size = self.window.inner_size();
+        let pixel = viewport.window_to_viewport_pixel(
+            (window_size.width, window_size.height),
+            self.dimensions,
+            physical_position,
+        )?;
+
+        let resolution = viewport.get_resolution();
+        Some(screen_offset_to_world(
+            &viewport.camera,
+            [
+                pixel[0] - resolution[0] as f32 / 2.0,
+                pixel[1] - resolution[1] as f32 / 2.0,
+            ],
+        ))
+    }
+
+    /// Turn a position in the world into the position in physical pixels on the window the camera draws it at,
+    /// 0, 0 is the top left of the window. Positions the camera can't see are outside the window
+    pub fn world_to_screen(&self, camera: &Camera, world_position: [f32; 2]) -> [f32; 2] {
+        let window_size = self.window.inner_size();
+        let pixel_scale = self.get_screen_pixel_scale();
+
+        // the pixel snapping is undone by the offset when upscaling, so the real camera position is used
+        let [x, y] = rotate_by_camera(
+            [
+                world_position[0] - camera.position[0],
+                world_position[1] - camera.position[1],
+            ],
+            camera.rotation,
+        );
+
+        [
+            window_size.width as f32 / 2.0 + x * camera.zoom * pixel_scale,
+            window_size.height as f32 / 2.0 - y * camera.zoom * pixel_scale,
+        ]
+    }
+
+    /// Get the pick id of the object drawn at the position in physical pixels on the window in the last draw_all,
+    /// None if nothing with an id is there. Picking has to be on in the render settings, otherwise this is always None
+    pub fn pick(&self, physical_position: [f32; 2]) -> Option<u32> {
+        debug_assert!(
+            self.render_settings.picking,
+            "Picking has to be turned on in the render settings to pick"
+        );
+        if !self.render_settings.picking {
+            return None;
+        }
+
+        let window_size = self.window.inner_size();
+        let pixel_scale = self.get_screen_pixel_scale();
+
+        // pixels from the center of the image with y going up
+        let screen_position = [
+            (physical_position[0] - window_size.width as f32 / 2.0) / pixel_scale,
+            (window_size.height as f32 / 2.0 - physical_position[1]) / pixel_scale,
+        ];
+        // the black bars can't be picked
+        if screen_position[0].abs() > self.dimensions[0] as f32 / 2.0
+            || screen_position[1].abs() > self.dimensions[1] as f32 / 2.0
+        {
+            return None;
+        }
+
+        // the render resolution is cropped around the center, and the image was moved by the subpixel offset
+        let render_resolution = self
+            .render_settings
+            .render_resolution
+            .unwrap_or(self.dimensions);
+        let pixel = [
+            render_resolution[0] as f32 / 2.0 + screen_position[0] + self.last_subpixel_offset[0],
+            render_resolution[1] as f32 / 2.0 + screen_position[1] + self.last_subpixel_offset[1],
+        ];
+
+        self.read_pick_id("id_texture", pixel, render_resolution)
+    }
+
+    /// Get the pick id of the object drawn at the position in physical pixels on the window
+    /// in the last draw_viewports, None if nothing with an id is there or the position isn't in the viewport.
+    /// Picking has to be on in the render settings, otherwise this is always None,
+    /// and the camera of the viewport shouldn't have moved since it was drawn
+    pub fn pick_in_viewport(
+        &self,
+        viewport: &viewport::Viewport,
+        physical_position: [f32; 2],
+    ) -> Option<u32> {
+        debug_assert!(
+            self.render_settings.picking,
+            "Picking has to be turned on in the render settings to pick"
+        );
+        if !self.render_settings.picking {
+            return None;
+        }
+
+        let window_size = self.window.inner_size();
+        let pixel = viewport.window_to_viewport_pixel(
+            (window_size.width, window_size.height),
+            self.dimensions,
+            physical_position,
+        )?;
+
+        // the viewport was moved by the subpixel offset of its camera when it was put in the window
+        let offset = viewport.camera.get_subpixel_offset();
+        let texture_name = format!("{}id_texture", get_viewport_prefix(viewport.get_name()));
+        self.read_pick_id(
+            &texture_name,
+            [pixel[0] + offset[0], pixel[1] + offset[1]],
+            viewport.get_resolution(),
+        )
+    }
+
+    /// read the id in the id texture at the pixel, the pixel is in the resolution of the texture from the bottom left
+    fn read_pick_id(
+        &self,
+        texture_name: &str,
+        pixel: [f32; 2],
+        resolution: [u32; 2],
+    ) -> Option<u32> {
+        let pixel = [pixel[0].floor(), pixel[1].floor()];
+        if pixel[0] < 0.0
+            || pixel[1] < 0.0
+            || pixel[0] >= resolution[0] as f32
+            || pixel[1] >= resolution[1] as f32
+        {
+            return None;
+        }
+
+        let id_texture = self.cache.get_texture(texture_name)?;
+
+        // only copy the one pixel back instead of the whole texture
+        let pixel_texture = glium::texture::Texture2d::empty_with_format(
+            &self.display,
+            glium::texture::UncompressedFloatFormat::U8U8U8U8,
+            glium::texture::MipmapsOption::NoMipmap,
+            1,
+            1,
+        )
+        .expect("Failed to create pick texture");
+        id_texture.as_surface().blit_color(
+            &glium::Rect {
+                left: pixel[0] as u32,
+                bottom: pixel[1] as u32,
+                width: 1,
+                height: 1,
+            },
+            &pixel_texture.as_surface(),
+            &glium::BlitTarget {
+                left: 0,
+                bottom: 0,
+                width: 1,
+                height: 1,
+            },
+            glium::uniforms::MagnifySamplerFilter::Nearest,
+        );
+
+        let pixels: Vec<Vec<(u8, u8, u8, u8)>> = pixel_texture.read();
+        let (r, g, b, a) = pixels[0][0];
+        picking::decode_pick_id([r, g, b, a])
+    }
+
+    /// how many physical pixels of the window one pixel of the resolution is drawn as,
+    /// the render resolution is cropped to the resolution without scaling so it doesn't matter here
+    fn get_screen_pixel_scale(&self) -> f32 {
+        let window_size = self.window.inner_size();
+        let scales = get_letterbox_scales((window_size.width, window_size.height), self.dimensions);
+        scales[0] * window_size.width as f32 / self.dimensions[0] as f32
+    }
+
     pub(crate) fn adjust_transform_for_drawable(
         &self,
         transform: &Transform,
@@ -238,19 +524,28 @@ impl LumenpyxProgram {
         let render_resolution = self.get_render_resolution();
         let mut new_transform = transform.clone();
 
-        let mut scale = transform.get_scale();
-        // scale off the resolution
+        // scale off the resolution, this is done after the rotation so rotated objects aren't stretched
+        let mut screen_scale = [
+            transform.screen_scale[0] * camera.zoom,
+            transform.screen_scale[1] * camera.zoom,
+        ];
         if render_resolution[0] > render_resolution[1] {
-            scale[0] *= render_resolution[1] as f32 / render_resolution[0] as f32;
+            screen_scale[0] *= render_resolution[1] as f32 / render_resolution[0] as f32;
         } else {
-            scale[1] *= render_resolution[0] as f32 / render_resolution[1] as f32;
+            screen_scale[1] *= render_resolution[0] as f32 / render_resolution[1] as f32;
         }
-        new_transform.set_scale(scale[0], scale[1], scale[2]);
+        new_transform.screen_scale = screen_scale;
 
-        let (mut x, mut y, z) = (transform.get_x(), transform.get_y(), transform.get_z());
+        let (x, y, z) = (transform.get_x(), transform.get_y(), transform.get_z());
         // adjust off the camera no need to translate the z, it would just mess up the height map's interaction with the light
-        x -= camera.position[0];
-        y -= camera.position[1];
+        let camera_position = camera.get_render_position();
+        let [mut x, mut y] = rotate_by_camera(
+            [x - camera_position[0], y - camera_position[1]],
+            camera.rotation,
+        );
+        x *= camera.zoom;
+        y *= camera.zoom;
+        new_transform.set_rotation(transform.get_rotation() - camera.rotation);
 
         x /= render_resolution[0] as f32;
         y /= render_resolution[1] as f32;
@@ -350,11 +645,19 @@ impl LumenpyxCache {
     }
 }
 
-/// The transform struct is used to determine the position and scale of an object
+/// The transform struct is used to determine the position, rotation, scale and skew of an object.
+/// Positions are in pixels, a scale of 1.0 is the normal size of the object,
+/// and a positive rotation in radians turns the object clockwise
 #[derive(Copy, Clone)]
 pub struct Transform {
-    matrix: [[f32; 4]; 4],
+    position: [f32; 3],
     rotation: f32,
+    scale: [f32; 3],
+    /// the angle in radians the y axis of the object leans to the side, 0.0 is no skew
+    skew: f32,
+    /// scales the object along the screen after it is rotated,
+    /// only used by the transforms that are drawn so the aspect ratio of the render resolution doesn't stretch rotated objects
+    screen_scale: [f32; 2],
 }
 
 impl Default for Transform {
@@ -364,87 +667,130 @@ impl Default for Transform {
 }
 
 impl Transform {
+    /// Make a transform out of a matrix, the matrix is split back into the position, rotation, scale and skew.
+    /// Only the 2d part, the z scale and the position are kept, anything else like perspective is dropped,
+    /// so get_matrix gives back the same matrix only for matrices made by a transform
     pub fn from_matrix(matrix: [[f32; 4]; 4]) -> Transform {
+        let (rotation, skew, scale) =
+            decompose_linear([[matrix[0][0], matrix[1][0]], [matrix[0][1], matrix[1][1]]]);
+
         Transform {
-            matrix,
-            rotation: 0.0,
+            position: [matrix[3][0], matrix[3][1], matrix[3][2]],
+            rotation,
+            scale: [scale[0], scale[1], matrix[2][2]],
+            skew,
+            screen_scale: [1.0, 1.0],
         }
     }
 
     pub fn new(pos: [f32; 3]) -> Transform {
         Transform {
-            matrix: [
-                [1.0, 0.0, 0.0, 0.0],
-                [0.0, 1.0, 0.0, 0.0],
-                [0.0, 0.0, 1.0, 0.0],
-                [pos[0], pos[1], pos[2], 1.0],
-            ],
+            position: pos,
             rotation: 0.0,
+            scale: [1.0, 1.0, 1.0],
+            skew: 0.0,
+            screen_scale: [1.0, 1.0],
         }
     }
 
+    /// the 2d part of the transform without the position, as rows
+    fn get_linear(&self) -> [[f32; 2]; 2] {
+        let rotation_skew_scale =
+            multiply_linear(self.get_rotation_linear(), self.get_skew_scale());
+        multiply_linear(
+            [[self.screen_scale[0], 0.0], [0.0, self.screen_scale[1]]],
+            rotation_skew_scale,
+        )
+    }
+
+    fn get_rotation_linear(&self) -> [[f32; 2]; 2] {
+        // clockwise
+        let (sin, cos) = self.rotation.sin_cos();
+        [[cos, sin], [-sin, cos]]
+    }
+
+    fn get_skew_scale(&self) -> [[f32; 2]; 2] {
+        [
+            [self.scale[0], self.skew.tan() * self.scale[1]],
+            [0.0, self.scale[1]],
+        ]
+    }
+
     /// Get the matrix of the transform
     pub fn get_matrix(&self) -> [[f32; 4]; 4] {
-        let rotation_matrix = [
-            [self.rotation.cos(), -self.rotation.sin(), 0.0, 0.0],
-            [self.rotation.sin(), self.rotation.cos(), 0.0, 0.0],
-            [0.0, 0.0, 1.0, 0.0],
-            [0.0, 0.0, 0.0, 1.0],
-        ];
+        self.get_matrix_with_pivot([0.0, 0.0])
+    }
 
-        let mut self_matrix = self.matrix;
-        self_matrix[3][2] = 0.0;
+    /// Get the matrix of the transform, rotating around the pivot instead of the center.
+    /// The pivot is in the object's local space, -1.0 to 1.0 covers the whole object
+    pub fn get_matrix_with_pivot(&self, pivot: [f32; 2]) -> [[f32; 4]; 4] {
+        let linear = self.get_linear();
 
-        multiply_matrix(rotation_matrix, self_matrix)
+        // the pivot stays where it would be without the rotation
+        let unrotated = multiply_linear(
+            [[self.screen_scale[0], 0.0], [0.0, self.screen_scale[1]]],
+            self.get_skew_scale(),
+        );
+        let unrotated_pivot = apply_linear(unrotated, pivot);
+        let rotated_pivot = apply_linear(linear, pivot);
+
+        // the z position is left out, it would just mess up the height map's interaction with the light
+        [
+            [linear[0][0], linear[1][0], 0.0, 0.0],
+            [linear[0][1], linear[1][1], 0.0, 0.0],
+            [0.0, 0.0, self.scale[2], 0.0],
+            [
+                self.position[0] + unrotated_pivot[0] - rotated_pivot[0],
+                self.position[1] + unrotated_pivot[1] - rotated_pivot[1],
+                0.0,
+                1.0,
+            ],
+        ]
     }
 
-    /// Set the matrix of the transform
+    /// Set the position of the transform
     pub fn translate(&mut self, x: f32, y: f32, z: f32) {
-        self.matrix[3][0] = x;
-        self.matrix[3][1] = y;
-        self.matrix[3][2] = z;
+        self.position = [x, y, z];
     }
 
     /// set the scale of the transform
     pub fn set_scale(&mut self, x: f32, y: f32, z: f32) {
-        self.matrix[0][0] = x;
-        self.matrix[1][1] = y;
-        self.matrix[2][2] = z;
+        self.scale = [x, y, z];
     }
 
     /// get the scale of the transform
     pub fn get_scale(&self) -> [f32; 3] {
-        [self.matrix[0][0], self.matrix[1][1], self.matrix[2][2]]
+        self.scale
     }
 
     /// set the x position of the transform
     pub fn set_x(&mut self, x: f32) {
-        self.matrix[3][0] = x;
+        self.position[0] = x;
     }
 
     /// get the x position of the transform
     pub fn get_x(&self) -> f32 {
-        self.matrix[3][0]
+        self.position[0]
     }
 
     /// set the y position of the transform
     pub fn set_y(&mut self, y: f32) {
-        self.matrix[3][1] = y;
+        self.position[1] = y;
     }
 
     /// get the y position of the transform
     pub fn get_y(&self) -> f32 {
-        self.matrix[3][1]
+        self.position[1]
     }
 
     /// set the z position of the transform
     pub fn set_z(&mut self, z: f32) {
-        self.matrix[3][2] = z;
+        self.position[2] = z;
     }
 
     /// get the z position of the transform
     pub fn get_z(&self) -> f32 {
-        self.matrix[3][2]
+        self.position[2]
     }
 
     /// set the rotation of the transform
@@ -456,32 +802,113 @@ impl Transform {
         self.rotation
     }
 
-    pub fn add_parent(&self, parent: &Transform) -> Transform {
-        let mut new_transform = Transform::new([0.0, 0.0, 0.0]);
+    /// Set the angle in radians the object leans, the top moves right for positive angles
+    pub fn set_skew(&mut self, angle: f32) {
+        self.skew = angle;
+    }
 
-        new_transform.set_x(self.get_x() + parent.get_x());
-        new_transform.set_y(self.get_y() + parent.get_y());
-        new_transform.set_z(self.get_z() + parent.get_z());
+    pub fn get_skew(&self) -> f32 {
+        self.skew
+    }
 
-        new_transform.set_rotation(self.get_rotation() + parent.get_rotation());
+    /// Turn the transform around a point in the same space as its position, the angle is added to its rotation
+    pub fn rotate_around(&mut self, pivot: [f32; 2], angle: f32) {
+        let mut rotation = Transform::new([0.0, 0.0, 0.0]);
+        rotation.set_rotation(angle);
+        let offset =
+            rotation.transform_vector([self.position[0] - pivot[0], self.position[1] - pivot[1]]);
 
-        new_transform.set_scale(
-            self.get_scale()[0] * parent.get_scale()[0],
-            self.get_scale()[1] * parent.get_scale()[1],
-            self.get_scale()[2] * parent.get_scale()[2],
-        );
+        self.position[0] = pivot[0] + offset[0];
+        self.position[1] = pivot[1] + offset[1];
+        self.rotation += angle;
+    }
 
-        new_transform
+    /// Move a point from the space of the transform into the space its position is in,
+    /// e.g. from the space of a child to the world
+    pub fn transform_point(&self, point: [f32; 2]) -> [f32; 2] {
+        let vector = self.transform_vector(point);
+        [self.position[0] + vector[0], self.position[1] + vector[1]]
+    }
+
+    /// Like transform_point but without moving it by the position, for directions and offsets
+    pub fn transform_vector(&self, vector: [f32; 2]) -> [f32; 2] {
+        apply_linear(self.get_linear(), vector)
+    }
+
+    /// Get the transform that undoes this one, None if it can't be undone because the scale is 0.0
+    pub fn inverse(&self) -> Option<Transform> {
+        let linear = self.get_linear();
+        let determinant = linear[0][0] * linear[1][1] - linear[0][1] * linear[1][0];
+        if determinant == 0.0 || self.scale[2] == 0.0 {
+            return None;
+        }
+
+        let inverse_linear = [
+            [linear[1][1] / determinant, -linear[0][1] / determinant],
+            [-linear[1][0] / determinant, linear[0][0] / determinant],
+        ];
+        let position = apply_linear(inverse_linear, [self.position[0], self.position[1]]);
+        let (rotation, skew, scale) = decompose_linear(inverse_linear);
+
+        Some(Transform {
+            position: [
+                -position[0],
+                -position[1],
+                -self.position[2] / self.scale[2],
+            ],
+            rotation,
+            scale: [scale[0], scale[1], 1.0 / self.scale[2]],
+            skew,
+            screen_scale: [1.0, 1.0],
+        })
+    }
+
+    /// Put this transform inside the parent, the position, rotation, scale and skew of the parent are all applied to it
+    pub fn add_parent(&self, parent: &Transform) -> Transform {
+        let position = parent.transform_point([self.position[0], self.position[1]]);
+        let linear = multiply_linear(parent.get_linear(), self.get_linear());
+        let (rotation, skew, scale) = decompose_linear(linear);
+
+        Transform {
+            position: [
+                position[0],
+                position[1],
+                parent.position[2] + self.position[2] * parent.scale[2],
+            ],
+            rotation,
+            scale: [scale[0], scale[1], self.scale[2] * parent.scale[2]],
+            skew,
+            screen_scale: [1.0, 1.0],
+        }
+    }
+
+    /// Get the transform t of the way to the other transform, t is usually from 0.0 to 1.0
+    pub fn interpolate(&self, other: &Transform, t: f32) -> Transform {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        let lerp_3 =
+            |a: [f32; 3], b: [f32; 3]| [lerp(a[0], b[0]), lerp(a[1], b[1]), lerp(a[2], b[2])];
+
+        Transform {
+            position: lerp_3(self.position, other.position),
+            rotation: lerp(self.rotation, other.rotation),
+            scale: lerp_3(self.scale, other.scale),
+            skew: lerp(self.skew, other.skew),
+            screen_scale: [
+                lerp(self.screen_scale[0], other.screen_scale[0]),
+                lerp(self.screen_scale[1], other.screen_scale[1]),
+            ],
+        }
     }
 }
 
-fn multiply_matrix(matrix1: [[f32; 4]; 4], matrix2: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
-    let mut new_matrix = [[0.0; 4]; 4];
+/// multiply two 2x2 matrices stored as rows
+fn multiply_linear(matrix1: [[f32; 2]; 2], matrix2: [[f32; 2]; 2]) -> [[f32; 2]; 2] {
+    let mut new_matrix = [[0.0; 2]; 2];
 
-    for i in 0..4 {
-        for j in 0..4 {
-            for k in 0..4 {
-                new_matrix[i][j] = new_matrix[i][j] + matrix1[i][k] * matrix2[k][j];
+    for i in 0..2 {
+        for j in 0..2 {
+            for k in 0..2 {
+                new_matrix[i][j] += matrix1[i][k] * matrix2[k][j];
             }
         }
     }
@@ -489,28 +916,51 @@ fn multiply_matrix(matrix1: [[f32; 4]; 4], matrix2: [[f32; 4]; 4]) -> [[f32; 4];
     new_matrix
 }
 
-/// a is the parent
-impl<'a, 'b> std::ops::Add<&'b Transform> for &'a Transform {
-    type Output = Transform;
+fn apply_linear(matrix: [[f32; 2]; 2], vector: [f32; 2]) -> [f32; 2] {
+    [
+        matrix[0][0] * vector[0] + matrix[0][1] * vector[1],
+        matrix[1][0] * vector[0] + matrix[1][1] * vector[1],
+    ]
+}
 
-    fn add(self, other: &'b Transform) -> Transform {
-        let mut new_transform = Transform::new([0.0, 0.0, 0.0]);
+/// split a 2x2 matrix stored as rows into the clockwise rotation, the skew and the x and y scale,
+/// a flip ends up as a negative y scale
+fn decompose_linear(matrix: [[f32; 2]; 2]) -> (f32, f32, [f32; 2]) {
+    let scale_x = matrix[0][0].hypot(matrix[1][0]);
+    let rotation = if scale_x == 0.0 {
+        0.0
+    } else {
+        (-matrix[1][0]).atan2(matrix[0][0])
+    };
 
-        new_transform.translate(
-            self.get_x() + other.get_x(),
-            self.get_y() + other.get_y(),
-            self.get_z() + other.get_z(),
-        );
+    // undo the rotation to get the skew and the y scale
+    let (sin, cos) = rotation.sin_cos();
+    let skewed_y = cos * matrix[0][1] - sin * matrix[1][1];
+    let scale_y = sin * matrix[0][1] + cos * matrix[1][1];
+    let skew = if scale_y == 0.0 {
+        0.0
+    } else {
+        (skewed_y / scale_y).atan()
+    };
 
-        new_transform.set_rotation(self.get_rotation() + other.get_rotation());
+    (rotation, skew, [scale_x, scale_y])
+}
 
-        new_transform.set_scale(
-            self.get_x() * other.get_x(),
-            self.get_y() * other.get_y(),
-            self.get_z() * other.get_z(),
-        );
+/// get the offset a wrapper adds on top of the object's transform so the two together are the transform.
+/// If the object's transform can't be undone because its scale is 0.0, the transform is used as the offset
+pub(crate) fn get_wrapper_offset(object_transform: &Transform, transform: &Transform) -> Transform {
+    match object_transform.inverse() {
+        Some(inverse) => inverse.add_parent(transform),
+        None => *transform,
+    }
+}
 
-        new_transform
+/// a is the parent
+impl<'a, 'b> std::ops::Add<&'b Transform> for &'a Transform {
+    type Output = Transform;
+
+    fn add(self, other: &'b Transform) -> Transform {
+        other.add_parent(self)
     }
 }
 
@@ -579,15 +1029,107 @@ fn setup_window() -> (
 #[derive(Copy, Clone)]
 pub struct Camera {
     pub position: [f32; 3],
+    zoom: f32,
+    rotation: f32,
+    pixel_snap: bool,
 }
 
 impl Camera {
     /// the z position of the camera is just for reflection purposes
     pub fn new(position: [f32; 3]) -> Camera {
-        Camera { position }
+        Camera {
+            position,
+            zoom: 1.0,
+            rotation: 0.0,
+            pixel_snap: false,
+        }
+    }
+
+    /// Set how much bigger everything is drawn, 2.0 draws every pixel as 2x2 pixels.
+    /// Whole numbers keep pixel art crisp, other values work but pixels won't all be the same size
+    pub fn set_zoom(&mut self, zoom: f32) {
+        if zoom <= 0.0 {
+            panic!("Camera zoom must be greater than 0");
+        }
+        self.zoom = zoom;
+    }
+
+    pub fn get_zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// Set the rotation of the camera in radians, the scene is turned the opposite way.
+    /// This goes the same direction as the rotation of a transform
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation;
+    }
+
+    pub fn get_rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    /// If true, the scene is drawn from the camera position rounded to the nearest render pixel so pixel art doesn't shimmer,
+    /// and the part that was rounded off is made up for by moving the whole image when it's upscaled to the window.
+    /// This keeps scrolling smooth when the window is bigger than the render resolution
+    pub fn set_pixel_snap(&mut self, pixel_snap: bool) {
+        self.pixel_snap = pixel_snap;
+    }
+
+    pub fn get_pixel_snap(&self) -> bool {
+        self.pixel_snap
+    }
+
+    /// the position the scene is drawn from, rounded to render pixels if pixel snapping is on
+    pub(crate) fn get_render_position(&self) -> [f32; 2] {
+        if !self.pixel_snap {
+            return [self.position[0], self.position[1]];
+        }
+
+        [
+            (self.position[0] * self.zoom).round() / self.zoom,
+            (self.position[1] * self.zoom).round() / self.zoom,
+        ]
+    }
+
+    /// how far in render pixels on the screen the image has to be moved to make up for the pixel snapping
+    pub(crate) fn get_subpixel_offset(&self) -> [f32; 2] {
+        let render_position = self.get_render_position();
+        let offset = [
+            (self.position[0] - render_position[0]) * self.zoom,
+            (self.position[1] - render_position[1]) * self.zoom,
+        ];
+
+        rotate_by_camera(offset, self.rotation)
     }
 }
 
+/// the prefix of the names of the textures the pipeline draws to for a viewport
+fn get_viewport_prefix(name: &str) -> String {
+    format!("viewport_{}_", name)
+}
+
+/// turn a position in render pixels from the center of what the camera sees, with y going up, into the world
+fn screen_offset_to_world(camera: &Camera, screen_position: [f32; 2]) -> [f32; 2] {
+    let [x, y] = rotate_by_camera(
+        [
+            screen_position[0] / camera.zoom,
+            screen_position[1] / camera.zoom,
+        ],
+        -camera.rotation,
+    );
+
+    [x + camera.position[0], y + camera.position[1]]
+}
+
+/// turn a position relative to the camera into the direction it is on the screen
+fn rotate_by_camera(position: [f32; 2], camera_rotation: f32) -> [f32; 2] {
+    let (sin, cos) = camera_rotation.sin_cos();
+    [
+        position[0] * cos - position[1] * sin,
+        position[0] * sin + position[1] * cos,
+    ]
+}
+
 pub struct RenderSettings {
     shadows: bool,
     reflections: bool,
@@ -598,6 +1140,8 @@ pub struct RenderSettings {
     render_resolution: Option<[u32; 2]>,
     blur_reflections: bool,
     blur_strength: f32,
+    /// draw the pick ids of the drawables so they can be picked
+    picking: bool,
 }
 
 impl Default for RenderSettings {
@@ -608,6 +1152,7 @@ impl Default for RenderSettings {
             render_resolution: None,
             blur_reflections: false,
             blur_strength: 0.01,
+            picking: false,
         }
     }
 }
@@ -638,6 +1183,12 @@ impl RenderSettings {
         self.blur_strength = strength;
         self
     }
+
+    /// Draw the pick id of every drawable to a texture so LumenpyxProgram::pick can find what is under the mouse
+    pub fn with_picking(mut self, picking: bool) -> Self {
+        self.picking = picking;
+        self
+    }
 }
 
 /// Draw everything to the screen
@@ -646,16 +1197,51 @@ pub fn draw_all(
     drawables: Vec<&dyn Drawable>,
     program: &mut LumenpyxProgram,
     camera: &Camera,
+) {
+    if program.get_render_resolution() < program.dimensions {
+        panic!("Render resolution must be greater than or equal to the window resolution");
+    }
+
+    prepare_scene(&lights, &drawables, program);
+    let finished_texture_name = draw_scene(lights, drawables, program, camera);
+    program.last_subpixel_offset = camera.get_subpixel_offset();
+
+    let finished_texture = program
+        .get_pipeline_texture(finished_texture_name)
+        .expect("Failed to get finished texture");
+    draw_upscale(
+        glium::uniforms::Sampler(finished_texture, DEFAULT_BEHAVIOR),
+        program,
+        program.dimensions,
+        camera.get_subpixel_offset(),
+    );
+}
+
+/// load the shaders and the textures the scene needs, and update the lights for this frame
+pub(crate) fn prepare_scene(
+    lights: &[&dyn lights::LightDrawable],
+    drawables: &[&dyn Drawable],
+    program: &mut LumenpyxProgram,
 ) {
     // this is kind of inefficient, but it works for now
-    for drawable in &drawables {
+    for drawable in drawables {
         drawable.try_load_shaders(program);
     }
-    for light in &lights {
+    for light in lights {
         light.try_load_shaders(program);
+        light.update_light();
     }
     load_all_textures(program);
+}
 
+/// Draw the scene to the pipeline textures of the viewport being drawn,
+/// returns the name of the texture that should be shown
+pub(crate) fn draw_scene(
+    lights: Vec<&dyn lights::LightDrawable>,
+    drawables: Vec<&dyn Drawable>,
+    program: &LumenpyxProgram,
+    camera: &Camera,
+) -> &'static str {
     /*
     STEP 1:
         render every albedo to a texture
@@ -675,8 +1261,11 @@ pub fn draw_all(
         upscale the result to the screen size
     */
 
+    let mut drawables = drawables;
+    layers::sort_drawables(&mut drawables, program);
+
     let reflected_texture = program
-        .get_texture("reflected_texture")
+        .get_pipeline_texture("reflected_texture")
         .expect("Failed to get reflected texture");
 
     let (
@@ -685,7 +1274,13 @@ pub fn draw_all(
         height_texture,
         roughness_texture,
         shadow_strength_texture,
-    ) = draw_all_no_post(drawables, program, camera);
+    ) = draw_all_no_post(drawables.clone(), program, camera);
+
+    // lights that can't reach anything the camera sees aren't drawn
+    let lights = lights
+        .into_iter()
+        .filter(|light| is_light_visible(*light, program.get_render_resolution(), camera))
+        .collect();
 
     let lit_texture = draw_lighting(
         lights,
@@ -697,15 +1292,13 @@ pub fn draw_all(
         &shadow_strength_texture,
     );
 
+    // the emissive parts ignore the lights, so they go on top of the lit image
+    if program.render_settings.shadows {
+        draw_emissive(drawables, program, camera, lit_texture);
+    }
+
     let display = &program.display;
-    let debug = &program.debug;
     let render_settings = &program.render_settings;
-    let render_resolution = render_settings
-        .render_resolution
-        .unwrap_or(program.dimensions);
-    if render_resolution < program.dimensions {
-        panic!("Render resolution must be greater than or equal to the window resolution");
-    }
     if render_settings.reflections {
         let roughness = glium::uniforms::Sampler(roughness_texture, DEFAULT_BEHAVIOR);
         let height = glium::uniforms::Sampler(height_texture, DEFAULT_BEHAVIOR);
@@ -731,184 +1324,92 @@ pub fn draw_all(
         );
     }
 
-    {
-        let reflected_texture = if render_settings.reflections {
-            reflected_texture
-        } else if render_settings.shadows {
-            &lit_texture
-        } else {
-            &albedo_texture
-        };
-
-        let finished_texture = match debug {
-            DebugOption::None => glium::uniforms::Sampler(reflected_texture, DEFAULT_BEHAVIOR),
-            DebugOption::Albedo => glium::uniforms::Sampler(albedo_texture, DEFAULT_BEHAVIOR),
-            DebugOption::Height => glium::uniforms::Sampler(height_texture, DEFAULT_BEHAVIOR),
-            DebugOption::Roughness => glium::uniforms::Sampler(roughness_texture, DEFAULT_BEHAVIOR),
-            DebugOption::Normal => glium::uniforms::Sampler(normal_texture, DEFAULT_BEHAVIOR),
-            DebugOption::ShadowStrength => {
-                glium::uniforms::Sampler(shadow_strength_texture, DEFAULT_BEHAVIOR)
+    match program.debug {
+        DebugOption::None => {
+            if render_settings.reflections {
+                "reflected_texture"
+            } else if render_settings.shadows {
+                "lit_texture"
+            } else {
+                "albedo_texture"
             }
-        };
-
-        draw_upscale(finished_texture, &program, program.dimensions);
+        }
+        DebugOption::Albedo => "albedo_texture",
+        DebugOption::Height => "height_texture",
+        DebugOption::Roughness => "roughness_texture",
+        DebugOption::Normal => "normal_texture",
+        DebugOption::ShadowStrength => "shadow_strength_texture",
     }
 }
 
-fn load_all_textures(program: &mut LumenpyxProgram) {
-    let display = &program.display;
-    let render_settings = &program.render_settings;
-    let render_resolution = render_settings
-        .render_resolution
-        .unwrap_or(program.dimensions);
-    if render_resolution < program.dimensions {
-        panic!("Render resolution must be greater than or equal to the window resolution");
-    }
-
-    let albedo_texture = program.cache.get_texture("albedo_texture");
-    if albedo_texture.is_none() {
-        let albedo_texture_owned = glium::texture::Texture2d::empty_with_format(
-            display,
-            glium::texture::UncompressedFloatFormat::U8U8U8U8,
-            glium::texture::MipmapsOption::NoMipmap,
-            render_resolution[0],
-            render_resolution[1],
-        )
-        .expect("Failed to create albedo texture");
-
-        program
-            .cache
-            .insert("albedo_texture".to_string(), albedo_texture_owned);
-    }
-
-    let height_texture = program.cache.get_texture("height_texture");
-    if height_texture.is_none() {
-        let height_texture_owned = glium::texture::Texture2d::empty_with_format(
-            display,
-            glium::texture::UncompressedFloatFormat::U8U8U8U8,
-            glium::texture::MipmapsOption::NoMipmap,
-            render_resolution[0],
-            render_resolution[1],
-        )
-        .expect("Failed to create height texture");
-
-        program
-            .cache
-            .insert("height_texture".to_string(), height_texture_owned);
-    }
-
-    let normal_texture = program.cache.get_texture("normal_texture");
-    if normal_texture.is_none() {
-        let normal_texture_owned = glium::texture::Texture2d::empty_with_format(
-            display,
-            glium::texture::UncompressedFloatFormat::U8U8U8U8,
-            glium::texture::MipmapsOption::NoMipmap,
-            render_resolution[0],
-            render_resolution[1],
-        )
-        .expect("Failed to create normal texture");
-
-        program
-            .cache
-            .insert("normal_texture".to_string(), normal_texture_owned);
-    }
-
-    let roughness_texture = program.cache.get_texture("roughness_texture");
-    if roughness_texture.is_none() {
-        let roughness_texture_owned = glium::texture::Texture2d::empty_with_format(
-            display,
-            glium::texture::UncompressedFloatFormat::U8U8U8U8,
-            glium::texture::MipmapsOption::NoMipmap,
-            render_resolution[0],
-            render_resolution[1],
-        )
-        .expect("Failed to create roughness texture");
-
-        program
-            .cache
-            .insert("roughness_texture".to_string(), roughness_texture_owned);
-    }
-
-    let shadow_strength_texture = program.cache.get_texture("shadow_strength_texture");
-    if shadow_strength_texture.is_none() {
-        let shadow_strength_texture_owned = glium::texture::Texture2d::empty_with_format(
-            display,
-            glium::texture::UncompressedFloatFormat::U8U8U8U8,
-            glium::texture::MipmapsOption::NoMipmap,
-            render_resolution[0],
-            render_resolution[1],
-        )
-        .expect("Failed to create shadow strength texture");
-
-        program.cache.insert(
-            "shadow_strength_texture".to_string(),
-            shadow_strength_texture_owned,
-        );
-    }
-
-    let last_drawable_texture = program.cache.get_texture("last_drawable_texture");
-    if last_drawable_texture.is_none() {
-        let last_drawable_texture_owned = glium::texture::Texture2d::empty_with_format(
-            display,
-            glium::texture::UncompressedFloatFormat::U8U8U8U8,
-            glium::texture::MipmapsOption::NoMipmap,
-            render_resolution[0],
-            render_resolution[1],
-        )
-        .expect("Failed to create last drawable texture");
-
-        program.cache.insert(
-            "last_drawable_texture".to_string(),
-            last_drawable_texture_owned,
-        );
-    }
-
-    let reflected_texture = program.get_texture("reflected_texture");
-    if reflected_texture.is_none() {
-        let reflected_texture_owned = glium::texture::Texture2d::empty_with_format(
-            display,
-            glium::texture::UncompressedFloatFormat::U8U8U8U8,
-            glium::texture::MipmapsOption::NoMipmap,
-            render_resolution[0],
-            render_resolution[1],
-        )
-        .expect("Failed to create reflected frame buffer");
-
-        program
-            .cache
-            .insert("reflected_texture".to_string(), reflected_texture_owned);
-    }
+/// whether the light can reach any part of what the camera sees at the render resolution,
+/// each viewport checks against its own camera and resolution
+fn is_light_visible(
+    light: &dyn lights::LightDrawable,
+    render_resolution: [u32; 2],
+    camera: &Camera,
+) -> bool {
+    let Some(radius) = light.get_light_radius() else {
+        return true;
+    };
+    // the radius is in render pixels, so it covers less of the world when zoomed in
+    let radius = radius / camera.zoom;
+
+    let mut half_size = [
+        render_resolution[0] as f32 / 2.0 / camera.zoom,
+        render_resolution[1] as f32 / 2.0 / camera.zoom,
+    ];
+    // a turned camera can see into the corners of the box around it
+    if camera.rotation != 0.0 {
+        let half_diagonal = (half_size[0] * half_size[0] + half_size[1] * half_size[1]).sqrt();
+        half_size = [half_diagonal, half_diagonal];
+    }
+
+    let transform = light.get_transform();
+    let distance = [
+        (transform.get_x() - camera.position[0]).abs(),
+        (transform.get_y() - camera.position[1]).abs(),
+    ];
+    distance[0] <= half_size[0] + radius && distance[1] <= half_size[1] + radius
+}
 
-    let reflection_texture = program.get_texture("reflection_texture");
-    if reflection_texture.is_none() {
-        let reflection_texture_owned = glium::texture::Texture2d::empty_with_format(
-            display,
-            glium::texture::UncompressedFloatFormat::U8U8U8U8,
-            glium::texture::MipmapsOption::NoMipmap,
-            render_resolution[0],
-            render_resolution[1],
-        )
-        .expect("Failed to create reflection frame buffer");
+/// the textures every viewport draws the scene to
+const PIPELINE_TEXTURES: [&str; 11] = [
+    "albedo_texture",
+    "height_texture",
+    "normal_texture",
+    "roughness_texture",
+    "shadow_strength_texture",
+    "last_drawable_texture",
+    "reflected_texture",
+    "reflection_texture",
+    "lit_texture",
+    "id_texture",
+    "layer_texture",
+];
 
-        program
-            .cache
-            .insert("reflection_texture".to_string(), reflection_texture_owned);
-    }
+fn load_all_textures(program: &mut LumenpyxProgram) {
+    let render_resolution = program.get_render_resolution();
+
+    for name in PIPELINE_TEXTURES {
+        let name = program.get_pipeline_texture_name(name);
+        let up_to_date = program.cache.get_texture(&name).is_some_and(|texture| {
+            texture.dimensions() == (render_resolution[0], render_resolution[1])
+        });
+        if up_to_date {
+            continue;
+        }
 
-    let lit_texture = program.get_texture("lit_texture");
-    if lit_texture.is_none() {
-        let lit_texture_owned = glium::texture::Texture2d::empty_with_format(
-            display,
+        let texture = glium::texture::Texture2d::empty_with_format(
+            &program.display,
             glium::texture::UncompressedFloatFormat::U8U8U8U8,
             glium::texture::MipmapsOption::NoMipmap,
             render_resolution[0],
             render_resolution[1],
         )
-        .expect("Failed to create lit frame buffer");
+        .unwrap_or_else(|_| panic!("Failed to create {}", name));
 
-        program
-            .cache
-            .insert("lit_texture".to_string(), lit_texture_owned);
+        program.cache.insert(name, texture);
     }
 }
 
@@ -925,41 +1426,31 @@ fn draw_all_no_post<'a>(
 ) {
     let display = &program.display;
     let render_settings = &program.render_settings;
-    let render_resolution = render_settings
-        .render_resolution
-        .unwrap_or(program.dimensions);
-    if render_resolution < program.dimensions {
-        panic!("Render resolution must be greater than or equal to the window resolution");
-    }
+    let render_resolution = program.get_render_resolution();
 
     let albedo_texture = program
-        .get_texture("albedo_texture")
+        .get_pipeline_texture("albedo_texture")
         .expect("Failed to get albedo texture");
 
     let height_texture = program
-        .cache
-        .get_texture("height_texture")
+        .get_pipeline_texture("height_texture")
         .expect("Failed to get height texture");
 
     let normal_texture = program
-        .cache
-        .get_texture("normal_texture")
+        .get_pipeline_texture("normal_texture")
         .expect("Failed to get normal texture");
 
     let roughness_texture = program
-        .cache
-        .get_texture("roughness_texture")
+        .get_pipeline_texture("roughness_texture")
         .expect("Failed to get roughness texture");
 
     let shadow_strength_texture = program
-        .cache
-        .get_texture("shadow_strength_texture")
+        .get_pipeline_texture("shadow_strength_texture")
         .expect("Failed to get shadow strength texture");
 
     {
         let last_drawable_texture = program
-            .cache
-            .get_texture("last_drawable_texture")
+            .get_pipeline_texture("last_drawable_texture")
             .expect("Failed to get last drawable texture");
 
         let mut last_drawable_framebuffer =
@@ -995,7 +1486,8 @@ fn draw_all_no_post<'a>(
             glium::framebuffer::SimpleFrameBuffer::new(display, normal_texture)
                 .expect("Failed to create normal framebuffer");
 
-        normal_framebuffer.clear_color(0.0, 0.0, 1.0, 0.0);
+        // a normal pointing straight out of the screen, stored as normal * 0.5 + 0.5
+        normal_framebuffer.clear_color(0.5, 0.5, 1.0, 0.0);
 
         let mut shadow_strength_framebuffer =
             glium::framebuffer::SimpleFrameBuffer::new(display, shadow_strength_texture)
@@ -1003,21 +1495,73 @@ fn draw_all_no_post<'a>(
 
         shadow_strength_framebuffer.clear_color(0.0, 0.0, 0.0, 0.0);
 
+        let id_texture = program
+            .get_pipeline_texture("id_texture")
+            .expect("Failed to get id texture");
+
+        let mut id_framebuffer = glium::framebuffer::SimpleFrameBuffer::new(display, id_texture)
+            .expect("Failed to create id framebuffer");
+
+        id_framebuffer.clear_color(0.0, 0.0, 0.0, 0.0);
+
+        let layer_texture = program
+            .get_pipeline_texture("layer_texture")
+            .expect("Failed to get layer texture");
+
+        let mut layer_framebuffer =
+            glium::framebuffer::SimpleFrameBuffer::new(display, layer_texture)
+                .expect("Failed to create layer framebuffer");
+
+        layer_framebuffer.clear_color(0.0, 0.0, 0.0, 0.0);
+
+        // everything is on light layer 0 unless a layer says otherwise, so only draw the light layers if one does
+        let uses_light_layers = program
+            .layers
+            .values()
+            .any(|layer| layer.get_light_layer() != 0);
+
         for drawable in &drawables {
             let new_transform =
                 program.adjust_transform_for_drawable(&drawable.get_transform(), camera);
 
             drawable.draw_albedo(program, &new_transform, &mut albedo_framebuffer);
-            if render_settings.shadows {
-                let shadow_strength = drawable.get_recieve_shadows_strength();
-
-                shaders::draw_recieve_shadows(
-                    &mut shadow_strength_framebuffer,
-                    &program,
-                    shadow_strength,
-                    last_drawable_sampler,
-                    this_drawable_sampler,
-                );
+            if render_settings.shadows || render_settings.picking || uses_light_layers {
+                if render_settings.shadows {
+                    let shadow_strength = drawable.get_recieve_shadows_strength();
+
+                    shaders::draw_recieve_shadows(
+                        &mut shadow_strength_framebuffer,
+                        program,
+                        shadow_strength,
+                        last_drawable_sampler,
+                        this_drawable_sampler,
+                    );
+                }
+
+                // objects without an id still cover the ones behind them
+                if render_settings.picking {
+                    let pick_id = picking::encode_pick_id(drawable.get_pick_id());
+
+                    shaders::draw_changed_id(
+                        &mut id_framebuffer,
+                        program,
+                        pick_id,
+                        last_drawable_sampler,
+                        this_drawable_sampler,
+                    );
+                }
+
+                if uses_light_layers {
+                    let light_layer = program.get_layer(drawable.get_layer()).get_light_layer();
+
+                    shaders::draw_changed_id(
+                        &mut layer_framebuffer,
+                        program,
+                        light_layer,
+                        last_drawable_sampler,
+                        this_drawable_sampler,
+                    );
+                }
 
                 // copy the albedo to the last drawable framebuffer
                 albedo_framebuffer.blit_whole_color_to(
@@ -1040,10 +1584,30 @@ fn draw_all_no_post<'a>(
 
                 drawable.draw_height(program, &new_transform, &mut height_framebuffer);
             }
+
+            // the overlays need the height of the whole scene, so they are drawn after the height
+            let height_sampler = glium::uniforms::Sampler(height_texture, DEFAULT_BEHAVIOR);
+            for drawable in &drawables {
+                let new_transform =
+                    program.adjust_transform_for_drawable(&drawable.get_transform(), camera);
+
+                drawable.draw_albedo_overlay(
+                    program,
+                    &new_transform,
+                    &mut albedo_framebuffer,
+                    height_sampler,
+                );
+            }
         }
 
+        // layers without reflections leave the roughness at 0.0 so nothing is reflected on them
+        let reflective_drawables: Vec<&&dyn Drawable> = drawables
+            .iter()
+            .filter(|drawable| program.get_layer(drawable.get_layer()).get_reflections())
+            .collect();
+
         if program.render_settings.reflections {
-            for drawable in &drawables {
+            for drawable in &reflective_drawables {
                 let new_transform =
                     program.adjust_transform_for_drawable(&drawable.get_transform(), camera);
 
@@ -1052,7 +1616,7 @@ fn draw_all_no_post<'a>(
         }
 
         if program.render_settings.reflections {
-            for drawable in &drawables {
+            for drawable in &reflective_drawables {
                 let new_transform =
                     program.adjust_transform_for_drawable(&drawable.get_transform(), camera);
 
@@ -1070,6 +1634,28 @@ fn draw_all_no_post<'a>(
     )
 }
 
+fn draw_emissive(
+    drawables: Vec<&dyn Drawable>,
+    program: &LumenpyxProgram,
+    camera: &Camera,
+    lit_texture: &glium::Texture2d,
+) {
+    let mut lit_framebuffer =
+        glium::framebuffer::SimpleFrameBuffer::new(&program.display, lit_texture)
+            .expect("Failed to create lit frame buffer");
+
+    for drawable in &drawables {
+        let new_transform =
+            program.adjust_transform_for_drawable(&drawable.get_transform(), camera);
+
+        // unlit layers are drawn over the lighting at their full color
+        if !program.get_layer(drawable.get_layer()).get_lit() {
+            drawable.draw_albedo(program, &new_transform, &mut lit_framebuffer);
+        }
+        drawable.draw_emissive(program, &new_transform, &mut lit_framebuffer);
+    }
+}
+
 fn draw_lighting<'a>(
     lights: Vec<&dyn lights::LightDrawable>,
     program: &'a LumenpyxProgram,
@@ -1081,15 +1667,9 @@ fn draw_lighting<'a>(
 ) -> &'a glium::Texture2d {
     let display = &program.display;
     let render_settings = &program.render_settings;
-    let render_resolution = render_settings
-        .render_resolution
-        .unwrap_or(program.dimensions);
-    if render_resolution < program.dimensions {
-        panic!("Render resolution must be greater than or equal to the window resolution");
-    }
 
     let lit_texture = program
-        .get_texture("lit_texture")
+        .get_pipeline_texture("lit_texture")
         .expect("Failed to get lit texture");
 
     if render_settings.shadows {
@@ -1109,7 +1689,7 @@ fn draw_lighting<'a>(
 
             light.draw(
                 program,
-                new_transform.get_matrix(),
+                lights::get_light_matrix(&new_transform),
                 &mut lit_framebuffer,
                 height_sampler,
                 albedo,
@@ -1121,3 +1701,213 @@ fn draw_lighting<'a>(
 
     lit_texture
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} is not close to {}", a, b);
+    }
+
+    fn assert_transforms_close(a: &Transform, b: &Transform) {
+        for (row_a, row_b) in a.get_matrix().iter().zip(b.get_matrix().iter()) {
+            for (value_a, value_b) in row_a.iter().zip(row_b.iter()) {
+                assert_close(*value_a, *value_b);
+            }
+        }
+    }
+
+    fn assert_points_close(a: [f32; 2], b: [f32; 2]) {
+        assert_close(a[0], b[0]);
+        assert_close(a[1], b[1]);
+    }
+
+    fn test_transform() -> Transform {
+        let mut transform = Transform::new([10.0, -4.0, 2.0]);
+        transform.set_rotation(0.6);
+        transform.set_scale(2.0, 0.5, 1.5);
+        transform.set_skew(0.3);
+        transform
+    }
+
+    #[test]
+    fn wrapper_offset_round_trips() {
+        let object_transform = test_transform();
+        let mut transform = Transform::new([-3.0, 7.0, 1.0]);
+        transform.set_rotation(-1.2);
+        transform.set_scale(1.0, 3.0, 1.0);
+
+        let offset = get_wrapper_offset(&object_transform, &transform);
+
+        assert_transforms_close(&object_transform.add_parent(&offset), &transform);
+    }
+
+    #[test]
+    fn lights_are_culled_against_each_camera() {
+        // reaches sqrt(2.0 * 256.0 - 1.0) / 0.5 = about 45 pixels
+        let light = lights::PointLight::new([100.0, 0.0, 1.0], [1.0, 1.0, 1.0], 2.0, 0.5);
+        let left_camera = Camera::new([0.0, 0.0, 1.0]);
+        let right_camera = Camera::new([200.0, 0.0, 1.0]);
+
+        // a wide viewport sees it, a narrow one on the same camera doesn't
+        assert!(is_light_visible(&light, [160, 90], &left_camera));
+        assert!(!is_light_visible(&light, [60, 90], &left_camera));
+        assert!(is_light_visible(&light, [160, 90], &right_camera));
+        assert!(!is_light_visible(&light, [60, 90], &right_camera));
+
+        // zooming in sees less of the world
+        let mut zoomed_camera = Camera::new([0.0, 0.0, 1.0]);
+        zoomed_camera.set_zoom(4.0);
+        assert!(!is_light_visible(&light, [160, 90], &zoomed_camera));
+    }
+
+    #[test]
+    fn turned_cameras_see_into_the_corners() {
+        let light = lights::PointLight::new([52.0, 52.0, 1.0], [1.0, 1.0, 1.0], 1.0, 4.0);
+        let mut camera = Camera::new([0.0, 0.0, 1.0]);
+        assert!(!is_light_visible(&light, [90, 90], &camera));
+        camera.set_rotation(std::f32::consts::FRAC_PI_4);
+        assert!(is_light_visible(&light, [90, 90], &camera));
+    }
+
+    #[test]
+    fn lights_without_a_radius_are_always_drawn() {
+        // no falloff reaches anywhere
+        let light = lights::PointLight::new([0.0, 0.0, 1.0], [1.0, 1.0, 1.0], 1.0, 0.0);
+        assert!(is_light_visible(
+            &light,
+            [1, 1],
+            &Camera::new([1000.0, 1000.0, 1.0])
+        ));
+    }
+
+    #[test]
+    fn screen_offset_follows_the_camera() {
+        let mut camera = Camera::new([10.0, 20.0, 1.0]);
+        camera.set_zoom(2.0);
+        let [x, y] = screen_offset_to_world(&camera, [8.0, -4.0]);
+        assert_close(x, 14.0);
+        assert_close(y, 18.0);
+
+        // a position turned with the camera goes back to where it was
+        camera.set_rotation(0.7);
+        let offset = rotate_by_camera([3.0, 5.0], 0.7);
+        let [x, y] = screen_offset_to_world(&camera, [offset[0] * 2.0, offset[1] * 2.0]);
+        assert_close(x, 13.0);
+        assert_close(y, 25.0);
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let transform = test_transform();
+        let inverse = transform.inverse().unwrap();
+
+        let identity = Transform::default();
+        assert_transforms_close(&transform.add_parent(&inverse), &identity);
+        assert_transforms_close(&inverse.add_parent(&transform), &identity);
+
+        let point = [3.0, -8.0];
+        assert_points_close(
+            inverse.transform_point(transform.transform_point(point)),
+            point,
+        );
+    }
+
+    #[test]
+    fn zero_scale_has_no_inverse() {
+        let mut transform = test_transform();
+        transform.set_scale(0.0, 1.0, 1.0);
+        assert!(transform.inverse().is_none());
+
+        transform.set_scale(1.0, 1.0, 0.0);
+        assert!(transform.inverse().is_none());
+    }
+
+    #[test]
+    fn parent_rotation_turns_the_child_offset() {
+        let mut parent = Transform::new([10.0, 0.0, 0.0]);
+        parent.set_rotation(std::f32::consts::FRAC_PI_2);
+        let mut child = Transform::new([5.0, 0.0, 0.0]);
+        child.set_rotation(0.25);
+
+        // a clockwise quarter turn moves the offset from the right to below
+        let world = child.add_parent(&parent);
+        assert_close(world.get_x(), 10.0);
+        assert_close(world.get_y(), -5.0);
+        assert_close(world.get_rotation(), std::f32::consts::FRAC_PI_2 + 0.25);
+    }
+
+    #[test]
+    fn points_and_vectors_are_transformed() {
+        let mut transform = Transform::new([1.0, 2.0, 0.0]);
+        transform.set_scale(2.0, 3.0, 1.0);
+        assert_points_close(transform.transform_point([3.0, 4.0]), [7.0, 14.0]);
+        assert_points_close(transform.transform_vector([3.0, 4.0]), [6.0, 12.0]);
+
+        transform.set_skew(std::f32::consts::FRAC_PI_4);
+        assert_points_close(transform.transform_vector([0.0, 1.0]), [3.0, 3.0]);
+    }
+
+    #[test]
+    fn rotate_around_moves_and_turns() {
+        let mut transform = Transform::new([2.0, 0.0, 0.0]);
+        transform.set_rotation(0.5);
+        transform.rotate_around([1.0, 0.0], std::f32::consts::FRAC_PI_2);
+
+        assert_close(transform.get_x(), 1.0);
+        assert_close(transform.get_y(), -1.0);
+        assert_close(transform.get_rotation(), 0.5 + std::f32::consts::FRAC_PI_2);
+    }
+
+    #[test]
+    fn interpolate_goes_between_the_transforms() {
+        let from = Transform::new([0.0, 10.0, 0.0]);
+        let mut to = Transform::new([4.0, 20.0, 2.0]);
+        to.set_rotation(1.0);
+        to.set_scale(3.0, 1.0, 1.0);
+        to.set_skew(0.2);
+
+        assert_transforms_close(&from.interpolate(&to, 0.0), &from);
+        assert_transforms_close(&from.interpolate(&to, 1.0), &to);
+
+        let half = from.interpolate(&to, 0.5);
+        assert_close(half.get_x(), 2.0);
+        assert_close(half.get_y(), 15.0);
+        assert_close(half.get_z(), 1.0);
+        assert_close(half.get_rotation(), 0.5);
+        assert_close(half.get_scale()[0], 2.0);
+        assert_close(half.get_skew(), 0.1);
+    }
+
+    #[test]
+    fn decompose_keeps_skew_and_negative_scale() {
+        let mut transform = test_transform();
+        transform.set_scale(2.0, -0.5, 1.0);
+        let (rotation, skew, scale) = decompose_linear(transform.get_linear());
+        assert_close(rotation, 0.6);
+        assert_close(skew, 0.3);
+        assert_close(scale[0], 2.0);
+        assert_close(scale[1], -0.5);
+
+        // a flip in x comes back as a half turn and a flip in y, which is the same matrix
+        transform.set_scale(-2.0, 0.5, 1.0);
+        let (rotation, skew, scale) = decompose_linear(transform.get_linear());
+        assert_close(scale[0], 2.0);
+        assert_close(scale[1], -0.5);
+        let mut decomposed = Transform::new([10.0, -4.0, 2.0]);
+        decomposed.set_rotation(rotation);
+        decomposed.set_skew(skew);
+        decomposed.set_scale(scale[0], scale[1], 1.0);
+        assert_transforms_close(&decomposed, &transform);
+    }
+
+    #[test]
+    fn from_matrix_round_trips() {
+        let transform = test_transform();
+        let from_matrix = Transform::from_matrix(transform.get_matrix());
+        assert_transforms_close(&from_matrix, &transform);
+        assert_close(from_matrix.get_rotation(), 0.6);
+        assert_close(from_matrix.get_skew(), 0.3);
+    }
+}
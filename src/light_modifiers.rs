@@ -0,0 +1,351 @@
+use std::cell::RefCell;
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+use glium::framebuffer::SimpleFrameBuffer;
+
+use crate::animation::AnimationTimeElapsed;
+use crate::clock::Clock;
+use crate::lights::LightDrawable;
+use crate::tween::Tweenable;
+use crate::LumenpyxProgram;
+use crate::Transform;
+
+/// Something that changes a light over time
+#[derive(Clone, Debug)]
+pub enum LightModifier {
+    /// Randomly dims the light like a flame.
+    /// strength is how much it can dim from 0.0 to 1.0 and speed is how many times a second it changes
+    Flicker { strength: f32, speed: f32 },
+    /// Smoothly brightens and dims the light.
+    /// The intensity is multiplied by 1.0 + amplitude * sin, frequency is in times a second
+    Pulse { amplitude: f32, frequency: f32 },
+    /// Turns the light fully on and off.
+    /// duty_cycle is the part of each flash from 0.0 to 1.0 the light is on,
+    /// off_intensity is what the intensity is multiplied by when it's off
+    Strobe {
+        frequency: f32,
+        duty_cycle: f32,
+        off_intensity: f32,
+    },
+    /// Smoothly goes through the colors, taking period seconds to go through all of them
+    ColorCycle { colors: Vec<[f32; 3]>, period: f32 },
+}
+
+impl LightModifier {
+    pub fn flicker(strength: f32, speed: f32) -> Self {
+        LightModifier::Flicker { strength, speed }
+    }
+
+    pub fn pulse(amplitude: f32, frequency: f32) -> Self {
+        LightModifier::Pulse {
+            amplitude,
+            frequency,
+        }
+    }
+
+    pub fn strobe(frequency: f32, duty_cycle: f32, off_intensity: f32) -> Self {
+        LightModifier::Strobe {
+            frequency,
+            duty_cycle,
+            off_intensity,
+        }
+    }
+
+    pub fn color_cycle(colors: Vec<[f32; 3]>, period: f32) -> Self {
+        LightModifier::ColorCycle { colors, period }
+    }
+
+    /// change the intensity and color for the time in seconds
+    fn apply(&self, time: f32, seed: u64, intensity: &mut f32, color: &mut [f32; 3]) {
+        // every light gets its own offset so lights with the same modifier don't line up
+        let phase = hash_to_unit(seed, u64::MAX);
+
+        match self {
+            LightModifier::Flicker { strength, speed } => {
+                let noise = value_noise(seed, time * speed);
+                *intensity *= 1.0 - strength * noise;
+            }
+            LightModifier::Pulse {
+                amplitude,
+                frequency,
+            } => {
+                let angle = (time * frequency + phase) * 2.0 * PI;
+                *intensity *= 1.0 + amplitude * angle.sin();
+            }
+            LightModifier::Strobe {
+                frequency,
+                duty_cycle,
+                off_intensity,
+            } => {
+                if (time * frequency + phase).fract() >= *duty_cycle {
+                    *intensity *= off_intensity;
+                }
+            }
+            LightModifier::ColorCycle { colors, period } => {
+                if colors.is_empty() || *period <= 0.0 {
+                    return;
+                }
+
+                let position = (time / period + phase).fract() * colors.len() as f32;
+                let index = position as usize % colors.len();
+                let next = (index + 1) % colors.len();
+                *color = <[f32; 3]>::lerp(colors[index], colors[next], position.fract());
+            }
+        }
+    }
+}
+
+/// A light with modifiers that change it over time, it can be drawn like any other light.
+/// The seed makes the flicker and the timing of the other modifiers different for every light,
+/// give every light its own seed so they don't flicker in sync
+pub struct ModifiedLight<L: LightDrawable + Clone> {
+    light: L,
+    /// the light with the modifiers applied, worked out once per frame in update_light
+    modified_light: RefCell<L>,
+    modifiers: Vec<LightModifier>,
+    seed: u64,
+    time: AnimationTimeElapsed,
+}
+
+impl<L: LightDrawable + Clone> ModifiedLight<L> {
+    /// Starts now on the clock, usually `program.get_clock()`
+    pub fn new(light: L, seed: u64, clock: Rc<dyn Clock>) -> Self {
+        Self {
+            modified_light: RefCell::new(light.clone()),
+            light,
+            modifiers: vec![],
+            seed,
+            time: AnimationTimeElapsed::from_clock(clock),
+        }
+    }
+
+    pub fn with_modifier(mut self, modifier: LightModifier) -> Self {
+        self.modifiers.push(modifier);
+        self
+    }
+
+    pub fn add_modifier(&mut self, modifier: LightModifier) {
+        self.modifiers.push(modifier);
+    }
+
+    pub fn clear_modifiers(&mut self) {
+        self.modifiers.clear();
+    }
+
+    pub fn get_modifiers(&self) -> &[LightModifier] {
+        &self.modifiers
+    }
+
+    /// Get the light without the modifiers
+    pub fn get_light(&self) -> &L {
+        &self.light
+    }
+
+    /// Get the light without the modifiers, changes to it are kept and modified on top of
+    pub fn get_light_mut(&mut self) -> &mut L {
+        &mut self.light
+    }
+
+    /// Get the light with the modifiers applied for the current time
+    pub fn get_modified_light(&self) -> L {
+        let time = self.time.as_secs_f32();
+        let mut intensity = self.light.get_light_intensity();
+        let mut color = self.light.get_light_color();
+        for modifier in &self.modifiers {
+            modifier.apply(time, self.seed, &mut intensity, &mut color);
+        }
+
+        let mut light = self.light.clone();
+        light.set_light_intensity(intensity);
+        light.set_light_color(color);
+        light
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Run the modifiers from the clock starting now
+    pub fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.time = AnimationTimeElapsed::from_clock(clock);
+    }
+
+    pub fn set_time(&mut self, time: AnimationTimeElapsed) {
+        self.time = time;
+    }
+}
+
+impl<L: LightDrawable + Clone> LightDrawable for ModifiedLight<L> {
+    fn draw(
+        &self,
+        program: &LumenpyxProgram,
+        matrix_transform: [[f32; 4]; 4],
+        albedo_framebuffer: &mut SimpleFrameBuffer,
+        height_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+        albedo_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+        roughness_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+        shadow_strength_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    ) {
+        self.modified_light.borrow().draw(
+            program,
+            matrix_transform,
+            albedo_framebuffer,
+            height_uniform,
+            albedo_uniform,
+            roughness_uniform,
+            shadow_strength_uniform,
+        );
+    }
+
+    fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
+        self.light.try_load_shaders(program);
+    }
+
+    fn get_transform(&self) -> Transform {
+        self.light.get_transform()
+    }
+
+    fn set_transform(&mut self, transform: Transform) {
+        self.light.set_transform(transform);
+    }
+
+    fn get_light_intensity(&self) -> f32 {
+        self.light.get_light_intensity()
+    }
+
+    fn set_light_intensity(&mut self, intensity: f32) {
+        self.light.set_light_intensity(intensity);
+    }
+
+    fn get_light_color(&self) -> [f32; 3] {
+        self.light.get_light_color()
+    }
+
+    fn set_light_color(&mut self, color: [f32; 3]) {
+        self.light.set_light_color(color);
+    }
+
+    fn get_light_radius(&self) -> Option<f32> {
+        self.modified_light.borrow().get_light_radius()
+    }
+
+    fn update_light(&self) {
+        *self.modified_light.borrow_mut() = self.get_modified_light();
+    }
+
+    fn get_layer_mask(&self) -> u32 {
+        self.light.get_layer_mask()
+    }
+
+    fn set_layer_mask(&mut self, layer_mask: u32) {
+        self.light.set_layer_mask(layer_mask);
+    }
+}
+
+/// a random number from 0.0 to 1.0 that is always the same for the seed and index
+fn hash_to_unit(seed: u64, index: u64) -> f32 {
+    // splitmix64
+    let mut x = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15).wrapping_add(index);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^= x >> 31;
+    (x >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// smooth noise from 0.0 to 1.0, a new random value every 1.0 of x
+pub(crate) fn value_noise(seed: u64, x: f32) -> f32 {
+    let x = x.max(0.0);
+    let index = x.floor() as u64;
+    let t = x.fract();
+    let smooth_t = t * t * (3.0 - 2.0 * t);
+    <f32 as Tweenable>::lerp(
+        hash_to_unit(seed, index),
+        hash_to_unit(seed, index + 1),
+        smooth_t,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedStepClock;
+    use crate::lights::PointLight;
+    use std::time::Duration;
+
+    fn intensities(light: &ModifiedLight<PointLight>, clock: &FixedStepClock) -> Vec<f32> {
+        (0..20)
+            .map(|ticks| {
+                clock.set_ticks(ticks);
+                light.get_modified_light().get_light_intensity()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn modifiers_replay_exactly_on_the_same_clock() {
+        let clock = Rc::new(FixedStepClock::new(Duration::from_millis(50)));
+        let light = ModifiedLight::new(
+            PointLight::new([0.0, 0.0, 1.0], [1.0, 1.0, 1.0], 2.0, 0.02),
+            7,
+            clock.clone(),
+        )
+        .with_modifier(LightModifier::flicker(0.5, 8.0))
+        .with_modifier(LightModifier::pulse(0.2, 1.0));
+
+        let first = intensities(&light, &clock);
+        assert_eq!(first, intensities(&light, &clock));
+        assert!(first.iter().any(|intensity| *intensity != first[0]));
+    }
+
+    #[test]
+    fn strobe_is_on_for_the_duty_cycle() {
+        // ten steps per flash, so on for exactly three of them
+        let clock = Rc::new(FixedStepClock::new(Duration::from_millis(100)));
+        let light = ModifiedLight::new(
+            PointLight::new([0.0, 0.0, 1.0], [1.0, 1.0, 1.0], 2.0, 0.02),
+            3,
+            clock.clone(),
+        )
+        .with_modifier(LightModifier::strobe(1.0, 0.3, 0.0));
+
+        let intensities = intensities(&light, &clock);
+        for flash in intensities.chunks(10) {
+            assert_eq!(
+                flash.iter().filter(|intensity| **intensity == 2.0).count(),
+                3
+            );
+            assert_eq!(
+                flash.iter().filter(|intensity| **intensity == 0.0).count(),
+                7
+            );
+        }
+    }
+
+    #[test]
+    fn modified_light_only_changes_when_updated() {
+        let clock = Rc::new(FixedStepClock::new(Duration::from_millis(100)));
+        let light = ModifiedLight::new(
+            PointLight::new([0.0, 0.0, 1.0], [1.0, 1.0, 1.0], 2.0, 0.02),
+            3,
+            clock.clone(),
+        )
+        .with_modifier(LightModifier::strobe(1.0, 0.5, 0.0));
+
+        // find a step where the strobe is on, then one where it is off
+        let on = (0..10)
+            .find(|ticks| {
+                clock.set_ticks(*ticks);
+                light.get_modified_light().get_light_intensity() == 2.0
+            })
+            .unwrap();
+        clock.set_ticks(on);
+        light.update_light();
+        let on_radius = light.get_light_radius();
+
+        clock.set_ticks(on + 5);
+        assert_eq!(light.get_light_radius(), on_radius);
+        light.update_light();
+        assert_eq!(light.get_light_radius(), Some(0.0));
+    }
+}
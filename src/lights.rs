@@ -1,6 +1,7 @@
 use crate::shaders::FULL_SCREEN_QUAD;
 use crate::LumenpyxProgram;
 use crate::Transform;
+use crate::DEFAULT_BEHAVIOR;
 use glium;
 use glium::framebuffer::SimpleFrameBuffer;
 use glium::uniform;
@@ -34,11 +35,20 @@ pub const DEFAULT_LIGHT_BLENDING: Blend = glium::Blend {
     constant_value: (0.0, 0.0, 0.0, 0.0),
 };
 
+/// the matrix a light is drawn with, the height of the light is put in it since lights need it for the shading.
+/// This way lights moved by something else, like a node of a scene, are drawn at the height they were moved to
+pub(crate) fn get_light_matrix(transform: &Transform) -> [[f32; 4]; 4] {
+    let mut matrix = transform.get_matrix();
+    matrix[3][2] = transform.get_z();
+    matrix
+}
+
 /// A trait for drawable lights
 /// This trait is used to draw lights in the scene
 /// If you want to create a custom light, you can implement this trait
 /// Follow the example here: https://github.com/ABC-Engine/lumenpyx/wiki/Creating-custom-drawable-objects
 pub trait LightDrawable {
+    /// The matrix is the transform of the light on the screen, with the height of the light in matrix_transform[3][2]
     fn draw(
         &self,
         program: &LumenpyxProgram,
@@ -52,6 +62,36 @@ pub trait LightDrawable {
     fn try_load_shaders(&self, program: &mut LumenpyxProgram);
     fn get_transform(&self) -> Transform;
     fn set_transform(&mut self, transform: Transform);
+
+    /// Get the intensity of the light, this is what light modifiers change
+    fn get_light_intensity(&self) -> f32;
+
+    /// Set the intensity of the light, this is what light modifiers change
+    fn set_light_intensity(&mut self, intensity: f32);
+
+    /// Get the color of the light, this is what light modifiers change
+    fn get_light_color(&self) -> [f32; 3];
+
+    /// Set the color of the light, this is what light modifiers change
+    fn set_light_color(&mut self, color: [f32; 3]);
+
+    /// Called once every time the scene is drawn, before any light is drawn.
+    /// Lights that change over time can work out how they look for this frame here
+    fn update_light(&self) {}
+
+    /// How far in render pixels the light can reach, lights that can't reach anything a camera sees aren't drawn.
+    /// None means it can reach anywhere
+    fn get_light_radius(&self) -> Option<f32> {
+        None
+    }
+
+    /// Get the light layers the light lights, bit n is light layer n. Every layer by default
+    fn get_layer_mask(&self) -> u32 {
+        u32::MAX
+    }
+
+    /// Set the light layers the light lights, bit n is light layer n, e.g. `1 << 2` only lights layers on light layer 2
+    fn set_layer_mask(&mut self, _layer_mask: u32) {}
 }
 
 /// A point light source
@@ -62,6 +102,7 @@ pub struct PointLight {
     color: [f32; 3],
     intensity: f32,
     falloff: f32,
+    layer_mask: u32,
 }
 
 impl PointLight {
@@ -72,6 +113,7 @@ impl PointLight {
             color,
             intensity,
             falloff,
+            layer_mask: u32::MAX,
         }
     }
 
@@ -90,16 +132,31 @@ impl PointLight {
         self.color = [r, g, b];
     }
 
+    /// Get the color of the light
+    pub fn get_color(&self) -> [f32; 3] {
+        self.color
+    }
+
     /// Set the intensity of the light
     /// If the intensity is above 1.0, it can result in overexposure
     pub fn set_intensity(&mut self, intensity: f32) {
         self.intensity = intensity;
     }
 
+    /// Get the intensity of the light
+    pub fn get_intensity(&self) -> f32 {
+        self.intensity
+    }
+
     /// Set the falloff of the light
     pub fn set_falloff(&mut self, falloff: f32) {
         self.falloff = falloff;
     }
+
+    /// Get the falloff of the light
+    pub fn get_falloff(&self) -> f32 {
+        self.falloff
+    }
 }
 
 impl LightDrawable for PointLight {
@@ -145,9 +202,43 @@ impl LightDrawable for PointLight {
     fn set_transform(&mut self, transform: Transform) {
         self.position = [transform.get_x(), transform.get_y(), transform.get_z()];
     }
+
+    fn get_light_intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    fn set_light_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+    }
+
+    fn get_light_color(&self) -> [f32; 3] {
+        self.color
+    }
+
+    fn set_light_color(&mut self, color: [f32; 3]) {
+        self.color = color;
+    }
+
+    fn get_layer_mask(&self) -> u32 {
+        self.layer_mask
+    }
+
+    fn set_layer_mask(&mut self, layer_mask: u32) {
+        self.layer_mask = layer_mask;
+    }
+
+    fn get_light_radius(&self) -> Option<f32> {
+        if self.falloff <= 0.0 {
+            return None;
+        }
+
+        // past this the light adds less than one step of an 8 bit color
+        Some((self.intensity.max(0.0) * 256.0 - 1.0).max(0.0).sqrt() / self.falloff)
+    }
 }
 
 /// An area light source
+#[derive(Copy, Clone)]
 pub struct AreaLight {
     position: [f32; 3],
     color: [f32; 3],
@@ -155,6 +246,7 @@ pub struct AreaLight {
     falloff: f32,
     width: f32,
     height: f32,
+    layer_mask: u32,
 }
 
 impl AreaLight {
@@ -174,6 +266,7 @@ impl AreaLight {
             falloff,
             width,
             height,
+            layer_mask: u32::MAX,
         }
     }
 
@@ -192,16 +285,31 @@ impl AreaLight {
         self.color = [r, g, b];
     }
 
+    /// Get the color of the light
+    pub fn get_color(&self) -> [f32; 3] {
+        self.color
+    }
+
     /// Set the intensity of the light
     pub fn set_intensity(&mut self, intensity: f32) {
         self.intensity = intensity;
     }
 
+    /// Get the intensity of the light
+    pub fn get_intensity(&self) -> f32 {
+        self.intensity
+    }
+
     /// Set the falloff of the light
     pub fn set_falloff(&mut self, falloff: f32) {
         self.falloff = falloff;
     }
 
+    /// Get the falloff of the light
+    pub fn get_falloff(&self) -> f32 {
+        self.falloff
+    }
+
     /// Set the width of the light
     pub fn set_width(&mut self, width: f32) {
         self.width = width;
@@ -258,9 +366,44 @@ impl LightDrawable for AreaLight {
     fn set_transform(&mut self, transform: Transform) {
         self.position = [transform.get_x(), transform.get_y(), transform.get_z()];
     }
+
+    fn get_light_intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    fn set_light_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+    }
+
+    fn get_light_color(&self) -> [f32; 3] {
+        self.color
+    }
+
+    fn set_light_color(&mut self, color: [f32; 3]) {
+        self.color = color;
+    }
+
+    fn get_layer_mask(&self) -> u32 {
+        self.layer_mask
+    }
+
+    fn set_layer_mask(&mut self, layer_mask: u32) {
+        self.layer_mask = layer_mask;
+    }
+
+    fn get_light_radius(&self) -> Option<f32> {
+        if self.falloff <= 0.0 {
+            return None;
+        }
+
+        // past this the light adds less than one step of an 8 bit color
+        let half_diagonal = (self.width * self.width + self.height * self.height).sqrt() / 2.0;
+        Some((self.intensity.max(0.0) * 256.0).sqrt() / self.falloff + half_diagonal)
+    }
 }
 
 /// A directional light source with directional and distance falloff
+#[derive(Copy, Clone)]
 pub struct DirectionalLight {
     position: [f32; 3],
     direction: [f32; 3],
@@ -268,6 +411,7 @@ pub struct DirectionalLight {
     intensity: f32,
     angular_falloff: f32,
     distance_falloff: f32,
+    layer_mask: u32,
 }
 
 impl Default for DirectionalLight {
@@ -279,6 +423,7 @@ impl Default for DirectionalLight {
             intensity: 1.0,
             angular_falloff: 0.001,
             distance_falloff: 0.0,
+            layer_mask: u32::MAX,
         }
     }
 }
@@ -300,6 +445,7 @@ impl DirectionalLight {
             intensity,
             angular_falloff,
             distance_falloff,
+            layer_mask: u32::MAX,
         }
     }
 
@@ -320,12 +466,22 @@ impl DirectionalLight {
         self.color = [r, g, b];
     }
 
+    /// Get the color of the light
+    pub fn get_color(&self) -> [f32; 3] {
+        self.color
+    }
+
     /// Set the intensity of the light
     /// If the intensity is above 1.0, it can result in overexposure
     pub fn set_intensity(&mut self, intensity: f32) {
         self.intensity = intensity;
     }
 
+    /// Get the intensity of the light
+    pub fn get_intensity(&self) -> f32 {
+        self.intensity
+    }
+
     /// Set the angular falloff of the light
     /// 0.0 is no falloff, 1.0 is full falloff
     pub fn set_angular_falloff(&mut self, angular_falloff: f32) {
@@ -394,6 +550,40 @@ impl LightDrawable for DirectionalLight {
     fn set_transform(&mut self, transform: Transform) {
         self.position = [transform.get_x(), transform.get_y(), transform.get_z()];
     }
+
+    fn get_light_intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    fn set_light_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+    }
+
+    fn get_light_color(&self) -> [f32; 3] {
+        self.color
+    }
+
+    fn set_light_color(&mut self, color: [f32; 3]) {
+        self.color = color;
+    }
+
+    fn get_layer_mask(&self) -> u32 {
+        self.layer_mask
+    }
+
+    fn set_layer_mask(&mut self, layer_mask: u32) {
+        self.layer_mask = layer_mask;
+    }
+}
+
+/// the light layer of every pixel, lights only light the pixels on the layers in their mask
+fn get_layer_map(
+    program: &LumenpyxProgram,
+) -> glium::uniforms::Sampler<'_, glium::texture::Texture2d> {
+    let layer_texture = program
+        .get_pipeline_texture("layer_texture")
+        .expect("Failed to get layer texture");
+    glium::uniforms::Sampler(layer_texture, DEFAULT_BEHAVIOR)
 }
 
 /// draw the point light
@@ -416,7 +606,7 @@ pub(crate) fn draw_point_light(
     let light_pos = [
         ((matrix_transform[3][0]) + 1.0) * 0.5,
         ((matrix_transform[3][1]) + 1.0) * 0.5,
-        light.position[2] * matrix_transform[2][2],
+        matrix_transform[3][2],
     ];
 
     let vertex_buffer = glium::VertexBuffer::new(display, &shape)
@@ -424,6 +614,8 @@ pub(crate) fn draw_point_light(
 
     let uniforms = &uniform! {
         heightmap: heightmap,
+        layer_map: get_layer_map(program),
+        light_mask: light.layer_mask,
         albedomap: albedo_uniform,
         shadow_strength_map: shadow_strength_uniform,
         light_pos: light_pos,
@@ -467,7 +659,7 @@ fn draw_area_light(
     let light_pos = [
         ((matrix_transform[3][0]) + 1.0) * 0.5,
         ((matrix_transform[3][1]) + 1.0) * 0.5,
-        light.position[2] * matrix_transform[2][2],
+        matrix_transform[3][2],
     ];
     let light_width = light.width * matrix_transform[0][0];
     let light_height = light.height * matrix_transform[1][1];
@@ -477,6 +669,8 @@ fn draw_area_light(
 
     let uniforms = &uniform! {
         heightmap: height_uniform,
+        layer_map: get_layer_map(program),
+        light_mask: light.layer_mask,
         albedomap: albedo_uniform,
         shadow_strength_map: shadow_strength_uniform,
         light_pos: light_pos,
@@ -520,7 +714,7 @@ fn draw_directional_light(
     let light_pos = [
         ((matrix_transform[3][0]) + 1.0) * 0.5,
         ((matrix_transform[3][1]) + 1.0) * 0.5,
-        light.position[2] * matrix_transform[2][2],
+        matrix_transform[3][2],
     ];
 
     let vertex_buffer = glium::VertexBuffer::new(display, &shape)
@@ -528,6 +722,8 @@ fn draw_directional_light(
 
     let uniforms = &uniform! {
         heightmap: height_uniform,
+        layer_map: get_layer_map(program),
+        light_mask: light.layer_mask,
         albedomap: albedo_uniform,
         shadow_strength_map: shadow_strength_uniform,
         light_pos: light_pos,
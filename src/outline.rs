@@ -0,0 +1,319 @@
+use glium::uniform;
+use glium::DrawParameters;
+use glium::Surface;
+
+use crate::Drawable;
+use crate::LumenpyxProgram;
+use crate::Transform;
+use crate::DEFAULT_BEHAVIOR;
+use crate::DEFAULT_BLEND;
+use crate::FULL_SCREEN_QUAD;
+
+const OUTLINE_SHADER_FRAG: &str = include_str!("../shaders/technical_shaders/outline.frag");
+const OUTLINE_SHADER_VERT: &str = include_str!("../shaders/technical_shaders/outline.vert");
+
+/// Draws an object with an outline around its albedo.
+/// Put this in the drawables instead of the object itself.
+pub struct Outline<'a, T>
+where
+    T: Drawable + ?Sized,
+{
+    object: &'a T,
+    /// added on top of the object's transform
+    transform: Transform,
+    color: [f32; 4],
+    /// in pixels
+    thickness: u32,
+    /// if true, the outline is only drawn where something taller is in front of the object
+    occluded_only: bool,
+    /// fills the parts of the object that are covered, only used if occluded_only is true
+    silhouette_color: Option<[f32; 4]>,
+}
+
+impl<'a, T> Outline<'a, T>
+where
+    T: Drawable + ?Sized,
+{
+    /// Creates a 1 pixel outline around the object
+    pub fn new(object: &'a T, color: [f32; 4]) -> Self {
+        Self {
+            object,
+            transform: Transform::new([0.0, 0.0, 0.0]),
+            color,
+            thickness: 1,
+            occluded_only: false,
+            silhouette_color: None,
+        }
+    }
+
+    pub fn set_color(&mut self, color: [f32; 4]) {
+        self.color = color;
+    }
+
+    pub fn get_color(&self) -> [f32; 4] {
+        self.color
+    }
+
+    /// Set the thickness of the outline in pixels
+    pub fn set_thickness(&mut self, thickness: u32) {
+        self.thickness = thickness;
+    }
+
+    pub fn get_thickness(&self) -> u32 {
+        self.thickness
+    }
+
+    /// If true, the outline is only drawn where something with a higher height covers the object.
+    /// This uses the height of the scene, so shadows or reflections need to be on.
+    /// Make sure this is after the objects that cover it in the drawables
+    pub fn set_occluded_only(&mut self, occluded_only: bool) {
+        self.occluded_only = occluded_only;
+    }
+
+    pub fn get_occluded_only(&self) -> bool {
+        self.occluded_only
+    }
+
+    /// Fill the parts of the object that are covered with this color, only used if occluded only is on
+    pub fn set_silhouette_color(&mut self, silhouette_color: Option<[f32; 4]>) {
+        self.silhouette_color = silhouette_color;
+    }
+
+    pub fn get_silhouette_color(&self) -> Option<[f32; 4]> {
+        self.silhouette_color
+    }
+
+    /// Set the transform added on top of the object's transform
+    pub fn set_offset(&mut self, offset: Transform) {
+        self.transform = offset;
+    }
+
+    pub fn get_offset(&self) -> Transform {
+        self.transform
+    }
+
+    /// draw the albedo and height of the object on their own so the outline shader can find the edges
+    fn draw_object_textures(&self, program: &LumenpyxProgram, transform: &Transform) {
+        let display = &program.display;
+
+        let albedo_texture = program
+            .get_pipeline_texture("outline_albedo_texture")
+            .expect("Failed to get outline albedo texture");
+        let height_texture = program
+            .get_pipeline_texture("outline_height_texture")
+            .expect("Failed to get outline height texture");
+
+        let mut albedo_framebuffer =
+            glium::framebuffer::SimpleFrameBuffer::new(display, albedo_texture)
+                .expect("Failed to create outline albedo framebuffer");
+        albedo_framebuffer.clear_color(0.0, 0.0, 0.0, 0.0);
+        self.object
+            .draw_albedo(program, transform, &mut albedo_framebuffer);
+
+        let mut height_framebuffer =
+            glium::framebuffer::SimpleFrameBuffer::new(display, height_texture)
+                .expect("Failed to create outline height framebuffer");
+        height_framebuffer.clear_color(0.0, 0.0, 0.0, 0.0);
+        self.object
+            .draw_height(program, transform, &mut height_framebuffer);
+    }
+
+    fn draw_outline(
+        &self,
+        program: &LumenpyxProgram,
+        framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+        scene_height: Option<glium::uniforms::Sampler<glium::texture::Texture2d>>,
+    ) {
+        let display = &program.display;
+        let indices = &program.indices;
+
+        let shader = program
+            .get_shader("outline_shader")
+            .expect("Failed to get outline shader");
+
+        let albedo_texture = program
+            .get_pipeline_texture("outline_albedo_texture")
+            .expect("Failed to get outline albedo texture");
+        let height_texture = program
+            .get_pipeline_texture("outline_height_texture")
+            .expect("Failed to get outline height texture");
+
+        let object_albedo = glium::uniforms::Sampler(albedo_texture, DEFAULT_BEHAVIOR);
+        let object_height = glium::uniforms::Sampler(height_texture, DEFAULT_BEHAVIOR);
+        // the scene height isn't used unless occluded only is on, but it still needs to be bound
+        let scene_height = scene_height.unwrap_or(object_height);
+
+        let uniforms = uniform! {
+            object_albedo: object_albedo,
+            object_height: object_height,
+            scene_height: scene_height,
+            outline_color: self.color,
+            thickness: self.thickness as i32,
+            occluded_only: self.occluded_only,
+            silhouette_color: self.silhouette_color.unwrap_or([0.0, 0.0, 0.0, 0.0]),
+        };
+
+        let shape = FULL_SCREEN_QUAD;
+
+        let vertex_buffer =
+            glium::VertexBuffer::new(display, &shape).expect("Failed to create vertex buffer");
+
+        framebuffer
+            .draw(
+                &vertex_buffer,
+                indices,
+                shader,
+                &uniforms,
+                &DrawParameters {
+                    blend: DEFAULT_BLEND,
+                    ..Default::default()
+                },
+            )
+            .expect("Failed to draw outline");
+    }
+}
+
+impl<'a, T> Drawable for Outline<'a, T>
+where
+    T: Drawable + ?Sized,
+{
+    fn draw_albedo(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_albedo(program, transform, albedo_framebuffer);
+
+        // the occluded outline has to wait until the height of the scene is drawn
+        if !self.occluded_only {
+            self.draw_object_textures(program, transform);
+            self.draw_outline(program, albedo_framebuffer, None);
+        }
+    }
+
+    fn draw_height(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        height_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_height(program, transform, height_framebuffer);
+    }
+
+    fn draw_roughness(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        roughness_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_roughness(program, transform, roughness_framebuffer);
+    }
+
+    fn draw_normal(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        normal_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_normal(program, transform, normal_framebuffer);
+    }
+
+    fn draw_albedo_overlay(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+        scene_height: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    ) {
+        self.object
+            .draw_albedo_overlay(program, transform, albedo_framebuffer, scene_height);
+
+        if self.occluded_only {
+            self.draw_object_textures(program, transform);
+            self.draw_outline(program, albedo_framebuffer, Some(scene_height));
+        }
+    }
+
+    fn draw_emissive(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        emissive_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_emissive(program, transform, emissive_framebuffer);
+    }
+
+    fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
+        self.object.try_load_shaders(program);
+
+        if program.get_shader("outline_shader").is_none() {
+            let shader = glium::Program::from_source(
+                &program.display,
+                OUTLINE_SHADER_VERT,
+                OUTLINE_SHADER_FRAG,
+                None,
+            )
+            .expect("Failed to create outline shader");
+
+            program.add_shader(shader, "outline_shader");
+        }
+
+        let render_resolution = program.get_render_resolution();
+
+        // only remake the textures if the render resolution changed
+        // named per viewport like the other pipeline textures, so viewports with different resolutions don't fight over them
+        for name in ["outline_albedo_texture", "outline_height_texture"] {
+            let name = program.get_pipeline_texture_name(name);
+            let needs_texture = match program.get_texture(&name) {
+                Some(texture) => {
+                    texture.dimensions() != (render_resolution[0], render_resolution[1])
+                }
+                None => true,
+            };
+
+            if needs_texture {
+                let new_texture = glium::texture::Texture2d::empty_with_format(
+                    &program.display,
+                    glium::texture::UncompressedFloatFormat::U8U8U8U8,
+                    glium::texture::MipmapsOption::NoMipmap,
+                    render_resolution[0],
+                    render_resolution[1],
+                )
+                .expect("Failed to create outline texture");
+
+                program.add_texture(new_texture, &name);
+            }
+        }
+    }
+
+    fn get_transform(&self) -> Transform {
+        self.object.get_transform().add_parent(&self.transform)
+    }
+
+    fn get_recieve_shadows_strength(&self) -> f32 {
+        self.object.get_recieve_shadows_strength()
+    }
+
+    fn get_pick_id(&self) -> Option<u32> {
+        self.object.get_pick_id()
+    }
+
+    fn get_layer(&self) -> &str {
+        self.object.get_layer()
+    }
+
+    fn get_z_index(&self) -> i32 {
+        self.object.get_z_index()
+    }
+
+    /// Moves the object to the transform by changing the offset, so get_transform returns the transform afterwards
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = crate::get_wrapper_offset(&self.object.get_transform(), &transform);
+    }
+}
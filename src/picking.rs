@@ -0,0 +1,214 @@
+use crate::Drawable;
+use crate::LumenpyxProgram;
+use crate::Transform;
+
+/// Draws an object with an id that can be found with `LumenpyxProgram::pick`.
+/// Put this in the drawables instead of the object itself, picking has to be turned on in the render settings.
+///
+/// The id is written where the object changes the albedo, so parts of it drawn over
+/// the exact same color, or fully transparent parts, can't be picked
+pub struct Pickable<'a, T>
+where
+    T: Drawable + ?Sized,
+{
+    object: &'a T,
+    /// added on top of the object's transform
+    transform: Transform,
+    id: u32,
+}
+
+impl<'a, T> Pickable<'a, T>
+where
+    T: Drawable + ?Sized,
+{
+    /// The id can be anything but u32::MAX, it's up to you to keep them unique
+    pub fn new(object: &'a T, id: u32) -> Self {
+        if id == u32::MAX {
+            panic!("Pick id can't be u32::MAX");
+        }
+
+        Self {
+            object,
+            transform: Transform::new([0.0, 0.0, 0.0]),
+            id,
+        }
+    }
+
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    /// Set the transform added on top of the object's transform
+    pub fn set_offset(&mut self, offset: Transform) {
+        self.transform = offset;
+    }
+
+    pub fn get_offset(&self) -> Transform {
+        self.transform
+    }
+}
+
+/// the id as it is stored in the id texture, 0 is nothing so every id is one higher.
+/// That is why u32::MAX can't be an id, it would wrap around to nothing
+pub(crate) fn encode_pick_id(id: Option<u32>) -> u32 {
+    id.map_or(0, |id| id.wrapping_add(1))
+}
+
+/// the id from the bytes of a pixel in the id texture
+pub(crate) fn decode_pick_id(bytes: [u8; 4]) -> Option<u32> {
+    match u32::from_le_bytes(bytes) {
+        0 => None,
+        id => Some(id - 1),
+    }
+}
+
+impl<'a, T> Drawable for Pickable<'a, T>
+where
+    T: Drawable + ?Sized,
+{
+    fn draw_albedo(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_albedo(program, transform, albedo_framebuffer);
+    }
+
+    fn draw_height(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        height_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_height(program, transform, height_framebuffer);
+    }
+
+    fn draw_roughness(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        roughness_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_roughness(program, transform, roughness_framebuffer);
+    }
+
+    fn draw_normal(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        normal_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_normal(program, transform, normal_framebuffer);
+    }
+
+    fn draw_albedo_overlay(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+        scene_height: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    ) {
+        self.object
+            .draw_albedo_overlay(program, transform, albedo_framebuffer, scene_height);
+    }
+
+    fn draw_emissive(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        emissive_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_emissive(program, transform, emissive_framebuffer);
+    }
+
+    fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
+        self.object.try_load_shaders(program);
+    }
+
+    fn get_transform(&self) -> Transform {
+        self.object.get_transform().add_parent(&self.transform)
+    }
+
+    fn get_recieve_shadows_strength(&self) -> f32 {
+        self.object.get_recieve_shadows_strength()
+    }
+
+    fn get_pick_id(&self) -> Option<u32> {
+        Some(self.id)
+    }
+
+    fn get_layer(&self) -> &str {
+        self.object.get_layer()
+    }
+
+    fn get_z_index(&self) -> i32 {
+        self.object.get_z_index()
+    }
+
+    /// Moves the object to the transform by changing the offset, so get_transform returns the transform afterwards
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = crate::get_wrapper_offset(&self.object.get_transform(), &transform);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::Layered;
+    use crate::primitives::Sprite;
+
+    #[test]
+    fn pick_ids_round_trip() {
+        assert_eq!(encode_pick_id(None), 0);
+        assert_eq!(decode_pick_id([0; 4]), None);
+
+        for id in [0, 1, 255, 256, 0x12345678, u32::MAX - 1] {
+            let encoded = encode_pick_id(Some(id));
+            assert_eq!(encoded, id + 1);
+            assert_eq!(decode_pick_id(encoded.to_le_bytes()), Some(id));
+        }
+    }
+
+    #[test]
+    fn max_id_is_reserved() {
+        // it would be stored as nothing
+        assert_eq!(encode_pick_id(Some(u32::MAX)), encode_pick_id(None));
+        assert_eq!(decode_pick_id([255; 4]), Some(u32::MAX - 1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn pickable_rejects_max_id() {
+        let sprite = Sprite::new_untextured(Transform::new([0.0, 0.0, 0.0]));
+        Pickable::new(&sprite, u32::MAX);
+    }
+
+    #[test]
+    fn pickable_forwards_to_the_object() {
+        let mut sprite = Sprite::new_untextured(Transform::new([3.0, 4.0, 0.0]));
+        sprite.set_shadow_strength(0.25);
+        let layered = Layered::new(&sprite, "ui", 5);
+        let mut pickable = Pickable::new(&layered, 7);
+
+        assert_eq!(pickable.get_pick_id(), Some(7));
+        assert_eq!(pickable.get_layer(), "ui");
+        assert_eq!(pickable.get_z_index(), 5);
+        assert_eq!(pickable.get_recieve_shadows_strength(), 0.25);
+
+        pickable.set_offset(Transform::new([1.0, -1.0, 0.0]));
+        let transform = pickable.get_transform();
+        assert_eq!([transform.get_x(), transform.get_y()], [4.0, 3.0]);
+
+        // setting the transform moves the offset and leaves the object where it is
+        pickable.set_transform(Transform::new([10.0, 20.0, 0.0]));
+        let transform = pickable.get_transform();
+        assert_eq!([transform.get_x(), transform.get_y()], [10.0, 20.0]);
+        assert_eq!(sprite.get_transform().get_x(), 3.0);
+    }
+}
@@ -233,12 +233,35 @@ fn draw_rectangle(
         .expect("Failed to draw rectangle");
 }
 
+/// multiply the color by the tint and the alpha by the opacity
+fn apply_tint(color: [f32; 4], tint: [f32; 4], opacity: f32) -> [f32; 4] {
+    [
+        color[0] * tint[0],
+        color[1] * tint[1],
+        color[2] * tint[2],
+        color[3] * tint[3] * opacity,
+    ]
+}
+
+/// a height and roughness of 0, covering what is under the shape as much as the shape is visible,
+/// so faded shapes don't hide the height and roughness of what is behind them.
+/// None if the shape isn't faded, so it leaves the height and roughness alone like it always has
+fn get_flat_color(color: [f32; 4], tint: [f32; 4], opacity: f32) -> Option<[f32; 4]> {
+    if opacity * tint[3] >= 1.0 {
+        return None;
+    }
+
+    Some([0.0, 0.0, 0.0, apply_tint(color, tint, opacity)[3]])
+}
+
 /// A circle primitive.
 pub struct Circle {
     color: [f32; 4],
     radius: f32,
     pub transform: Transform,
     shadow_strength: f32,
+    tint: [f32; 4],
+    opacity: f32,
 }
 
 impl Circle {
@@ -249,8 +272,29 @@ impl Circle {
             radius,
             transform,
             shadow_strength: 0.5,
+            tint: [1.0, 1.0, 1.0, 1.0],
+            opacity: 1.0,
         }
     }
+
+    /// Set the color the circle is multiplied by, [1.0, 1.0, 1.0, 1.0] leaves the circle unchanged
+    pub fn set_tint(&mut self, tint: [f32; 4]) {
+        self.tint = tint;
+    }
+
+    pub fn get_tint(&self) -> [f32; 4] {
+        self.tint
+    }
+
+    /// Set the opacity of the circle, 0.0 is invisible and 1.0 is fully visible.
+    /// The shadow strength is scaled by the opacity as well, and a faded circle only covers the height and roughness behind it as much as it is visible
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity;
+    }
+
+    pub fn get_opacity(&self) -> f32 {
+        self.opacity
+    }
 }
 
 impl Drawable for Circle {
@@ -261,14 +305,47 @@ impl Drawable for Circle {
         albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
     ) {
         draw_circle(
-            self.color,
+            apply_tint(self.color, self.tint, self.opacity),
             self.radius,
             transform.get_matrix(),
             program,
             albedo_framebuffer,
         );
     }
-    // don't need to implement the other draw functions because they are not used
+
+    fn draw_height(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        height_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        if let Some(color) = get_flat_color(self.color, self.tint, self.opacity) {
+            draw_circle(
+                color,
+                self.radius,
+                transform.get_matrix(),
+                program,
+                height_framebuffer,
+            );
+        }
+    }
+
+    fn draw_roughness(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        roughness_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        if let Some(color) = get_flat_color(self.color, self.tint, self.opacity) {
+            draw_circle(
+                color,
+                self.radius,
+                transform.get_matrix(),
+                program,
+                roughness_framebuffer,
+            );
+        }
+    }
 
     fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
         if program.get_shader("circle_ahr_shader").is_some() {
@@ -291,7 +368,7 @@ impl Drawable for Circle {
     }
 
     fn get_recieve_shadows_strength(&self) -> f32 {
-        self.shadow_strength
+        self.shadow_strength * self.opacity
     }
 
     fn set_transform(&mut self, transform: Transform) {
@@ -483,6 +560,8 @@ pub struct Rectangle {
     height: f32,
     pub transform: Transform,
     shadow_strength: f32,
+    tint: [f32; 4],
+    opacity: f32,
 }
 
 impl Rectangle {
@@ -494,8 +573,29 @@ impl Rectangle {
             height,
             transform,
             shadow_strength: 0.5,
+            tint: [1.0, 1.0, 1.0, 1.0],
+            opacity: 1.0,
         }
     }
+
+    /// Set the color the rectangle is multiplied by, [1.0, 1.0, 1.0, 1.0] leaves the rectangle unchanged
+    pub fn set_tint(&mut self, tint: [f32; 4]) {
+        self.tint = tint;
+    }
+
+    pub fn get_tint(&self) -> [f32; 4] {
+        self.tint
+    }
+
+    /// Set the opacity of the rectangle, 0.0 is invisible and 1.0 is fully visible.
+    /// The shadow strength is scaled by the opacity as well, and a faded rectangle only covers the height and roughness behind it as much as it is visible
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity;
+    }
+
+    pub fn get_opacity(&self) -> f32 {
+        self.opacity
+    }
 }
 
 impl Drawable for Rectangle {
@@ -506,7 +606,7 @@ impl Drawable for Rectangle {
         albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
     ) {
         draw_rectangle(
-            self.color,
+            apply_tint(self.color, self.tint, self.opacity),
             self.width,
             self.height,
             transform.get_matrix(),
@@ -515,6 +615,42 @@ impl Drawable for Rectangle {
         );
     }
 
+    fn draw_height(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        height_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        if let Some(color) = get_flat_color(self.color, self.tint, self.opacity) {
+            draw_rectangle(
+                color,
+                self.width,
+                self.height,
+                transform.get_matrix(),
+                program,
+                height_framebuffer,
+            );
+        }
+    }
+
+    fn draw_roughness(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        roughness_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        if let Some(color) = get_flat_color(self.color, self.tint, self.opacity) {
+            draw_rectangle(
+                color,
+                self.width,
+                self.height,
+                transform.get_matrix(),
+                program,
+                roughness_framebuffer,
+            );
+        }
+    }
+
     fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
         if program.get_shader("rectangle_ahr_shader").is_some() {
             return;
@@ -536,7 +672,7 @@ impl Drawable for Rectangle {
     }
 
     fn get_recieve_shadows_strength(&self) -> f32 {
-        self.shadow_strength
+        self.shadow_strength * self.opacity
     }
 
     fn set_transform(&mut self, transform: Transform) {
@@ -1029,7 +1165,7 @@ impl From<glium::texture::Texture2d> for Texture {
 pub enum Normal {
     /// Path to a normal map
     Path(String),
-    /// Solid color normal map
+    /// Solid color normal map, stored like a normal map image so [0.5, 0.5, 1.0, 1.0] points straight out of the screen
     Solid([f32; 4]),
     /// Generates a relatively accurate normal map from the height map
     AutoGenerated,
@@ -1201,6 +1337,16 @@ pub struct Sprite {
     normal_texture: TextureHandle,
     pub transform: Transform,
     shadow_strength: f32,
+    /// the part of the texture that is drawn, x, y, width, height in 0.0 - 1.0 texture coordinates
+    source_rect: [f32; 4],
+    flip_x: bool,
+    flip_y: bool,
+    /// the point the sprite rotates around, [0.5, 0.5] is the center
+    pivot: [f32; 2],
+    tint: [f32; 4],
+    opacity: f32,
+    dissolve: Option<Dissolve>,
+    palette_swap: Option<PaletteSwap>,
 }
 
 impl Sprite {
@@ -1275,6 +1421,14 @@ impl Sprite {
                 normal_texture: normal_handle,
                 transform,
                 shadow_strength: 0.5,
+                source_rect: [0.0, 0.0, 1.0, 1.0],
+                flip_x: false,
+                flip_y: false,
+                pivot: [0.5, 0.5],
+                tint: [1.0, 1.0, 1.0, 1.0],
+                opacity: 1.0,
+                dissolve: None,
+                palette_swap: None,
             },
             albedo_handle,
             height_handle,
@@ -1283,39 +1437,204 @@ impl Sprite {
         )
     }
 
+    /// a sprite with no textures behind it, for testing things that don't draw
+    #[cfg(test)]
+    pub(crate) fn new_untextured(transform: Transform) -> Sprite {
+        let handle = TextureHandle { id: 0 };
+        Sprite {
+            width: 1,
+            height: 1,
+            albedo_texture: handle,
+            height_texture: handle,
+            roughness_texture: handle,
+            normal_texture: handle,
+            transform,
+            shadow_strength: 0.5,
+            source_rect: [0.0, 0.0, 1.0, 1.0],
+            flip_x: false,
+            flip_y: false,
+            pivot: [0.5, 0.5],
+            tint: [1.0, 1.0, 1.0, 1.0],
+            opacity: 1.0,
+            dissolve: None,
+            palette_swap: None,
+        }
+    }
+
     pub fn set_shadow_strength(&mut self, strength: f32) {
         self.shadow_strength = strength;
     }
 
+    /// Set the part of the texture that is drawn in 0.0 - 1.0 texture coordinates.
+    /// The rect is x, y, width, height with (0.0, 0.0) being the top left of the texture
+    pub fn set_source_rect(&mut self, source_rect: [f32; 4]) {
+        // textures are stored bottom to top, so flip the y to match
+        self.source_rect = [
+            source_rect[0],
+            1.0 - source_rect[1] - source_rect[3],
+            source_rect[2],
+            source_rect[3],
+        ];
+    }
+
+    /// Set the part of the texture that is drawn in pixels.
+    /// (0, 0) is the top left of the texture
+    pub fn set_source_rect_pixels(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        self.set_source_rect([
+            x as f32 / self.width as f32,
+            y as f32 / self.height as f32,
+            width as f32 / self.width as f32,
+            height as f32 / self.height as f32,
+        ]);
+    }
+
+    /// Get the part of the texture that is drawn in 0.0 - 1.0 texture coordinates.
+    /// The rect is x, y, width, height with (0.0, 0.0) being the top left of the texture
+    pub fn get_source_rect(&self) -> [f32; 4] {
+        [
+            self.source_rect[0],
+            1.0 - self.source_rect[1] - self.source_rect[3],
+            self.source_rect[2],
+            self.source_rect[3],
+        ]
+    }
+
+    /// Draw the whole texture again
+    pub fn clear_source_rect(&mut self) {
+        self.source_rect = [0.0, 0.0, 1.0, 1.0];
+    }
+
+    /// Mirror the sprite horizontally, this is applied to all of the textures
+    pub fn set_flip_x(&mut self, flip_x: bool) {
+        self.flip_x = flip_x;
+    }
+
+    pub fn get_flip_x(&self) -> bool {
+        self.flip_x
+    }
+
+    /// Mirror the sprite vertically, this is applied to all of the textures
+    pub fn set_flip_y(&mut self, flip_y: bool) {
+        self.flip_y = flip_y;
+    }
+
+    pub fn get_flip_y(&self) -> bool {
+        self.flip_y
+    }
+
+    /// Set the point that the sprite rotates around.
+    /// [0.0, 0.0] is the bottom left of the sprite, [1.0, 1.0] is the top right, the default is [0.5, 0.5]
+    pub fn set_pivot(&mut self, pivot: [f32; 2]) {
+        self.pivot = pivot;
+    }
+
+    pub fn get_pivot(&self) -> [f32; 2] {
+        self.pivot
+    }
+
+    /// Set the color the albedo is multiplied by, [1.0, 1.0, 1.0, 1.0] leaves the sprite unchanged
+    pub fn set_tint(&mut self, tint: [f32; 4]) {
+        self.tint = tint;
+    }
+
+    pub fn get_tint(&self) -> [f32; 4] {
+        self.tint
+    }
+
+    /// Set the opacity of the sprite, 0.0 is invisible and 1.0 is fully visible.
+    /// The shadow strength is scaled by the opacity as well
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity;
+    }
+
+    pub fn get_opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    /// Burn the sprite away with a noise texture, None turns the effect off
+    pub fn set_dissolve(&mut self, dissolve: Option<Dissolve>) {
+        self.dissolve = dissolve;
+    }
+
+    pub fn get_dissolve(&self) -> Option<Dissolve> {
+        self.dissolve
+    }
+
+    /// Set how far the sprite is dissolved, 0.0 is fully visible and 1.0 is gone.
+    /// Does nothing if there is no dissolve set
+    pub fn set_dissolve_threshold(&mut self, threshold: f32) {
+        if let Some(dissolve) = &mut self.dissolve {
+            dissolve.threshold = threshold;
+        }
+    }
+
+    /// Swap the colors of the sprite with another palette, None turns the effect off
+    pub fn set_palette_swap(&mut self, palette_swap: Option<PaletteSwap>) {
+        self.palette_swap = palette_swap;
+    }
+
+    pub fn get_palette_swap(&self) -> Option<PaletteSwap> {
+        self.palette_swap
+    }
+
+    /// Get the size of the part of the texture that is drawn in pixels
+    pub fn get_drawn_size(&self) -> [f32; 2] {
+        [
+            self.width as f32 * self.source_rect[2],
+            self.height as f32 * self.source_rect[3],
+        ]
+    }
+
     pub fn free_all_textures(&self, program: &mut LumenpyxProgram) {
         program.remove_texture(&self.albedo_texture);
         program.remove_texture(&self.height_texture);
         program.remove_texture(&self.roughness_texture);
         program.remove_texture(&self.normal_texture);
     }
-}
 
-impl Drawable for Sprite {
-    fn draw_albedo(
+    /// the tint is only applied to the albedo, the opacity and effects are applied to every texture
+    fn get_draw_settings(&self, channel: SpriteChannel) -> SpriteDrawSettings {
+        let is_albedo = channel == SpriteChannel::Albedo;
+        SpriteDrawSettings {
+            source_rect: self.source_rect,
+            flip_x: self.flip_x,
+            flip_y: self.flip_y,
+            is_albedo,
+            is_normal_map: channel == SpriteChannel::Normal,
+            is_emissive: channel == SpriteChannel::Emissive,
+            tint: if is_albedo {
+                self.tint
+            } else {
+                [1.0, 1.0, 1.0, 1.0]
+            },
+            opacity: self.opacity,
+            dissolve: self.dissolve,
+            palette_swap: self.palette_swap,
+            palette_row: match channel {
+                SpriteChannel::Albedo => 0,
+                SpriteChannel::Height => 1,
+                SpriteChannel::Roughness => 2,
+                SpriteChannel::Normal | SpriteChannel::Emissive => -1,
+            },
+            palette_albedo: Some(self.albedo_texture),
+        }
+    }
+
+    /// scale the transform to the size of the sprite and get the matrix rotated around the pivot
+    fn get_sprite_matrix(
         &self,
-        program: &LumenpyxProgram,
         transform: &Transform,
-        albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
-    ) {
-        let albedo_texture: &glium::Texture2d = program
-            .get_texture_from_handle(&self.albedo_texture)
-            .expect("failed to get albedo texture");
-
+        framebuffer: &glium::framebuffer::SimpleFrameBuffer,
+    ) -> [[f32; 4]; 4] {
         // scale the transform matrix to match the size of the texture
         // check which side is longer and scale the other side to match
-        let width = self.width as f32;
-        let height = self.height as f32;
+        let [width, height] = self.get_drawn_size();
         let mut transform = transform.clone();
 
         // adjust size of the sprite to match the texture
         {
-            let smallest_dimension = (albedo_framebuffer.get_dimensions().1 as f32)
-                .min(albedo_framebuffer.get_dimensions().0 as f32);
+            let smallest_dimension =
+                (framebuffer.get_dimensions().1 as f32).min(framebuffer.get_dimensions().0 as f32);
             let x_scale = width / smallest_dimension;
             let y_scale = height / smallest_dimension;
 
@@ -1326,11 +1645,29 @@ impl Drawable for Sprite {
             );
         }
 
+        // the quad goes from -1.0 to 1.0, so move the pivot into that space
+        let pivot = [self.pivot[0] * 2.0 - 1.0, self.pivot[1] * 2.0 - 1.0];
+        transform.get_matrix_with_pivot(pivot)
+    }
+}
+
+impl Drawable for Sprite {
+    fn draw_albedo(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        let albedo_texture: &glium::Texture2d = program
+            .get_texture_from_handle(&self.albedo_texture)
+            .expect("failed to get albedo texture");
+
         draw_texture(
             &albedo_texture,
-            transform.get_matrix(),
+            self.get_sprite_matrix(transform, albedo_framebuffer),
             program,
             albedo_framebuffer,
+            &self.get_draw_settings(SpriteChannel::Albedo),
         );
     }
 
@@ -1344,33 +1681,12 @@ impl Drawable for Sprite {
             .get_texture_from_handle(&self.height_texture)
             .expect("failed to get albedo texture");
 
-        // scale the transform matrix to match the size of the texture
-        // check which side is longer and scale the other side to match
-        let width = height_texture.get_width() as f32;
-        let height = height_texture
-            .get_height()
-            .expect("failed to get height of sprite's texture") as f32;
-        let mut transform = transform.clone();
-
-        // adjust size of the sprite to match the texture
-        {
-            let smallest_dimension = (height_framebuffer.get_dimensions().1 as f32)
-                .min(height_framebuffer.get_dimensions().0 as f32);
-            let x_scale = width as f32 / smallest_dimension;
-            let y_scale = height as f32 / smallest_dimension;
-
-            transform.set_scale(
-                transform.get_scale()[0] * x_scale,
-                transform.get_scale()[1] * y_scale,
-                transform.get_scale()[2],
-            );
-        }
-
         draw_texture(
             &height_texture,
-            transform.get_matrix(),
+            self.get_sprite_matrix(transform, height_framebuffer),
             program,
             height_framebuffer,
+            &self.get_draw_settings(SpriteChannel::Height),
         );
     }
 
@@ -1384,31 +1700,12 @@ impl Drawable for Sprite {
             .get_texture_from_handle(&self.normal_texture)
             .expect("failed to get normal texture");
 
-        // scale the transform matrix to match the size of the texture
-        // check which side is longer and scale the other side to match
-        let width = self.width as f32;
-        let height = self.height as f32;
-        let mut transform = transform.clone();
-
-        // adjust size of the sprite to match the texture
-        {
-            let smallest_dimension = (normal_framebuffer.get_dimensions().1 as f32)
-                .min(normal_framebuffer.get_dimensions().0 as f32);
-            let x_scale = width / smallest_dimension;
-            let y_scale = height / smallest_dimension;
-
-            transform.set_scale(
-                transform.get_scale()[0] * x_scale,
-                transform.get_scale()[1] * y_scale,
-                transform.get_scale()[2],
-            );
-        }
-
         draw_texture(
             &normal_texture,
-            transform.get_matrix(),
+            self.get_sprite_matrix(transform, normal_framebuffer),
             program,
             normal_framebuffer,
+            &self.get_draw_settings(SpriteChannel::Normal),
         );
     }
 
@@ -1422,31 +1719,37 @@ impl Drawable for Sprite {
             .get_texture_from_handle(&self.roughness_texture)
             .unwrap();
 
-        // scale the transform matrix to match the size of the texture
-        // check which side is longer and scale the other side to match
-        let width = self.width as f32;
-        let height = self.height as f32;
-        let mut transform = transform.clone();
-
-        // adjust size of the sprite to match the texture
-        {
-            let smallest_dimension = (roughness_framebuffer.get_dimensions().1 as f32)
-                .min(roughness_framebuffer.get_dimensions().0 as f32);
-            let x_scale = width / smallest_dimension;
-            let y_scale = height / smallest_dimension;
+        draw_texture(
+            &roughness_texture,
+            self.get_sprite_matrix(transform, roughness_framebuffer),
+            program,
+            roughness_framebuffer,
+            &self.get_draw_settings(SpriteChannel::Roughness),
+        );
+    }
 
-            transform.set_scale(
-                transform.get_scale()[0] * x_scale,
-                transform.get_scale()[1] * y_scale,
-                transform.get_scale()[2],
-            );
+    fn draw_emissive(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        emissive_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        // only the edge of the dissolve glows
+        match self.dissolve {
+            Some(dissolve) if dissolve.glow_strength > 0.0 => {}
+            _ => return,
         }
 
+        let albedo_texture = program
+            .get_texture_from_handle(&self.albedo_texture)
+            .expect("failed to get albedo texture");
+
         draw_texture(
-            &roughness_texture,
-            transform.get_matrix(),
+            albedo_texture,
+            self.get_sprite_matrix(transform, emissive_framebuffer),
             program,
-            roughness_framebuffer,
+            emissive_framebuffer,
+            &self.get_draw_settings(SpriteChannel::Emissive),
         );
     }
 
@@ -1472,7 +1775,7 @@ impl Drawable for Sprite {
 
     /// 0.0 is no shadows, 1.0 is full shadows
     fn get_recieve_shadows_strength(&self) -> f32 {
-        self.shadow_strength
+        self.shadow_strength * self.opacity
     }
 
     fn set_transform(&mut self, transform: Transform) {
@@ -1480,11 +1783,160 @@ impl Drawable for Sprite {
     }
 }
 
+/// Burns a sprite away where the noise texture is darker than the threshold,
+/// the pixels just above the threshold glow with the edge color
+#[derive(Clone, Copy, Debug)]
+pub struct Dissolve {
+    noise_texture: TextureHandle,
+    threshold: f32,
+    edge_width: f32,
+    edge_color: [f32; 4],
+    glow_strength: f32,
+}
+
+impl Dissolve {
+    /// The noise texture is stretched over the sprite, only the red channel is used
+    pub fn new(noise_texture: TextureHandle) -> Self {
+        Self {
+            noise_texture,
+            threshold: 0.0,
+            edge_width: 0.05,
+            edge_color: [1.0, 0.5, 0.1, 1.0],
+            glow_strength: 1.0,
+        }
+    }
+
+    /// 0.0 is fully visible and 1.0 is gone
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// How far above the threshold the noise has to be to not be part of the edge
+    pub fn with_edge_width(mut self, edge_width: f32) -> Self {
+        self.edge_width = edge_width;
+        self
+    }
+
+    /// The alpha is how much the edge color covers the albedo
+    pub fn with_edge_color(mut self, edge_color: [f32; 4]) -> Self {
+        self.edge_color = edge_color;
+        self
+    }
+
+    /// How bright the edge is without any lights on it, 0.0 turns the glow off.
+    /// The glow is only seen when shadows are on, otherwise the edge color in the albedo is all that shows
+    pub fn with_glow_strength(mut self, glow_strength: f32) -> Self {
+        self.glow_strength = glow_strength;
+        self
+    }
+
+    pub fn get_noise_texture(&self) -> TextureHandle {
+        self.noise_texture
+    }
+
+    pub fn get_threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    pub fn get_edge_width(&self) -> f32 {
+        self.edge_width
+    }
+
+    pub fn get_edge_color(&self) -> [f32; 4] {
+        self.edge_color
+    }
+
+    pub fn get_glow_strength(&self) -> f32 {
+        self.glow_strength
+    }
+}
+
+/// Swaps the colors of a sprite with the colors of another palette.
+/// The source palette is a single row of colors found in the albedo,
+/// the target palette has the replacement colors in the same columns.
+/// The first row of the target palette replaces the albedo,
+/// the optional second and third rows replace the height and roughness of the pixels with that albedo color
+#[derive(Clone, Copy, Debug)]
+pub struct PaletteSwap {
+    source_palette: TextureHandle,
+    target_palette: TextureHandle,
+}
+
+impl PaletteSwap {
+    pub fn new(source_palette: TextureHandle, target_palette: TextureHandle) -> Self {
+        Self {
+            source_palette,
+            target_palette,
+        }
+    }
+
+    pub fn get_source_palette(&self) -> TextureHandle {
+        self.source_palette
+    }
+
+    pub fn get_target_palette(&self) -> TextureHandle {
+        self.target_palette
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SpriteChannel {
+    Albedo,
+    Height,
+    Roughness,
+    Normal,
+    Emissive,
+}
+
+/// The settings passed to the sprite shader on top of the texture and matrix
+#[derive(Clone, Copy)]
+pub(crate) struct SpriteDrawSettings {
+    /// x, y, width, height in 0.0 - 1.0 texture coordinates, (0.0, 0.0) is the bottom left
+    pub(crate) source_rect: [f32; 4],
+    pub(crate) flip_x: bool,
+    pub(crate) flip_y: bool,
+    pub(crate) is_albedo: bool,
+    /// normal maps need their x and y flipped along with the texture
+    pub(crate) is_normal_map: bool,
+    /// only draw the glowing edge of the dissolve
+    pub(crate) is_emissive: bool,
+    /// multiplied with the color of the texture
+    pub(crate) tint: [f32; 4],
+    pub(crate) opacity: f32,
+    pub(crate) dissolve: Option<Dissolve>,
+    pub(crate) palette_swap: Option<PaletteSwap>,
+    /// the row of the target palette to use, 0 is albedo, 1 is height, 2 is roughness, -1 is none
+    pub(crate) palette_row: i32,
+    /// the albedo the palette colors are looked up in, if None the drawn texture is used
+    pub(crate) palette_albedo: Option<TextureHandle>,
+}
+
+impl Default for SpriteDrawSettings {
+    fn default() -> Self {
+        SpriteDrawSettings {
+            source_rect: [0.0, 0.0, 1.0, 1.0],
+            flip_x: false,
+            flip_y: false,
+            is_albedo: false,
+            is_normal_map: false,
+            is_emissive: false,
+            tint: [1.0, 1.0, 1.0, 1.0],
+            opacity: 1.0,
+            dissolve: None,
+            palette_swap: None,
+            palette_row: -1,
+            palette_albedo: None,
+        }
+    }
+}
+
 pub(crate) fn draw_texture(
     texture: &glium::texture::Texture2d,
     matrix_transform: [[f32; 4]; 4],
     program: &LumenpyxProgram,
     framebuffer: &mut SimpleFrameBuffer,
+    settings: &SpriteDrawSettings,
 ) {
     let display = &program.display;
     let indices = &program.indices;
@@ -1500,9 +1952,64 @@ pub(crate) fn draw_texture(
 
     let image = glium::uniforms::Sampler(texture, DEFAULT_BEHAVIOR);
 
+    // the samplers still have to be bound when the effects are off, so they fall back to the image
+    let (dissolve_threshold, dissolve_edge_width, dissolve_edge_color, dissolve_glow_strength) =
+        match settings.dissolve {
+            Some(dissolve) => (
+                dissolve.threshold,
+                dissolve.edge_width,
+                dissolve.edge_color,
+                dissolve.glow_strength,
+            ),
+            None => (0.0, 0.0, [0.0, 0.0, 0.0, 0.0], 0.0),
+        };
+    let dissolve_noise = match settings.dissolve {
+        Some(dissolve) => program
+            .get_texture_from_handle(&dissolve.noise_texture)
+            .expect("Failed to get dissolve noise texture"),
+        None => texture,
+    };
+
+    let (source_palette, target_palette) = match settings.palette_swap {
+        Some(palette_swap) => (
+            program
+                .get_texture_from_handle(&palette_swap.source_palette)
+                .expect("Failed to get source palette texture"),
+            program
+                .get_texture_from_handle(&palette_swap.target_palette)
+                .expect("Failed to get target palette texture"),
+        ),
+        None => (texture, texture),
+    };
+    let palette_albedo = match settings.palette_albedo {
+        Some(handle) => program
+            .get_texture_from_handle(&handle)
+            .expect("Failed to get palette albedo texture"),
+        None => texture,
+    };
+
     let uniform = &uniform! {
         matrix: matrix_transform,
         image: image,
+        source_rect: settings.source_rect,
+        flip_x: settings.flip_x,
+        flip_y: settings.flip_y,
+        is_albedo: settings.is_albedo,
+        is_normal_map: settings.is_normal_map,
+        tint: settings.tint,
+        opacity: settings.opacity,
+        use_dissolve: settings.dissolve.is_some(),
+        dissolve_noise: glium::uniforms::Sampler(dissolve_noise, DEFAULT_BEHAVIOR),
+        dissolve_threshold: dissolve_threshold,
+        dissolve_edge_width: dissolve_edge_width,
+        dissolve_edge_color: dissolve_edge_color,
+        is_emissive: settings.is_emissive,
+        dissolve_glow_strength: dissolve_glow_strength,
+        use_palette: settings.palette_swap.is_some(),
+        palette_albedo: glium::uniforms::Sampler(palette_albedo, DEFAULT_BEHAVIOR),
+        source_palette: glium::uniforms::Sampler(source_palette, DEFAULT_BEHAVIOR),
+        target_palette: glium::uniforms::Sampler(target_palette, DEFAULT_BEHAVIOR),
+        palette_row: settings.palette_row,
     };
 
     framebuffer
@@ -0,0 +1,88 @@
+use glium::uniforms::MagnifySamplerFilter;
+use glium::Surface;
+
+use crate::drawable_object::Drawable;
+use crate::lights::LightDrawable;
+use crate::Camera;
+use crate::LumenpyxProgram;
+use crate::TextureHandle;
+
+/// A texture a scene can be drawn to instead of the window, e.g. for a tv screen, a mirror or a minimap.
+/// The handle can be used as the albedo of a sprite like any other texture handle,
+/// and the sprite shows whatever was last drawn to the target
+pub struct RenderTarget {
+    /// the name of the textures the scene is drawn with, every target needs its own name
+    name: String,
+    handle: TextureHandle,
+    resolution: [u32; 2],
+}
+
+impl RenderTarget {
+    /// Make a target with the resolution in pixels, it's transparent until something is drawn to it
+    pub fn new(name: &str, resolution: [u32; 2], program: &mut LumenpyxProgram) -> Self {
+        let texture = glium::texture::Texture2d::empty_with_format(
+            &program.display,
+            glium::texture::UncompressedFloatFormat::U8U8U8U8,
+            glium::texture::MipmapsOption::NoMipmap,
+            resolution[0],
+            resolution[1],
+        )
+        .expect("Failed to create render target texture");
+
+        // start transparent instead of whatever was in the memory
+        texture.as_surface().clear_color(0.0, 0.0, 0.0, 0.0);
+
+        let handle = program.add_not_named_texture(texture);
+
+        Self {
+            name: name.to_string(),
+            handle,
+            resolution,
+        }
+    }
+
+    /// The handle of the texture, use it as a TextureInput e.g. `render_target.get_handle().into()`
+    pub fn get_handle(&self) -> TextureHandle {
+        self.handle
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_resolution(&self) -> [u32; 2] {
+        self.resolution
+    }
+
+    /// Draw the scene with all the lighting to the target, the same way draw_all draws to the window.
+    /// Don't draw a sprite using the target into it, it would show the last thing drawn to the target
+    pub fn draw(
+        &self,
+        lights: Vec<&dyn LightDrawable>,
+        drawables: Vec<&dyn Drawable>,
+        camera: &Camera,
+        program: &mut LumenpyxProgram,
+    ) {
+        program.begin_viewport(&format!("render_target_{}", self.name), self.resolution);
+        crate::prepare_scene(&lights, &drawables, program);
+        let finished_texture_name = crate::draw_scene(lights, drawables, program, camera);
+        let finished_texture_name = program.get_pipeline_texture_name(finished_texture_name);
+        program.end_viewport();
+
+        let finished_texture = program
+            .get_texture(&finished_texture_name)
+            .expect("Failed to get finished render target texture");
+        let target_texture = program
+            .get_texture_from_handle(&self.handle)
+            .expect("Failed to get render target texture, was it removed?");
+
+        finished_texture
+            .as_surface()
+            .fill(&target_texture.as_surface(), MagnifySamplerFilter::Nearest);
+    }
+
+    /// Remove the texture from the program, sprites using the handle can't be drawn after this
+    pub fn free_texture(&self, program: &mut LumenpyxProgram) {
+        program.remove_texture(&self.handle);
+    }
+}
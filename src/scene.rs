@@ -0,0 +1,691 @@
+use glium::framebuffer::SimpleFrameBuffer;
+
+use crate::drawable_object::Drawable;
+use crate::lights::LightDrawable;
+use crate::Camera;
+use crate::LumenpyxProgram;
+use crate::Transform;
+
+/// The id of a node in a scene, it stays the same until the node is removed
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct NodeId(usize);
+
+enum NodeContent {
+    /// a node that only groups its children
+    Empty,
+    Drawable(Box<dyn Drawable>),
+    Light(Box<dyn LightDrawable>),
+}
+
+struct SceneNode {
+    /// relative to the parent node
+    transform: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    content: NodeContent,
+    visible: bool,
+    /// these are used over the ones of the drawable when set
+    layer: Option<String>,
+    z_index: Option<i32>,
+    pick_id: Option<u32>,
+}
+
+/// A scene that keeps its drawables and lights between frames instead of passing them to draw_all every frame.
+/// Nodes can have children that move, rotate and scale with them, e.g. a torch light attached to a character.
+///
+/// The transform of a drawable or light is relative to its node, and the transform of a node is relative to its parent.
+/// Parents are drawn before their children
+///
+/// The scene owns its drawables, so wrappers that borrow their object like Layered, Pickable and Outline can't be put in it.
+/// Set the layer, z index and pick id on the node instead
+#[derive(Default)]
+pub struct Scene {
+    /// removed nodes leave a None so the ids of the other nodes don't change
+    nodes: Vec<Option<SceneNode>>,
+    roots: Vec<NodeId>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a node with nothing in it, useful for moving a group of nodes together
+    pub fn add_node(&mut self, parent: Option<NodeId>, transform: Transform) -> NodeId {
+        self.insert_node(parent, transform, NodeContent::Empty)
+    }
+
+    pub fn add_drawable(
+        &mut self,
+        parent: Option<NodeId>,
+        transform: Transform,
+        drawable: impl Drawable + 'static,
+    ) -> NodeId {
+        self.insert_node(parent, transform, NodeContent::Drawable(Box::new(drawable)))
+    }
+
+    pub fn add_light(
+        &mut self,
+        parent: Option<NodeId>,
+        transform: Transform,
+        light: impl LightDrawable + 'static,
+    ) -> NodeId {
+        self.insert_node(parent, transform, NodeContent::Light(Box::new(light)))
+    }
+
+    /// Remove the node and all of its children
+    pub fn remove_node(&mut self, node: NodeId) {
+        let removed = self.nodes[node.0]
+            .take()
+            .expect("Failed to remove node, it was already removed");
+
+        self.detach(node, removed.parent);
+        for child in removed.children {
+            // the child is still pointing at this node, so it has to be detached from nothing
+            if let Some(child_node) = &mut self.nodes[child.0] {
+                child_node.parent = None;
+            }
+            self.remove_node(child);
+        }
+    }
+
+    /// Move the node to another parent, or to the top of the scene with None.
+    /// The transform of the node is kept, so it moves with its new parent from where the parent is
+    pub fn set_parent(&mut self, node: NodeId, parent: Option<NodeId>) {
+        // make sure the node isn't being put inside itself
+        let mut ancestor = parent;
+        while let Some(ancestor_id) = ancestor {
+            if ancestor_id == node {
+                panic!("A node can't be a child of itself or its children");
+            }
+            ancestor = self.get_node(ancestor_id).parent;
+        }
+
+        let old_parent = self.get_node(node).parent;
+        self.detach(node, old_parent);
+
+        self.get_node_mut(node).parent = parent;
+        match parent {
+            Some(parent) => self.get_node_mut(parent).children.push(node),
+            None => self.roots.push(node),
+        }
+    }
+
+    pub fn get_parent(&self, node: NodeId) -> Option<NodeId> {
+        self.get_node(node).parent
+    }
+
+    pub fn get_children(&self, node: NodeId) -> &[NodeId] {
+        &self.get_node(node).children
+    }
+
+    /// The nodes without a parent
+    pub fn get_roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// Whether the node is still in the scene
+    pub fn contains(&self, node: NodeId) -> bool {
+        self.nodes.get(node.0).is_some_and(|node| node.is_some())
+    }
+
+    /// Set the transform of the node relative to its parent
+    pub fn set_transform(&mut self, node: NodeId, transform: Transform) {
+        self.get_node_mut(node).transform = transform;
+    }
+
+    /// Get the transform of the node relative to its parent
+    pub fn get_transform(&self, node: NodeId) -> Transform {
+        self.get_node(node).transform
+    }
+
+    /// Get the transform of the node with all of its parents applied
+    pub fn get_world_transform(&self, node: NodeId) -> Transform {
+        let scene_node = self.get_node(node);
+        match scene_node.parent {
+            Some(parent) => scene_node
+                .transform
+                .add_parent(&self.get_world_transform(parent)),
+            None => scene_node.transform,
+        }
+    }
+
+    /// Hide or show the node, its children are hidden with it
+    pub fn set_visible(&mut self, node: NodeId, visible: bool) {
+        self.get_node_mut(node).visible = visible;
+    }
+
+    /// Whether the node itself is visible, it's still not drawn if one of its parents is hidden
+    pub fn is_visible(&self, node: NodeId) -> bool {
+        self.get_node(node).visible
+    }
+
+    /// Draw the drawable of the node on this layer instead of its own
+    pub fn set_layer(&mut self, node: NodeId, layer: &str) {
+        self.get_node_mut(node).layer = Some(layer.to_string());
+    }
+
+    /// The layer the node is drawn on, the one of its drawable if it wasn't set
+    pub fn get_layer(&self, node: NodeId) -> &str {
+        let scene_node = self.get_node(node);
+        match (&scene_node.layer, &scene_node.content) {
+            (Some(layer), _) => layer,
+            (None, NodeContent::Drawable(drawable)) => drawable.get_layer(),
+            (None, _) => crate::layers::DEFAULT_LAYER,
+        }
+    }
+
+    /// Set the z index of the node, it's used over the z index of its drawable
+    pub fn set_z_index(&mut self, node: NodeId, z_index: i32) {
+        self.get_node_mut(node).z_index = Some(z_index);
+    }
+
+    /// The z index of the node, the one of its drawable if it wasn't set
+    pub fn get_z_index(&self, node: NodeId) -> i32 {
+        let scene_node = self.get_node(node);
+        match (scene_node.z_index, &scene_node.content) {
+            (Some(z_index), _) => z_index,
+            (None, NodeContent::Drawable(drawable)) => drawable.get_z_index(),
+            (None, _) => 0,
+        }
+    }
+
+    /// Set the id pick returns for the drawable of the node, None to use the id of the drawable.
+    /// The id can be anything but u32::MAX, like with Pickable
+    pub fn set_pick_id(&mut self, node: NodeId, id: Option<u32>) {
+        if id == Some(u32::MAX) {
+            panic!("Pick id can't be u32::MAX");
+        }
+        self.get_node_mut(node).pick_id = id;
+    }
+
+    /// The pick id of the node, the one of its drawable if it wasn't set
+    pub fn get_pick_id(&self, node: NodeId) -> Option<u32> {
+        let scene_node = self.get_node(node);
+        match (scene_node.pick_id, &scene_node.content) {
+            (Some(id), _) => Some(id),
+            (None, NodeContent::Drawable(drawable)) => drawable.get_pick_id(),
+            (None, _) => None,
+        }
+    }
+
+    /// Get the drawable of the node, None if the node isn't a drawable
+    pub fn get_drawable(&self, node: NodeId) -> Option<&dyn Drawable> {
+        match &self.get_node(node).content {
+            NodeContent::Drawable(drawable) => Some(drawable.as_ref()),
+            _ => None,
+        }
+    }
+
+    pub fn get_drawable_mut(&mut self, node: NodeId) -> Option<&mut dyn Drawable> {
+        match &mut self.get_node_mut(node).content {
+            NodeContent::Drawable(drawable) => Some(drawable.as_mut()),
+            _ => None,
+        }
+    }
+
+    /// Get the light of the node, None if the node isn't a light
+    pub fn get_light(&self, node: NodeId) -> Option<&dyn LightDrawable> {
+        match &self.get_node(node).content {
+            NodeContent::Light(light) => Some(light.as_ref()),
+            _ => None,
+        }
+    }
+
+    pub fn get_light_mut(&mut self, node: NodeId) -> Option<&mut dyn LightDrawable> {
+        match &mut self.get_node_mut(node).content {
+            NodeContent::Light(light) => Some(light.as_mut()),
+            _ => None,
+        }
+    }
+
+    /// Draw every visible node with draw_all
+    pub fn draw(&self, program: &mut LumenpyxProgram, camera: &Camera) {
+        let (lights, drawables) = self.get_world_objects();
+
+        crate::draw_all(
+            lights
+                .iter()
+                .map(|light| light as &dyn LightDrawable)
+                .collect(),
+            drawables
+                .iter()
+                .map(|drawable| drawable as &dyn Drawable)
+                .collect(),
+            program,
+            camera,
+        );
+    }
+
+    /// the visible lights and drawables with the transforms of their nodes, parents before children
+    fn get_world_objects(&self) -> (Vec<WorldLight<'_>>, Vec<WorldDrawable<'_>>) {
+        let mut lights = vec![];
+        let mut drawables = vec![];
+
+        // depth first, the stack is reversed so the nodes are drawn in the order they were added
+        let mut stack: Vec<(NodeId, Transform)> = self
+            .roots
+            .iter()
+            .rev()
+            .map(|root| (*root, Transform::default()))
+            .collect();
+        while let Some((node, parent_transform)) = stack.pop() {
+            let scene_node = self.get_node(node);
+            if !scene_node.visible {
+                continue;
+            }
+
+            let node_transform = scene_node.transform.add_parent(&parent_transform);
+            match &scene_node.content {
+                NodeContent::Empty => (),
+                NodeContent::Drawable(drawable) => drawables.push(WorldDrawable {
+                    object: drawable.as_ref(),
+                    node_transform,
+                    layer: scene_node.layer.as_deref(),
+                    z_index: scene_node.z_index,
+                    pick_id: scene_node.pick_id,
+                }),
+                NodeContent::Light(light) => lights.push(WorldLight {
+                    light: light.as_ref(),
+                    node_transform,
+                }),
+            }
+
+            for child in scene_node.children.iter().rev() {
+                stack.push((*child, node_transform));
+            }
+        }
+
+        (lights, drawables)
+    }
+
+    fn insert_node(
+        &mut self,
+        parent: Option<NodeId>,
+        transform: Transform,
+        content: NodeContent,
+    ) -> NodeId {
+        let node = NodeId(self.nodes.len());
+        self.nodes.push(Some(SceneNode {
+            transform,
+            parent,
+            children: vec![],
+            content,
+            visible: true,
+            layer: None,
+            z_index: None,
+            pick_id: None,
+        }));
+
+        match parent {
+            Some(parent) => self.get_node_mut(parent).children.push(node),
+            None => self.roots.push(node),
+        }
+
+        node
+    }
+
+    /// take the node out of the children of its parent
+    fn detach(&mut self, node: NodeId, parent: Option<NodeId>) {
+        let siblings = match parent {
+            Some(parent) => &mut self.get_node_mut(parent).children,
+            None => &mut self.roots,
+        };
+        siblings.retain(|sibling| *sibling != node);
+    }
+
+    fn get_node(&self, node: NodeId) -> &SceneNode {
+        self.nodes[node.0]
+            .as_ref()
+            .expect("Failed to get node, it was removed from the scene")
+    }
+
+    fn get_node_mut(&mut self, node: NodeId) -> &mut SceneNode {
+        self.nodes[node.0]
+            .as_mut()
+            .expect("Failed to get node, it was removed from the scene")
+    }
+}
+
+/// a drawable of a scene drawn with the transform of its node
+struct WorldDrawable<'a> {
+    object: &'a dyn Drawable,
+    node_transform: Transform,
+    layer: Option<&'a str>,
+    z_index: Option<i32>,
+    pick_id: Option<u32>,
+}
+
+impl<'a> Drawable for WorldDrawable<'a> {
+    fn draw_albedo(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        albedo_framebuffer: &mut SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_albedo(program, transform, albedo_framebuffer);
+    }
+
+    fn draw_height(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        height_framebuffer: &mut SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_height(program, transform, height_framebuffer);
+    }
+
+    fn draw_roughness(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        roughness_framebuffer: &mut SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_roughness(program, transform, roughness_framebuffer);
+    }
+
+    fn draw_normal(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        normal_framebuffer: &mut SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_normal(program, transform, normal_framebuffer);
+    }
+
+    fn draw_albedo_overlay(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        albedo_framebuffer: &mut SimpleFrameBuffer,
+        scene_height: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    ) {
+        self.object
+            .draw_albedo_overlay(program, transform, albedo_framebuffer, scene_height);
+    }
+
+    fn draw_emissive(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        emissive_framebuffer: &mut SimpleFrameBuffer,
+    ) {
+        self.object
+            .draw_emissive(program, transform, emissive_framebuffer);
+    }
+
+    fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
+        self.object.try_load_shaders(program);
+    }
+
+    fn get_transform(&self) -> Transform {
+        self.object.get_transform().add_parent(&self.node_transform)
+    }
+
+    fn get_recieve_shadows_strength(&self) -> f32 {
+        self.object.get_recieve_shadows_strength()
+    }
+
+    fn get_pick_id(&self) -> Option<u32> {
+        self.pick_id.or_else(|| self.object.get_pick_id())
+    }
+
+    fn get_layer(&self) -> &str {
+        self.layer.unwrap_or_else(|| self.object.get_layer())
+    }
+
+    fn get_z_index(&self) -> i32 {
+        self.z_index.unwrap_or_else(|| self.object.get_z_index())
+    }
+
+    // the transforms are changed through the nodes of the scene
+    fn set_transform(&mut self, _transform: Transform) {}
+}
+
+/// a light of a scene drawn with the transform of its node
+struct WorldLight<'a> {
+    light: &'a dyn LightDrawable,
+    node_transform: Transform,
+}
+
+impl<'a> LightDrawable for WorldLight<'a> {
+    fn draw(
+        &self,
+        program: &LumenpyxProgram,
+        matrix_transform: [[f32; 4]; 4],
+        albedo_framebuffer: &mut SimpleFrameBuffer,
+        height_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+        albedo_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+        roughness_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+        shadow_strength_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    ) {
+        self.light.draw(
+            program,
+            matrix_transform,
+            albedo_framebuffer,
+            height_uniform,
+            albedo_uniform,
+            roughness_uniform,
+            shadow_strength_uniform,
+        );
+    }
+
+    fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
+        self.light.try_load_shaders(program);
+    }
+
+    fn get_transform(&self) -> Transform {
+        self.light.get_transform().add_parent(&self.node_transform)
+    }
+
+    // the transforms are changed through the nodes of the scene
+    fn set_transform(&mut self, _transform: Transform) {}
+
+    fn get_light_intensity(&self) -> f32 {
+        self.light.get_light_intensity()
+    }
+
+    // the light is borrowed from the scene, so it's changed there
+    fn set_light_intensity(&mut self, _intensity: f32) {}
+
+    fn get_light_color(&self) -> [f32; 3] {
+        self.light.get_light_color()
+    }
+
+    fn set_light_color(&mut self, _color: [f32; 3]) {}
+
+    fn update_light(&self) {
+        self.light.update_light();
+    }
+
+    fn get_light_radius(&self) -> Option<f32> {
+        self.light.get_light_radius()
+    }
+
+    fn get_layer_mask(&self) -> u32 {
+        self.light.get_layer_mask()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lights::PointLight;
+    use crate::primitives::Sprite;
+
+    fn sprite() -> Sprite {
+        Sprite::new_untextured(Transform::default())
+    }
+
+    fn light(z: f32) -> PointLight {
+        PointLight::new([0.0, 0.0, z], [1.0, 1.0, 1.0], 1.0, 0.1)
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn remove_node_removes_children() {
+        let mut scene = Scene::new();
+        let root = scene.add_node(None, Transform::default());
+        let child = scene.add_drawable(Some(root), Transform::default(), sprite());
+        let grandchild = scene.add_light(Some(child), Transform::default(), light(0.0));
+        let other = scene.add_node(None, Transform::default());
+
+        scene.remove_node(root);
+
+        assert!(!scene.contains(root));
+        assert!(!scene.contains(child));
+        assert!(!scene.contains(grandchild));
+        assert!(scene.contains(other));
+        assert_eq!(scene.get_roots(), &[other]);
+
+        // the ids of the nodes left don't change
+        let new_node = scene.add_node(Some(other), Transform::default());
+        assert_ne!(new_node, root);
+        assert_eq!(scene.get_children(other), &[new_node]);
+    }
+
+    #[test]
+    fn remove_child_detaches_it() {
+        let mut scene = Scene::new();
+        let root = scene.add_node(None, Transform::default());
+        let first = scene.add_node(Some(root), Transform::default());
+        let second = scene.add_node(Some(root), Transform::default());
+
+        scene.remove_node(first);
+
+        assert!(scene.contains(root));
+        assert_eq!(scene.get_children(root), &[second]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn set_parent_rejects_itself() {
+        let mut scene = Scene::new();
+        let node = scene.add_node(None, Transform::default());
+        scene.set_parent(node, Some(node));
+    }
+
+    #[test]
+    #[should_panic]
+    fn set_parent_rejects_its_children() {
+        let mut scene = Scene::new();
+        let root = scene.add_node(None, Transform::default());
+        let child = scene.add_node(Some(root), Transform::default());
+        let grandchild = scene.add_node(Some(child), Transform::default());
+        scene.set_parent(root, Some(grandchild));
+    }
+
+    #[test]
+    fn set_parent_moves_the_node() {
+        let mut scene = Scene::new();
+        let first = scene.add_node(None, Transform::default());
+        let second = scene.add_node(None, Transform::default());
+        let child = scene.add_node(Some(first), Transform::default());
+
+        scene.set_parent(child, Some(second));
+        assert_eq!(scene.get_parent(child), Some(second));
+        assert!(scene.get_children(first).is_empty());
+        assert_eq!(scene.get_children(second), &[child]);
+
+        scene.set_parent(child, None);
+        assert_eq!(scene.get_parent(child), None);
+        assert_eq!(scene.get_roots(), &[first, second, child]);
+    }
+
+    #[test]
+    fn world_transform_under_rotated_parent() {
+        let mut scene = Scene::new();
+        let mut parent_transform = Transform::new([10.0, 0.0, 1.0]);
+        parent_transform.set_rotation(std::f32::consts::FRAC_PI_2);
+        parent_transform.set_scale(2.0, 2.0, 2.0);
+        let parent = scene.add_node(None, parent_transform);
+        let child = scene.add_node(Some(parent), Transform::new([1.0, 0.0, 2.0]));
+
+        // the rotation is clockwise, so (1, 0) ends up below the parent
+        let world = scene.get_world_transform(child);
+        assert_close(world.get_x(), 10.0);
+        assert_close(world.get_y(), -2.0);
+        assert_close(world.get_z(), 5.0);
+        assert_close(world.get_rotation(), std::f32::consts::FRAC_PI_2);
+        assert_close(world.get_scale()[0], 2.0);
+
+        // the drawn objects get the same transform
+        let light_node = scene.add_light(Some(child), Transform::default(), light(0.0));
+        let (lights, _) = scene.get_world_objects();
+        let light_transform = lights[0].get_transform();
+        let node_transform = scene.get_world_transform(light_node);
+        assert_close(light_transform.get_x(), node_transform.get_x());
+        assert_close(light_transform.get_y(), node_transform.get_y());
+    }
+
+    #[test]
+    fn lights_get_the_height_of_their_node() {
+        let mut scene = Scene::new();
+        let parent = scene.add_node(None, Transform::new([0.0, 0.0, 3.0]));
+        scene.add_light(Some(parent), Transform::default(), light(0.5));
+
+        let (lights, _) = scene.get_world_objects();
+        let transform = lights[0].get_transform();
+        assert_close(transform.get_z(), 3.5);
+        assert_close(crate::lights::get_light_matrix(&transform)[3][2], 3.5);
+    }
+
+    #[test]
+    fn hidden_subtrees_are_not_drawn() {
+        let mut scene = Scene::new();
+        let root = scene.add_node(None, Transform::default());
+        let hidden = scene.add_node(Some(root), Transform::default());
+        scene.add_drawable(Some(hidden), Transform::default(), sprite());
+        scene.add_light(Some(hidden), Transform::default(), light(0.0));
+        scene.add_drawable(Some(root), Transform::default(), sprite());
+        scene.add_light(None, Transform::default(), light(0.0));
+
+        let (lights, drawables) = scene.get_world_objects();
+        assert_eq!((lights.len(), drawables.len()), (2, 2));
+
+        scene.set_visible(hidden, false);
+        assert!(!scene.is_visible(hidden));
+        let (lights, drawables) = scene.get_world_objects();
+        assert_eq!((lights.len(), drawables.len()), (1, 1));
+
+        scene.set_visible(root, false);
+        let (lights, drawables) = scene.get_world_objects();
+        assert_eq!((lights.len(), drawables.len()), (1, 0));
+    }
+
+    #[test]
+    fn nodes_override_layer_z_index_and_pick_id() {
+        let mut scene = Scene::new();
+        let node = scene.add_drawable(None, Transform::default(), sprite());
+        assert_eq!(scene.get_layer(node), crate::layers::DEFAULT_LAYER);
+        assert_eq!(scene.get_z_index(node), 0);
+        assert_eq!(scene.get_pick_id(node), None);
+
+        scene.set_layer(node, "ui");
+        scene.set_z_index(node, 3);
+        scene.set_pick_id(node, Some(7));
+        assert_eq!(scene.get_layer(node), "ui");
+        assert_eq!(scene.get_z_index(node), 3);
+        assert_eq!(scene.get_pick_id(node), Some(7));
+
+        let (_, drawables) = scene.get_world_objects();
+        assert_eq!(drawables[0].get_layer(), "ui");
+        assert_eq!(drawables[0].get_z_index(), 3);
+        assert_eq!(drawables[0].get_pick_id(), Some(7));
+    }
+
+    #[test]
+    #[should_panic]
+    fn set_pick_id_rejects_max_id() {
+        let mut scene = Scene::new();
+        let node = scene.add_drawable(None, Transform::default(), sprite());
+        scene.set_pick_id(node, Some(u32::MAX));
+    }
+}
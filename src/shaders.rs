@@ -34,6 +34,12 @@ pub(crate) const RECIEVE_SHADOWS_VERTEX_SHADER_SRC: &str =
 pub(crate) const RECIEVE_SHADOWS_FRAGMENT_SHADER_SRC: &str =
     include_str!("../shaders/technical_shaders/set_recieve_shadows.frag");
 
+pub(crate) const CHANGED_ID_VERTEX_SHADER_SRC: &str =
+    include_str!("../shaders/technical_shaders/set_changed_id.vert");
+
+pub(crate) const CHANGED_ID_FRAGMENT_SHADER_SRC: &str =
+    include_str!("../shaders/technical_shaders/set_changed_id.frag");
+
 pub(crate) const FILL_ALPHA_VERTEX_SHADER_SRC: &str =
     include_str!("../shaders/technical_shaders/fill_alpha.vert");
 
@@ -140,14 +146,14 @@ pub(crate) fn draw_crop_centered(
         .expect("Failed to draw to the framebuffer");
 }
 
-/// upscale the result to the screen size
+/// upscale the result to the screen size, the image is moved by the offset in image pixels
 pub(crate) fn draw_upscale(
     image_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
     lumenpyx_program: &LumenpyxProgram,
     window_resolution: [u32; 2],
+    offset: [f32; 2],
 ) {
     let display = &lumenpyx_program.display;
-    let indices = &lumenpyx_program.indices;
 
     let texture = glium::texture::Texture2d::empty_with_format(
         display,
@@ -182,16 +188,32 @@ pub(crate) fn draw_upscale(
         }
     };
 
-    let upscale_shader = &lumenpyx_program
-        .get_shader("upscale_shader")
-        .expect("Failed to load upscale shader");
-
     let mut target = display.draw();
-    let dimensions = target.get_dimensions();
+    target.clear_color(0.0, 0.0, 0.0, 0.0);
+
+    let scales = get_letterbox_scales(target.get_dimensions(), window_resolution);
+    draw_image_to_rect(
+        &mut target,
+        new_uniform,
+        lumenpyx_program,
+        [-scales[0], -scales[1], scales[0], scales[1]],
+        offset,
+    );
 
+    target
+        .finish()
+        .expect("Failed to finish drawing to the screen");
+}
+
+/// how much of the target in each direction an image of the resolution covers,
+/// with black bars so it keeps its aspect ratio
+pub(crate) fn get_letterbox_scales(
+    target_dimensions: (u32, u32),
+    resolution: [u32; 2],
+) -> [f32; 2] {
     // figure out which dimensions need the black bars
-    let [target_width, target_height] = [dimensions.0 as f32, dimensions.1 as f32];
-    let [image_width, image_height] = [window_resolution[0] as f32, window_resolution[1] as f32];
+    let [target_width, target_height] = [target_dimensions.0 as f32, target_dimensions.1 as f32];
+    let [image_width, image_height] = [resolution[0] as f32, resolution[1] as f32];
 
     let mut dim_scales = [image_width / target_width, image_height / target_height];
 
@@ -204,29 +226,49 @@ pub(crate) fn draw_upscale(
         dim_scales[1] = 1.0;
     }
 
+    dim_scales
+}
+
+/// draw the image stretched over the rect of the target, the rect is left, bottom, right, top in -1 to 1.
+/// The image is moved by the offset in image pixels
+pub(crate) fn draw_image_to_rect(
+    target: &mut impl Surface,
+    image_uniform: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    lumenpyx_program: &LumenpyxProgram,
+    rect: [f32; 4],
+    offset: [f32; 2],
+) {
+    let display = &lumenpyx_program.display;
+    let indices = &lumenpyx_program.indices;
+
+    let upscale_shader = &lumenpyx_program
+        .get_shader("upscale_shader")
+        .expect("Failed to load upscale shader");
+
+    let [left, bottom, right, top] = rect;
     let shape = vec![
         Vertex {
-            position: [-1.0 * dim_scales[0], -1.0 * dim_scales[1]],
+            position: [left, bottom],
             tex_coords: [0.0, 0.0],
         },
         Vertex {
-            position: [1.0 * dim_scales[0], -1.0 * dim_scales[1]],
+            position: [right, bottom],
             tex_coords: [1.0, 0.0],
         },
         Vertex {
-            position: [1.0 * dim_scales[0], 1.0 * dim_scales[1]],
+            position: [right, top],
             tex_coords: [1.0, 1.0],
         },
         Vertex {
-            position: [1.0 * dim_scales[0], 1.0 * dim_scales[1]],
+            position: [right, top],
             tex_coords: [1.0, 1.0],
         },
         Vertex {
-            position: [-1.0 * dim_scales[0], 1.0 * dim_scales[1]],
+            position: [left, top],
             tex_coords: [0.0, 1.0],
         },
         Vertex {
-            position: [-1.0 * dim_scales[0], -1.0 * dim_scales[1]],
+            position: [left, bottom],
             tex_coords: [0.0, 0.0],
         },
     ];
@@ -235,10 +277,10 @@ pub(crate) fn draw_upscale(
         glium::VertexBuffer::new(display, &shape).expect("Failed to create vertex buffer");
 
     let uniforms = &uniform! {
-        image: new_uniform,
+        image: image_uniform,
+        offset: offset,
     };
 
-    target.clear_color(0.0, 0.0, 0.0, 0.0);
     target
         .draw(
             &vertex_buffer,
@@ -248,10 +290,6 @@ pub(crate) fn draw_upscale(
             &Default::default(),
         )
         .expect("Failed to draw to the screen");
-
-    target
-        .finish()
-        .expect("Failed to finish drawing to the screen");
 }
 
 pub(crate) fn draw_reflections(
@@ -267,8 +305,7 @@ pub(crate) fn draw_reflections(
     let indices = &program.indices;
 
     let reflection_texture = program
-        .cache
-        .get_texture("reflection_texture")
+        .get_pipeline_texture("reflection_texture")
         .expect("Failed to get reflection texture");
 
     let mut reflection_framebuffer =
@@ -565,6 +602,45 @@ pub(crate) fn draw_recieve_shadows(
         .expect("Failed to draw to the framebuffer");
 }
 
+/// write the id as 4 bytes everywhere the last drawable changed the albedo, used for the pick ids and the light layers.
+/// Like the shadow strength, it only knows where the albedo changed, so pixels where a drawable
+/// is drawn over the exact same color or is fully transparent keep the id of what is behind them
+pub(crate) fn draw_changed_id(
+    framebuffer: &mut SimpleFrameBuffer,
+    program: &LumenpyxProgram,
+    id: u32,
+    last_frame_sampler: glium::uniforms::Sampler<glium::texture::Texture2d>,
+    this_frame_sampler: glium::uniforms::Sampler<glium::texture::Texture2d>,
+) {
+    let display = &program.display;
+    let indices = &program.indices;
+    let shader = &program
+        .get_shader("changed_id_shader")
+        .expect("Failed to load shader");
+
+    let shape = FULL_SCREEN_QUAD;
+
+    let vertex_buffer =
+        glium::VertexBuffer::new(display, &shape).expect("Failed to create vertex buffer");
+
+    let bytes = id.to_le_bytes();
+    let uniforms = &uniform! {
+        last_draw: last_frame_sampler,
+        this_draw: this_frame_sampler,
+        id: bytes.map(|byte| byte as f32 / 255.0),
+    };
+
+    framebuffer
+        .draw(
+            &vertex_buffer,
+            indices,
+            shader,
+            uniforms,
+            &Default::default(),
+        )
+        .expect("Failed to draw to the framebuffer");
+}
+
 pub(crate) fn load_all_system_shaders(program: &mut LumenpyxProgram) {
     {
         let display = &program.display;
@@ -579,6 +655,19 @@ pub(crate) fn load_all_system_shaders(program: &mut LumenpyxProgram) {
         program.add_shader(set_recieve_shadows_shader, "recieve_shadows_shader");
     }
 
+    {
+        let display = &program.display;
+        let changed_id_shader = glium::Program::from_source(
+            display,
+            CHANGED_ID_VERTEX_SHADER_SRC,
+            CHANGED_ID_FRAGMENT_SHADER_SRC,
+            None,
+        )
+        .expect("Failed to load changed id shader");
+
+        program.add_shader(changed_id_shader, "changed_id_shader");
+    }
+
     {
         let display = &program.display;
         let sprite_shader = glium::Program::from_source(
@@ -12,7 +12,9 @@ use swash::zeno::{self, Vector};
 use swash::FontRef;
 use zeno::Format;
 
-use crate::primitives::{draw_texture, BASE_FRAGMENT_SHADER_SRC, BASE_VERTEX_SHADER_SRC};
+use crate::primitives::{
+    draw_texture, SpriteDrawSettings, BASE_FRAGMENT_SHADER_SRC, BASE_VERTEX_SHADER_SRC,
+};
 use crate::{Drawable, TextureHandle};
 
 pub use parley::fontique::Collection;
@@ -40,6 +42,10 @@ pub struct TextBox<'a> {
     roughness: f32,
     /// The normal that will be used in the draw function
     normal: [f32; 3],
+    /// multiplied with the albedo when drawing, doesn't require the textures to be redrawn
+    tint: [f32; 4],
+    opacity: f32,
+    shadow_strength: f32,
 }
 
 impl<'a> TextBox<'a> {
@@ -86,6 +92,9 @@ impl<'a> TextBox<'a> {
             height: 0.0,
             roughness: 0.0,
             normal: [0.0, 0.0, 0.0],
+            tint: [1.0, 1.0, 1.0, 1.0],
+            opacity: 1.0,
+            shadow_strength: 0.5,
         };
 
         // because we didn't draw the height, roughness, or normal properly
@@ -107,10 +116,11 @@ impl<'a> TextBox<'a> {
         roughness_data.text_color = [roughness, roughness, roughness, 255];
         let roughness_sprite = remake_text_box(&mut roughness_data, &self.font, lumenpyx_program);
 
+        // stored as normal * 0.5 + 0.5 like a normal map image
         let normal = self
             .normal
             .iter()
-            .map(|x| (x * 255.0) as u8)
+            .map(|x| ((x * 0.5 + 0.5) * 255.0) as u8)
             .collect::<Vec<u8>>();
         let mut normal_data = self.data.clone();
         normal_data.text_color = [normal[0], normal[1], normal[2], 255];
@@ -256,6 +266,47 @@ impl<'a> TextBox<'a> {
     pub fn set_normal(&mut self, normal: [f32; 3]) {
         self.normal = normal;
     }
+
+    /// Set the color the text is multiplied by, [1.0, 1.0, 1.0, 1.0] leaves the text unchanged
+    pub fn set_tint(&mut self, tint: [f32; 4]) {
+        self.tint = tint;
+    }
+
+    pub fn get_tint(&self) -> [f32; 4] {
+        self.tint
+    }
+
+    /// Set the opacity of the text, 0.0 is invisible and 1.0 is fully visible.
+    /// The shadow strength is scaled by the opacity as well
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity;
+    }
+
+    pub fn get_opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    pub fn set_shadow_strength(&mut self, shadow_strength: f32) {
+        self.shadow_strength = shadow_strength;
+    }
+
+    pub fn get_shadow_strength(&self) -> f32 {
+        self.shadow_strength
+    }
+
+    /// the tint is only applied to the albedo, the opacity is applied to every texture
+    fn get_draw_settings(&self, is_albedo: bool) -> SpriteDrawSettings {
+        SpriteDrawSettings {
+            is_albedo,
+            tint: if is_albedo {
+                self.tint
+            } else {
+                [1.0, 1.0, 1.0, 1.0]
+            },
+            opacity: self.opacity,
+            ..Default::default()
+        }
+    }
 }
 
 impl<'a> Drawable for TextBox<'a> {
@@ -294,6 +345,7 @@ impl<'a> Drawable for TextBox<'a> {
             transform.get_matrix(),
             program,
             albedo_framebuffer,
+            &self.get_draw_settings(true),
         );
     }
 
@@ -332,6 +384,7 @@ impl<'a> Drawable for TextBox<'a> {
             transform.get_matrix(),
             program,
             normal_framebuffer,
+            &self.get_draw_settings(false),
         );
     }
 
@@ -370,6 +423,7 @@ impl<'a> Drawable for TextBox<'a> {
             transform.get_matrix(),
             program,
             height_framebuffer,
+            &self.get_draw_settings(false),
         );
     }
 
@@ -408,6 +462,7 @@ impl<'a> Drawable for TextBox<'a> {
             transform.get_matrix(),
             program,
             roughness_framebuffer,
+            &self.get_draw_settings(false),
         );
     }
 
@@ -431,6 +486,10 @@ impl<'a> Drawable for TextBox<'a> {
         self.transform
     }
 
+    fn get_recieve_shadows_strength(&self) -> f32 {
+        self.shadow_strength * self.opacity
+    }
+
     fn set_transform(&mut self, transform: crate::Transform) {
         self.transform = transform;
     }
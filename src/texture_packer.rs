@@ -0,0 +1,454 @@
+use std::path::Path;
+use std::time::Duration;
+
+use image::RgbaImage;
+use rustc_hash::FxHashMap;
+use serde_json::Value;
+
+use crate::animation::Animation;
+use crate::primitives::{Normal, NormalInput, Sprite, Texture, TextureInput};
+use crate::LumenpyxProgram;
+use crate::TextureHandle;
+use crate::Transform;
+
+/// One frame of a packed sheet, with the trimming undone
+struct PackedFrame {
+    /// straight rgba in bytes with the top row first
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+    /// [0.0, 0.0] is the bottom left of the frame
+    pivot: Option<[f32; 2]>,
+    duration: Option<Duration>,
+}
+
+/// Sprites loaded from sheets packed by TexturePacker or anything else that writes the same json (hash or array).
+/// Frames can be trimmed, rotated and have pivots, and every frame becomes a sprite with the name of the frame.
+///
+/// Frames named like `walk_01.png`, `walk_02.png` are also put together into an animation named `walk`
+pub struct PackedSheet {
+    sprites: FxHashMap<String, Sprite>,
+    /// the frame names in the order they are in the json
+    frame_names: Vec<String>,
+    durations: FxHashMap<String, Duration>,
+    handles: Vec<TextureHandle>,
+}
+
+impl PackedSheet {
+    /// Load the json of each channel, the png is found from the image in the meta of the json.
+    /// The frames of the other channels are matched to the albedo by name.
+    /// A missing height is 0.0, a missing roughness is 1.0 and a missing normal is generated from the height.
+    /// Panics if a frame goes outside of its sheet
+    pub fn load(
+        albedo: &str,
+        height: Option<&str>,
+        roughness: Option<&str>,
+        normal: Option<&str>,
+        transform: Transform,
+        program: &mut LumenpyxProgram,
+    ) -> Self {
+        let albedo_frames = read_packed_sheet(Path::new(albedo));
+        let mut height_frames = height
+            .map(|path| read_packed_sheet(Path::new(path)))
+            .unwrap_or_default();
+        let mut roughness_frames = roughness
+            .map(|path| read_packed_sheet(Path::new(path)))
+            .unwrap_or_default();
+        let mut normal_frames = normal
+            .map(|path| read_packed_sheet(Path::new(path)))
+            .unwrap_or_default();
+
+        let mut sprites = FxHashMap::default();
+        let mut frame_names = vec![];
+        let mut durations = FxHashMap::default();
+        let mut handles = vec![];
+        for (name, frame) in albedo_frames {
+            let size = (frame.width, frame.height);
+            let to_texture = |frame: &PackedFrame| {
+                if (frame.width, frame.height) != size {
+                    panic!(
+                        "Every channel of the packed frame {} must be the same size",
+                        name
+                    );
+                }
+                let image = glium::texture::RawImage2d::from_raw_rgba_reversed(&frame.pixels, size);
+                glium::texture::Texture2d::new(&program.display, image)
+                    .expect("Failed to create texture from packed frame")
+            };
+
+            let channel = |frames: &mut Vec<(String, PackedFrame)>, default: f32| -> TextureInput {
+                match take_frame(frames, &name) {
+                    Some(frame) => Texture::Texture(to_texture(&frame)).into(),
+                    None => default.into(),
+                }
+            };
+
+            let albedo_input = Texture::Texture(to_texture(&frame)).into();
+            let height_input = channel(&mut height_frames, 0.0);
+            let roughness_input = channel(&mut roughness_frames, 1.0);
+            let normal_input = match take_frame(&mut normal_frames, &name) {
+                Some(normal) => NormalInput::Normal(Normal::Texture(to_texture(&normal))),
+                None => NormalInput::Normal(Normal::AutoGenerated),
+            };
+
+            let (mut sprite, albedo_handle, height_handle, roughness_handle, normal_handle) =
+                Sprite::new(
+                    albedo_input,
+                    height_input,
+                    roughness_input,
+                    normal_input,
+                    program,
+                    transform,
+                );
+            if let Some(pivot) = frame.pivot {
+                sprite.set_pivot(pivot);
+            }
+
+            handles.extend([
+                albedo_handle,
+                height_handle,
+                roughness_handle,
+                normal_handle,
+            ]);
+            if let Some(duration) = frame.duration {
+                durations.insert(name.clone(), duration);
+            }
+            sprites.insert(name.clone(), sprite);
+            frame_names.push(name);
+        }
+
+        Self {
+            sprites,
+            frame_names,
+            durations,
+            handles,
+        }
+    }
+
+    /// Get the sprite of the frame with this name, e.g. `walk_01.png`
+    pub fn get_sprite(&self, name: &str) -> Option<Sprite> {
+        self.sprites.get(name).copied()
+    }
+
+    /// The names of every frame in the order they were in the json
+    pub fn get_frame_names(&self) -> &[String] {
+        &self.frame_names
+    }
+
+    /// The names of the animations made from the frame names, in the order they first show up
+    pub fn get_animation_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        for frame_name in &self.frame_names {
+            if let Some((name, _)) = split_frame_name(frame_name) {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// Make an animation out of every frame named `name` followed by a number, in the order of the numbers.
+    /// Frames with a duration in the json use it, the rest are shown for time_between_frames
+    pub fn get_animation(
+        &self,
+        name: &str,
+        time_between_frames: Duration,
+        transform: Transform,
+        program: &LumenpyxProgram,
+        loop_animation: bool,
+    ) -> Option<Animation> {
+        let mut frames: Vec<(u64, &String)> = self
+            .frame_names
+            .iter()
+            .filter_map(|frame_name| match split_frame_name(frame_name) {
+                Some((animation_name, number)) if animation_name == name => {
+                    Some((number, frame_name))
+                }
+                _ => None,
+            })
+            .collect();
+        if frames.is_empty() {
+            return None;
+        }
+        frames.sort_by_key(|(number, _)| *number);
+
+        let sprites = frames
+            .iter()
+            .map(|(_, frame_name)| self.sprites[*frame_name])
+            .collect();
+        let durations = frames
+            .iter()
+            .map(|(_, frame_name)| {
+                self.durations
+                    .get(*frame_name)
+                    .copied()
+                    .unwrap_or(time_between_frames)
+            })
+            .collect();
+
+        Some(Animation::new_from_sprites(
+            sprites,
+            durations,
+            transform,
+            loop_animation,
+            program.get_clock(),
+        ))
+    }
+
+    /// Remove the textures of every frame from the program, the sprites can't be drawn after this
+    pub fn free_all_textures(&self, program: &mut LumenpyxProgram) {
+        for handle in &self.handles {
+            program.remove_texture(handle);
+        }
+    }
+}
+
+/// take the frame with the name out of the list of frames of a channel
+fn take_frame(frames: &mut Vec<(String, PackedFrame)>, name: &str) -> Option<PackedFrame> {
+    let index = frames
+        .iter()
+        .position(|(frame_name, _)| frame_name == name)?;
+    Some(frames.remove(index).1)
+}
+
+/// splits `walk_01.png` into `walk` and 1, None if the name doesn't end in a number
+fn split_frame_name(frame_name: &str) -> Option<(String, u64)> {
+    let stem = match frame_name.rfind('.') {
+        Some(dot) => &frame_name[..dot],
+        None => frame_name,
+    };
+    let name = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+    let number = stem[name.len()..].parse().ok()?;
+    let name = name.trim_end_matches(['_', '-', ' ']);
+    Some((name.to_string(), number))
+}
+
+fn read_packed_sheet(path: &Path) -> Vec<(String, PackedFrame)> {
+    let text = std::fs::read_to_string(path).unwrap_or_else(|_| {
+        panic!(
+            "Failed to read packed sheet json {}",
+            path.to_string_lossy()
+        )
+    });
+    let json: Value = serde_json::from_str(&text).expect("Failed to parse packed sheet json");
+
+    let directory = path
+        .parent()
+        .map(|parent| parent.to_path_buf())
+        .unwrap_or_default();
+    let image_path = directory.join(
+        json["meta"]["image"]
+            .as_str()
+            .expect("Packed sheet json is missing the image"),
+    );
+    let sheet = image::open(&image_path)
+        .unwrap_or_else(|_| {
+            panic!(
+                "Failed to load image at path {}",
+                image_path.to_string_lossy()
+            )
+        })
+        .to_rgba8();
+
+    get_frame_entries(&json)
+        .into_iter()
+        .map(|(name, frame)| {
+            let (pixels, width, height) = read_packed_frame(&sheet, frame).unwrap_or_else(|| {
+                panic!(
+                    "Packed frame {} is outside of the sheet {}",
+                    name,
+                    image_path.to_string_lossy()
+                )
+            });
+            let pivot = frame["pivot"].as_object().map(|pivot| {
+                let x = pivot.get("x").and_then(Value::as_f64).unwrap_or(0.5) as f32;
+                let y = pivot.get("y").and_then(Value::as_f64).unwrap_or(0.5) as f32;
+                // the json has the top left as 0, 0
+                [x, 1.0 - y]
+            });
+            let duration = frame["duration"].as_u64().map(Duration::from_millis);
+
+            (
+                name,
+                PackedFrame {
+                    pixels,
+                    width,
+                    height,
+                    pivot,
+                    duration,
+                },
+            )
+        })
+        .collect()
+}
+
+/// the frames are either an array with filenames or a map of filename to frame
+pub(crate) fn get_frame_entries(json: &Value) -> Vec<(String, &Value)> {
+    match &json["frames"] {
+        Value::Array(frames) => frames
+            .iter()
+            .map(|frame| {
+                (
+                    frame["filename"].as_str().unwrap_or_default().to_string(),
+                    frame,
+                )
+            })
+            .collect(),
+        Value::Object(frames) => frames
+            .iter()
+            .map(|(filename, frame)| (filename.clone(), frame))
+            .collect(),
+        _ => panic!("Packed sheet json is missing the frames"),
+    }
+}
+
+/// Cut one frame out of the sheet, trimmed frames are put back where they were in the full frame
+/// and rotated frames are turned back. Returns the rgba bytes with the top row first, the width and the height,
+/// or None if the frame goes outside of the sheet
+pub(crate) fn read_packed_frame(sheet: &RgbaImage, frame: &Value) -> Option<(Vec<u8>, u32, u32)> {
+    let number = |value: &Value| value.as_u64().unwrap_or(0) as u32;
+
+    let rect = &frame["frame"];
+    let placement = &frame["spriteSourceSize"];
+    let (frame_x, frame_y) = (number(&rect["x"]), number(&rect["y"]));
+    // the size of the frame before it was rotated
+    let (frame_width, frame_height) = (number(&rect["w"]), number(&rect["h"]));
+    let rotated = frame["rotated"].as_bool() == Some(true);
+
+    // rotated frames take up the height of the frame across the sheet
+    let (sheet_width, sheet_height) = if rotated {
+        (frame_height, frame_width)
+    } else {
+        (frame_width, frame_height)
+    };
+    if frame_x as u64 + sheet_width as u64 > sheet.width() as u64
+        || frame_y as u64 + sheet_height as u64 > sheet.height() as u64
+    {
+        return None;
+    }
+
+    let (width, height) = match frame["sourceSize"].as_object() {
+        Some(_) => (
+            number(&frame["sourceSize"]["w"]),
+            number(&frame["sourceSize"]["h"]),
+        ),
+        None => (frame_width, frame_height),
+    };
+    let (offset_x, offset_y) = (number(&placement["x"]), number(&placement["y"]));
+
+    let mut pixels = vec![0u8; (width * height * 4) as usize];
+    for y in 0..frame_height {
+        for x in 0..frame_width {
+            let (target_x, target_y) = (offset_x + x, offset_y + y);
+            if target_x >= width || target_y >= height {
+                continue;
+            }
+
+            // rotated frames are turned 90 degrees clockwise in the sheet
+            let (sheet_x, sheet_y) = if rotated {
+                (frame_x + frame_height - 1 - y, frame_y + x)
+            } else {
+                (frame_x + x, frame_y + y)
+            };
+            let pixel = sheet.get_pixel(sheet_x, sheet_y).0;
+            let index = ((target_y * width + target_x) * 4) as usize;
+            pixels[index..index + 4].copy_from_slice(&pixel);
+        }
+    }
+
+    Some((pixels, width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn pixel(value: u8) -> [u8; 4] {
+        [value, value, value, 255]
+    }
+
+    /// a sheet with the pixels in rows, the top row first
+    fn sheet(rows: &[&[u8]]) -> RgbaImage {
+        RgbaImage::from_fn(rows[0].len() as u32, rows.len() as u32, |x, y| {
+            image::Rgba(pixel(rows[y as usize][x as usize]))
+        })
+    }
+
+    fn pixels(rows: &[&[u8]]) -> Vec<u8> {
+        rows.iter()
+            .flat_map(|row| row.iter())
+            .flat_map(|value| match value {
+                0 => [0; 4],
+                value => pixel(*value),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn splits_frame_names() {
+        assert_eq!(
+            split_frame_name("walk_01.png"),
+            Some(("walk".to_string(), 1))
+        );
+        assert_eq!(split_frame_name("run-12"), Some(("run".to_string(), 12)));
+        assert_eq!(
+            split_frame_name("jump 3.png"),
+            Some(("jump".to_string(), 3))
+        );
+        assert_eq!(split_frame_name("idle.png"), None);
+    }
+
+    #[test]
+    fn puts_trimmed_frames_back() {
+        let sheet = sheet(&[&[1, 2, 3], &[4, 5, 6]]);
+        // the 2x1 pixels at (1, 1) are the bottom middle of a 4x3 frame
+        let frame = json!({
+            "frame": { "x": 1, "y": 1, "w": 2, "h": 1 },
+            "rotated": false,
+            "trimmed": true,
+            "spriteSourceSize": { "x": 1, "y": 2, "w": 2, "h": 1 },
+            "sourceSize": { "w": 4, "h": 3 },
+        });
+
+        let (frame_pixels, width, height) = read_packed_frame(&sheet, &frame).unwrap();
+        assert_eq!((width, height), (4, 3));
+        assert_eq!(
+            frame_pixels,
+            pixels(&[&[0, 0, 0, 0], &[0, 0, 0, 0], &[0, 5, 6, 0]])
+        );
+    }
+
+    #[test]
+    fn turns_rotated_frames_back() {
+        // a 2x3 frame turned 90 degrees clockwise is 3x2 in the sheet
+        let sheet = sheet(&[&[9, 5, 3, 1], &[9, 6, 4, 2]]);
+        let frame = json!({
+            "frame": { "x": 1, "y": 0, "w": 2, "h": 3 },
+            "rotated": true,
+        });
+
+        let (frame_pixels, width, height) = read_packed_frame(&sheet, &frame).unwrap();
+        assert_eq!((width, height), (2, 3));
+        assert_eq!(frame_pixels, pixels(&[&[1, 2], &[3, 4], &[5, 6]]));
+    }
+
+    #[test]
+    fn frames_outside_of_the_sheet_are_none() {
+        let sheet = sheet(&[&[1, 2], &[3, 4]]);
+        let frame = |x: u32, w: u32, h: u32, rotated: bool| {
+            json!({
+                "frame": { "x": x, "y": 0, "w": w, "h": h },
+                "rotated": rotated,
+            })
+        };
+
+        assert!(read_packed_frame(&sheet, &frame(0, 2, 2, false)).is_some());
+        assert!(read_packed_frame(&sheet, &frame(1, 2, 2, false)).is_none());
+        // 1 wide and 3 tall is 3 wide in the sheet when it is rotated
+        assert!(read_packed_frame(&sheet, &frame(0, 1, 3, true)).is_none());
+        assert!(read_packed_frame(&sheet, &frame(0, 2, 1, true)).is_some());
+    }
+}
@@ -0,0 +1,1270 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use rustc_hash::FxHashMap;
+use serde_json::Value;
+
+use crate::lights::{AreaLight, PointLight};
+use crate::primitives::{Normal, NormalInput, Texture, TextureInput};
+use crate::tilemap::{Tilemap, Tileset};
+use crate::LumenpyxProgram;
+use crate::Transform;
+
+// tiled stores the flipping of a tile in the top bits of the gid
+const FLIPPED_HORIZONTALLY_FLAG: u32 = 0x80000000;
+const FLIPPED_VERTICALLY_FLAG: u32 = 0x40000000;
+const FLIPPED_DIAGONALLY_FLAG: u32 = 0x20000000;
+const ROTATED_HEXAGONAL_120_FLAG: u32 = 0x10000000;
+const FLIP_FLAGS: u32 = FLIPPED_HORIZONTALLY_FLAG
+    | FLIPPED_VERTICALLY_FLAG
+    | FLIPPED_DIAGONALLY_FLAG
+    | ROTATED_HEXAGONAL_120_FLAG;
+
+const DEFAULT_HEIGHT: f32 = 0.0;
+const DEFAULT_ROUGHNESS: f32 = 1.0;
+const DEFAULT_SHADOW_STRENGTH: f32 = 0.5;
+const DEFAULT_LIGHT_HEIGHT: f32 = 1.0;
+const DEFAULT_LIGHT_INTENSITY: f32 = 1.0;
+const DEFAULT_LIGHT_FALLOFF: f32 = 0.02;
+
+/// Why a Tiled map couldn't be loaded
+#[derive(Debug)]
+pub enum TiledError {
+    /// A file of the map couldn't be read
+    Io(PathBuf, std::io::Error),
+    /// An image of a tileset couldn't be loaded
+    Image(PathBuf, image::ImageError),
+    /// The file isn't a valid Tiled file, the string says what is wrong with it
+    Invalid(PathBuf, String),
+    /// The map uses something that isn't supported, the string says what
+    Unsupported(PathBuf, String),
+}
+
+impl std::fmt::Display for TiledError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TiledError::Io(path, error) => {
+                write!(f, "Failed to read {}: {}", path.to_string_lossy(), error)
+            }
+            TiledError::Image(path, error) => {
+                write!(
+                    f,
+                    "Failed to load image {}: {}",
+                    path.to_string_lossy(),
+                    error
+                )
+            }
+            TiledError::Invalid(path, message) => {
+                write!(
+                    f,
+                    "Invalid Tiled file {}: {}",
+                    path.to_string_lossy(),
+                    message
+                )
+            }
+            TiledError::Unsupported(path, message) => {
+                write!(
+                    f,
+                    "Unsupported Tiled file {}: {}",
+                    path.to_string_lossy(),
+                    message
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for TiledError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TiledError::Io(_, error) => Some(error),
+            TiledError::Image(_, error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+fn invalid(path: &Path, message: impl Into<String>) -> TiledError {
+    TiledError::Invalid(path.to_path_buf(), message.into())
+}
+
+fn unsupported(path: &Path, message: impl Into<String>) -> TiledError {
+    TiledError::Unsupported(path.to_path_buf(), message.into())
+}
+
+/// A tile layer of a Tiled map.
+/// A layer that uses more than one tileset or shadow strength is split into one tilemap for each
+pub struct TiledTileLayer {
+    pub name: String,
+    pub tilemap: Tilemap,
+}
+
+/// Everything that was loaded from a Tiled map.
+/// The map is centered on (0, 0), one pixel in Tiled is one unit in lumenpyx.
+///
+/// Tile layers use these custom properties:
+/// - `height` and `roughness` (float) fill the channel for every tile in the layer
+/// - `shadow_strength` (float) is the shadow strength of the layer
+///
+/// Tiles in a tileset can also have a `height`, `roughness` and `shadow_strength` property, these are used over the layer's.
+/// A tileset can have `height_map`, `roughness_map` and `normal_map` (file) properties
+/// pointing to sheets that line up with the tileset image, these are used over everything else.
+///
+/// Point objects turn into point lights and rectangle objects turn into area lights.
+/// Lights use the `color` (color), `intensity`, `falloff` and `height` (float) properties.
+/// Set the `lights` (bool) property of an object layer to false to skip it.
+///
+/// Invisible layers are skipped, flipped tiles are drawn without the flip
+pub struct TiledMap {
+    pub tile_layers: Vec<TiledTileLayer>,
+    pub point_lights: Vec<PointLight>,
+    pub area_lights: Vec<AreaLight>,
+    /// in pixels
+    pub size: [u32; 2],
+}
+
+impl TiledMap {
+    /// Load a .tmx or .tmj map, tilesets in .tsx or .tsj files are loaded as well
+    pub fn load(path: &str, program: &mut LumenpyxProgram) -> Result<TiledMap, TiledError> {
+        let path = Path::new(path);
+        let map_data = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("tmx") | Some("xml") => parse_tmx(path)?,
+            Some("tmj") | Some("json") => parse_tmj(path)?,
+            _ => return Err(unsupported(path, "maps must be .tmx or .tmj files")),
+        };
+
+        build_map(map_data, program)
+    }
+
+    pub fn get_tilemaps(&self) -> Vec<&Tilemap> {
+        self.tile_layers
+            .iter()
+            .map(|layer| &layer.tilemap)
+            .collect()
+    }
+}
+
+type Properties = FxHashMap<String, String>;
+
+struct TiledMapData {
+    width: u32,
+    height: u32,
+    tile_width: u32,
+    tile_height: u32,
+    tilesets: Vec<TiledTilesetData>,
+    layers: Vec<TiledLayerData>,
+    /// the map file, for the errors
+    path: PathBuf,
+}
+
+struct TiledTilesetData {
+    first_gid: u32,
+    /// the image path relative to the current directory
+    image: PathBuf,
+    tile_width: u32,
+    tile_height: u32,
+    columns: u32,
+    /// the pixels around the edge of the image
+    margin: u32,
+    /// the pixels between the tiles
+    spacing: u32,
+    properties: Properties,
+    tile_properties: FxHashMap<u32, Properties>,
+    /// the directory the tileset was in, the file properties are relative to it
+    directory: PathBuf,
+    /// the file the tileset is in, for the errors
+    path: PathBuf,
+}
+
+enum TiledLayerData {
+    Tiles {
+        name: String,
+        width: u32,
+        height: u32,
+        data: Vec<u32>,
+        offset: [f32; 2],
+        properties: Properties,
+    },
+    Objects {
+        objects: Vec<TiledObjectData>,
+        offset: [f32; 2],
+        properties: Properties,
+    },
+}
+
+#[derive(PartialEq)]
+enum ObjectShape {
+    Rectangle,
+    Point,
+    Other,
+}
+
+struct TiledObjectData {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    shape: ObjectShape,
+    properties: Properties,
+}
+
+fn build_map(
+    map_data: TiledMapData,
+    program: &mut LumenpyxProgram,
+) -> Result<TiledMap, TiledError> {
+    let path = map_data.path.as_path();
+    let map_size = [
+        map_data.width * map_data.tile_width,
+        map_data.height * map_data.tile_height,
+    ];
+
+    let mut tile_layers = vec![];
+    let mut point_lights = vec![];
+    let mut area_lights = vec![];
+
+    // layers with the same materials share the tileset textures
+    let mut tileset_cache: FxHashMap<(usize, Option<String>, Option<String>), Tileset> =
+        FxHashMap::default();
+
+    for layer in &map_data.layers {
+        match layer {
+            TiledLayerData::Tiles {
+                name,
+                width,
+                height,
+                data,
+                offset,
+                properties,
+            } => {
+                if data.len() != (*width * *height) as usize {
+                    return Err(invalid(
+                        path,
+                        format!("tile layer {} doesn't have a tile for every cell", name),
+                    ));
+                }
+
+                let layer_shadow_strength = get_f32_property(properties, "shadow_strength", path)?
+                    .unwrap_or(DEFAULT_SHADOW_STRENGTH);
+
+                for (tileset_index, tileset_data) in map_data.tilesets.iter().enumerate() {
+                    // the shadow strength is per tilemap, so the tiles are split by their shadow strength
+                    let mut tiles_by_shadow_strength: Vec<(f32, Vec<Vec<Option<u32>>>)> = vec![];
+                    for (i, gid) in data.iter().enumerate() {
+                        let gid = gid & !FLIP_FLAGS;
+                        if gid == 0 || find_tileset(&map_data.tilesets, gid) != Some(tileset_index)
+                        {
+                            continue;
+                        }
+
+                        let tile = gid - tileset_data.first_gid;
+                        let shadow_strength = match tileset_data.tile_properties.get(&tile) {
+                            Some(tile_properties) => get_f32_property(
+                                tile_properties,
+                                "shadow_strength",
+                                &tileset_data.path,
+                            )?,
+                            None => None,
+                        }
+                        .unwrap_or(layer_shadow_strength);
+
+                        let tiles = match tiles_by_shadow_strength
+                            .iter()
+                            .position(|(strength, _)| *strength == shadow_strength)
+                        {
+                            Some(index) => &mut tiles_by_shadow_strength[index].1,
+                            None => {
+                                tiles_by_shadow_strength.push((
+                                    shadow_strength,
+                                    vec![vec![None; *width as usize]; *height as usize],
+                                ));
+                                &mut tiles_by_shadow_strength.last_mut().unwrap().1
+                            }
+                        };
+
+                        let x = i % *width as usize;
+                        let y = i / *width as usize;
+                        tiles[y][x] = Some(tile);
+                    }
+
+                    if tiles_by_shadow_strength.is_empty() {
+                        continue;
+                    }
+
+                    let key = (
+                        tileset_index,
+                        properties.get("height").cloned(),
+                        properties.get("roughness").cloned(),
+                    );
+                    let tileset = match tileset_cache.get(&key) {
+                        Some(tileset) => *tileset,
+                        None => {
+                            let tileset = build_tileset(tileset_data, properties, program)?;
+                            tileset_cache.insert(key, tileset);
+                            tileset
+                        }
+                    };
+
+                    // tiled counts from the top left, lumenpyx from the center going up
+                    let layer_size = [
+                        (*width * map_data.tile_width) as f32,
+                        (*height * map_data.tile_height) as f32,
+                    ];
+                    let x = offset[0] + layer_size[0] / 2.0 - map_size[0] as f32 / 2.0;
+                    let y = map_size[1] as f32 / 2.0 - (offset[1] + layer_size[1] / 2.0);
+
+                    for (shadow_strength, tiles) in tiles_by_shadow_strength {
+                        if let Some(tile) = tiles
+                            .iter()
+                            .flatten()
+                            .flatten()
+                            .find(|tile| **tile >= tileset.get_tile_count())
+                        {
+                            return Err(invalid(
+                                path,
+                                format!("tile {} is outside of its tileset", tile),
+                            ));
+                        }
+
+                        let mut tilemap =
+                            Tilemap::new(tileset, tiles, Transform::new([x, y, 0.0]), program);
+                        tilemap.set_shadow_strength(shadow_strength);
+
+                        tile_layers.push(TiledTileLayer {
+                            name: name.clone(),
+                            tilemap,
+                        });
+                    }
+                }
+            }
+            TiledLayerData::Objects {
+                objects,
+                offset,
+                properties,
+            } => {
+                if get_bool_property(properties, "lights", path)? == Some(false) {
+                    continue;
+                }
+
+                for object in objects {
+                    let color = get_color_property(&object.properties, "color", path)?
+                        .unwrap_or([1.0, 1.0, 1.0]);
+                    let intensity = get_f32_property(&object.properties, "intensity", path)?
+                        .unwrap_or(DEFAULT_LIGHT_INTENSITY);
+                    let falloff = get_f32_property(&object.properties, "falloff", path)?
+                        .unwrap_or(DEFAULT_LIGHT_FALLOFF);
+                    let z = get_f32_property(&object.properties, "height", path)?
+                        .unwrap_or(DEFAULT_LIGHT_HEIGHT);
+
+                    match object.shape {
+                        ObjectShape::Point => {
+                            let x = offset[0] + object.x - map_size[0] as f32 / 2.0;
+                            let y = map_size[1] as f32 / 2.0 - (offset[1] + object.y);
+                            point_lights.push(PointLight::new(
+                                [x, y, z],
+                                color,
+                                intensity,
+                                falloff,
+                            ));
+                        }
+                        ObjectShape::Rectangle => {
+                            // the position of a rectangle is its top left
+                            let x = offset[0] + object.x + object.width / 2.0
+                                - map_size[0] as f32 / 2.0;
+                            let y = map_size[1] as f32 / 2.0
+                                - (offset[1] + object.y + object.height / 2.0);
+                            area_lights.push(AreaLight::new(
+                                [x, y, z],
+                                color,
+                                intensity,
+                                falloff,
+                                object.width,
+                                object.height,
+                            ));
+                        }
+                        ObjectShape::Other => {}
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(TiledMap {
+        tile_layers,
+        point_lights,
+        area_lights,
+        size: map_size,
+    })
+}
+
+/// the tileset with the highest first gid that is still below the gid
+fn find_tileset(tilesets: &[TiledTilesetData], gid: u32) -> Option<usize> {
+    tilesets
+        .iter()
+        .enumerate()
+        .filter(|(_, tileset)| tileset.first_gid <= gid)
+        .max_by_key(|(_, tileset)| tileset.first_gid)
+        .map(|(index, _)| index)
+}
+
+fn build_tileset(
+    tileset_data: &TiledTilesetData,
+    layer_properties: &Properties,
+    program: &mut LumenpyxProgram,
+) -> Result<Tileset, TiledError> {
+    let albedo = load_texture(&tileset_data.image, program)?;
+
+    let height = build_channel(
+        tileset_data,
+        layer_properties,
+        "height",
+        "height_map",
+        DEFAULT_HEIGHT,
+        program,
+    )?;
+    let roughness = build_channel(
+        tileset_data,
+        layer_properties,
+        "roughness",
+        "roughness_map",
+        DEFAULT_ROUGHNESS,
+        program,
+    )?;
+    let normal = match tileset_data.properties.get("normal_map") {
+        Some(path) => Normal::Texture(load_texture(&tileset_data.directory.join(path), program)?),
+        None => Normal::AutoGenerated,
+    };
+
+    if albedo.width() < tileset_data.tile_width || albedo.height() < tileset_data.tile_height {
+        return Err(invalid(
+            &tileset_data.path,
+            "the tileset image is smaller than one tile",
+        ));
+    }
+    let albedo_size = albedo.dimensions();
+    for channel in [&height, &roughness] {
+        if let TextureInput::Texture(Texture::Texture(texture)) = channel {
+            if texture.dimensions() != albedo_size {
+                return Err(invalid(
+                    &tileset_data.path,
+                    "the material maps must be the same size as the tileset image",
+                ));
+            }
+        }
+    }
+    if let Normal::Texture(texture) = &normal {
+        if texture.dimensions() != albedo_size {
+            return Err(invalid(
+                &tileset_data.path,
+                "the material maps must be the same size as the tileset image",
+            ));
+        }
+    }
+
+    let mut tileset = Tileset::new(
+        albedo.into(),
+        height,
+        roughness,
+        NormalInput::Normal(normal),
+        [tileset_data.tile_width, tileset_data.tile_height],
+        program,
+    );
+    tileset.set_spacing(tileset_data.margin, tileset_data.spacing);
+
+    Ok(tileset)
+}
+
+/// load an image the same way sprites do, but return the error instead of panicking
+fn load_texture(
+    path: &Path,
+    program: &LumenpyxProgram,
+) -> Result<glium::texture::Texture2d, TiledError> {
+    let image = image::open(path)
+        .map_err(|error| TiledError::Image(path.to_path_buf(), error))?
+        .to_rgba32f();
+    let dimensions = image.dimensions();
+    let raw_image = glium::texture::RawImage2d::from_raw_rgba_reversed(&image, dimensions);
+
+    Ok(glium::texture::Texture2d::new(&program.display, raw_image)
+        .expect("Failed to create tileset texture"))
+}
+
+/// use the sheet from the tileset if there is one,
+/// otherwise paint every tile with its own value or the layer's value
+fn build_channel(
+    tileset_data: &TiledTilesetData,
+    layer_properties: &Properties,
+    property: &str,
+    sheet_property: &str,
+    default: f32,
+    program: &LumenpyxProgram,
+) -> Result<TextureInput, TiledError> {
+    if let Some(path) = tileset_data.properties.get(sheet_property) {
+        let texture = load_texture(&tileset_data.directory.join(path), program)?;
+        return Ok(texture.into());
+    }
+
+    let layer_value =
+        get_f32_property(layer_properties, property, &tileset_data.path)?.unwrap_or(default);
+
+    let mut tile_values = FxHashMap::default();
+    for (tile, properties) in &tileset_data.tile_properties {
+        if let Some(value) = get_f32_property(properties, property, &tileset_data.path)? {
+            tile_values.insert(*tile, value);
+        }
+    }
+    if tile_values.is_empty() {
+        return Ok(layer_value.into());
+    }
+
+    let image = image::open(&tileset_data.image)
+        .map_err(|error| TiledError::Image(tileset_data.image.clone(), error))?
+        .to_rgba32f();
+    let (width, height) = image.dimensions();
+
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let value = get_tile_at_pixel(tileset_data, x, y)
+            .and_then(|tile| tile_values.get(&tile).copied())
+            .unwrap_or(layer_value);
+
+        if pixel[3] == 0.0 {
+            data.extend_from_slice(&[0.0, 0.0, 0.0, 0.0]);
+        } else {
+            data.extend_from_slice(&[value, value, value, 1.0]);
+        }
+    }
+
+    let raw_image = glium::texture::RawImage2d::from_raw_rgba_reversed(&data, (width, height));
+    let texture = glium::texture::Texture2d::new(&program.display, raw_image)
+        .expect("Failed to create tileset texture");
+
+    Ok(texture.into())
+}
+
+/// the tile a pixel of the tileset image is in, None for the margin and the spacing between the tiles
+fn get_tile_at_pixel(tileset_data: &TiledTilesetData, x: u32, y: u32) -> Option<u32> {
+    let cell = |position: u32, tile_size: u32| {
+        let position = position.checked_sub(tileset_data.margin)?;
+        let stride = tile_size + tileset_data.spacing;
+        if position % stride >= tile_size {
+            return None;
+        }
+        Some(position / stride)
+    };
+
+    let column = cell(x, tileset_data.tile_width)?;
+    let row = cell(y, tileset_data.tile_height)?;
+    if column >= tileset_data.columns {
+        return None;
+    }
+
+    Some(row * tileset_data.columns + column)
+}
+
+fn get_f32_property(
+    properties: &Properties,
+    name: &str,
+    path: &Path,
+) -> Result<Option<f32>, TiledError> {
+    properties
+        .get(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| invalid(path, format!("property {} must be a number", name)))
+        })
+        .transpose()
+}
+
+fn get_bool_property(
+    properties: &Properties,
+    name: &str,
+    path: &Path,
+) -> Result<Option<bool>, TiledError> {
+    properties
+        .get(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| invalid(path, format!("property {} must be a bool", name)))
+        })
+        .transpose()
+}
+
+/// tiled colors are #AARRGGBB or #RRGGBB
+fn get_color_property(
+    properties: &Properties,
+    name: &str,
+    path: &Path,
+) -> Result<Option<[f32; 3]>, TiledError> {
+    let value = match properties.get(name) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let not_a_color = || invalid(path, format!("property {} must be a color", name));
+
+    let hex = value.trim_start_matches('#');
+    let hex = match hex.len() {
+        8 => &hex[2..],
+        6 => hex,
+        _ => return Err(not_a_color()),
+    };
+
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+            .map(|channel| channel as f32 / 255.0)
+            .ok_or_else(not_a_color)
+    };
+
+    Ok(Some([channel(0)?, channel(2)?, channel(4)?]))
+}
+
+fn parent_directory(path: &Path) -> PathBuf {
+    path.parent()
+        .map(|parent| parent.to_path_buf())
+        .unwrap_or_default()
+}
+
+fn read_file(path: &Path) -> Result<String, TiledError> {
+    std::fs::read_to_string(path).map_err(|error| TiledError::Io(path.to_path_buf(), error))
+}
+
+/// decode the tiles of a layer from csv or base64, the base64 can be zlib or gzip compressed
+fn decode_tile_data(
+    text: &str,
+    encoding: Option<&str>,
+    compression: Option<&str>,
+    path: &Path,
+) -> Result<Vec<u32>, TiledError> {
+    match encoding {
+        Some("csv") => text
+            .split(',')
+            .map(|gid| gid.trim())
+            .filter(|gid| !gid.is_empty())
+            .map(|gid| {
+                gid.parse()
+                    .map_err(|_| invalid(path, format!("tile {} is not a number", gid)))
+            })
+            .collect(),
+        Some("base64") => {
+            let bytes = decode_base64(text);
+            let bytes = match compression.unwrap_or_default() {
+                "" => bytes,
+                "zlib" => decompress(flate2::read::ZlibDecoder::new(bytes.as_slice()), path)?,
+                "gzip" => decompress(flate2::read::GzDecoder::new(bytes.as_slice()), path)?,
+                compression => {
+                    return Err(unsupported(
+                        path,
+                        format!(
+                            "{} compression isn't supported, use zlib, gzip or no compression",
+                            compression
+                        ),
+                    ))
+                }
+            };
+
+            if bytes.len() % 4 != 0 {
+                return Err(invalid(path, "the tile data is cut off"));
+            }
+
+            Ok(bytes
+                .chunks_exact(4)
+                .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .collect())
+        }
+        _ => Err(unsupported(
+            path,
+            "the tile layers must be saved as CSV or base64",
+        )),
+    }
+}
+
+fn decompress(mut decoder: impl Read, path: &Path) -> Result<Vec<u8>, TiledError> {
+    let mut bytes = vec![];
+    decoder.read_to_end(&mut bytes).map_err(|error| {
+        invalid(
+            path,
+            format!("failed to decompress the tile data: {}", error),
+        )
+    })?;
+    Ok(bytes)
+}
+
+fn decode_base64(text: &str) -> Vec<u8> {
+    let mut bytes = vec![];
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for character in text.bytes() {
+        let value = match character {
+            b'A'..=b'Z' => character - b'A',
+            b'a'..=b'z' => character - b'a' + 26,
+            b'0'..=b'9' => character - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            // padding and whitespace
+            _ => continue,
+        };
+
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+
+    bytes
+}
+
+fn parse_tmx(path: &Path) -> Result<TiledMapData, TiledError> {
+    let text = read_file(path)?;
+    let document =
+        roxmltree::Document::parse(&text).map_err(|error| invalid(path, error.to_string()))?;
+    let map = document.root_element();
+    let directory = parent_directory(path);
+
+    if map.attribute("infinite") == Some("1") {
+        return Err(unsupported(
+            path,
+            "infinite maps aren't supported, turn off infinite in the map properties",
+        ));
+    }
+
+    let mut tilesets = vec![];
+    for node in map.children().filter(|node| node.has_tag_name("tileset")) {
+        let first_gid = parse_attribute(&node, "firstgid", path)?;
+        let tileset = match node.attribute("source") {
+            Some(source) => {
+                let tileset_path = directory.join(source);
+                load_external_tileset(&tileset_path, first_gid)?
+            }
+            None => parse_tmx_tileset(&node, first_gid, &directory, path)?,
+        };
+        tilesets.push(tileset);
+    }
+
+    let mut layers = vec![];
+    parse_tmx_layers(&map, [0.0, 0.0], &mut layers, path)?;
+
+    Ok(TiledMapData {
+        width: parse_attribute(&map, "width", path)?,
+        height: parse_attribute(&map, "height", path)?,
+        tile_width: parse_attribute(&map, "tilewidth", path)?,
+        tile_height: parse_attribute(&map, "tileheight", path)?,
+        tilesets,
+        layers,
+        path: path.to_path_buf(),
+    })
+}
+
+fn load_external_tileset(path: &Path, first_gid: u32) -> Result<TiledTilesetData, TiledError> {
+    let directory = parent_directory(path);
+    let text = read_file(path)?;
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("tsj") | Some("json") => {
+            let json: Value =
+                serde_json::from_str(&text).map_err(|error| invalid(path, error.to_string()))?;
+            parse_tmj_tileset(&json, first_gid, &directory, path)
+        }
+        _ => {
+            let document = roxmltree::Document::parse(&text)
+                .map_err(|error| invalid(path, error.to_string()))?;
+            parse_tmx_tileset(&document.root_element(), first_gid, &directory, path)
+        }
+    }
+}
+
+fn parse_attribute<T: std::str::FromStr>(
+    node: &roxmltree::Node,
+    name: &str,
+    path: &Path,
+) -> Result<T, TiledError> {
+    let value = node.attribute(name).ok_or_else(|| {
+        invalid(
+            path,
+            format!("{} is missing {}", node.tag_name().name(), name),
+        )
+    })?;
+
+    value
+        .parse()
+        .map_err(|_| invalid(path, format!("attribute {} is invalid", name)))
+}
+
+fn parse_optional_attribute<T: std::str::FromStr>(
+    node: &roxmltree::Node,
+    name: &str,
+    default: T,
+    path: &Path,
+) -> Result<T, TiledError> {
+    match node.attribute(name) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| invalid(path, format!("attribute {} is invalid", name))),
+        None => Ok(default),
+    }
+}
+
+fn parse_tmx_properties(node: &roxmltree::Node) -> Properties {
+    let mut properties = Properties::default();
+    for property in node
+        .children()
+        .filter(|child| child.has_tag_name("properties"))
+        .flat_map(|properties| properties.children())
+        .filter(|child| child.has_tag_name("property"))
+    {
+        let name = property.attribute("name").unwrap_or_default().to_string();
+        // multiline strings are stored as the text of the property
+        let value = property
+            .attribute("value")
+            .or(property.text())
+            .unwrap_or_default()
+            .to_string();
+        properties.insert(name, value);
+    }
+    properties
+}
+
+fn parse_tmx_tileset(
+    node: &roxmltree::Node,
+    first_gid: u32,
+    directory: &Path,
+    path: &Path,
+) -> Result<TiledTilesetData, TiledError> {
+    let image = node
+        .children()
+        .find(|child| child.has_tag_name("image"))
+        .ok_or_else(|| {
+            unsupported(
+                path,
+                "tilesets made from a collection of images aren't supported",
+            )
+        })?;
+
+    let mut tile_properties = FxHashMap::default();
+    for tile in node.children().filter(|child| child.has_tag_name("tile")) {
+        tile_properties.insert(
+            parse_attribute(&tile, "id", path)?,
+            parse_tmx_properties(&tile),
+        );
+    }
+
+    Ok(TiledTilesetData {
+        first_gid,
+        image: directory.join(parse_attribute::<String>(&image, "source", path)?),
+        tile_width: parse_attribute(node, "tilewidth", path)?,
+        tile_height: parse_attribute(node, "tileheight", path)?,
+        columns: parse_attribute(node, "columns", path)?,
+        margin: parse_optional_attribute(node, "margin", 0, path)?,
+        spacing: parse_optional_attribute(node, "spacing", 0, path)?,
+        properties: parse_tmx_properties(node),
+        tile_properties,
+        directory: directory.to_path_buf(),
+        path: path.to_path_buf(),
+    })
+}
+
+fn parse_tmx_layers(
+    parent: &roxmltree::Node,
+    parent_offset: [f32; 2],
+    layers: &mut Vec<TiledLayerData>,
+    path: &Path,
+) -> Result<(), TiledError> {
+    for node in parent.children().filter(|node| node.is_element()) {
+        if node.attribute("visible") == Some("0") {
+            continue;
+        }
+
+        let offset = [
+            parent_offset[0] + parse_optional_attribute(&node, "offsetx", 0.0, path)?,
+            parent_offset[1] + parse_optional_attribute(&node, "offsety", 0.0, path)?,
+        ];
+
+        match node.tag_name().name() {
+            "layer" => {
+                let data = node
+                    .children()
+                    .find(|child| child.has_tag_name("data"))
+                    .ok_or_else(|| invalid(path, "a tile layer is missing its data"))?;
+
+                layers.push(TiledLayerData::Tiles {
+                    name: node.attribute("name").unwrap_or_default().to_string(),
+                    width: parse_attribute(&node, "width", path)?,
+                    height: parse_attribute(&node, "height", path)?,
+                    data: decode_tile_data(
+                        data.text().unwrap_or_default(),
+                        data.attribute("encoding"),
+                        data.attribute("compression"),
+                        path,
+                    )?,
+                    offset,
+                    properties: parse_tmx_properties(&node),
+                });
+            }
+            "objectgroup" => {
+                let mut objects = vec![];
+                for object in node.children().filter(|child| child.has_tag_name("object")) {
+                    let width = parse_optional_attribute(&object, "width", 0.0, path)?;
+                    let height = parse_optional_attribute(&object, "height", 0.0, path)?;
+                    let is_shape = |name| object.children().any(|child| child.has_tag_name(name));
+
+                    let shape = if is_shape("point") {
+                        ObjectShape::Point
+                    } else if is_shape("ellipse")
+                        || is_shape("polygon")
+                        || is_shape("polyline")
+                        || is_shape("text")
+                        || object.attribute("gid").is_some()
+                        || width == 0.0
+                        || height == 0.0
+                    {
+                        ObjectShape::Other
+                    } else {
+                        ObjectShape::Rectangle
+                    };
+
+                    objects.push(TiledObjectData {
+                        x: parse_optional_attribute(&object, "x", 0.0, path)?,
+                        y: parse_optional_attribute(&object, "y", 0.0, path)?,
+                        width,
+                        height,
+                        shape,
+                        properties: parse_tmx_properties(&object),
+                    });
+                }
+
+                layers.push(TiledLayerData::Objects {
+                    objects,
+                    offset,
+                    properties: parse_tmx_properties(&node),
+                });
+            }
+            "group" => parse_tmx_layers(&node, offset, layers, path)?,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_tmj(path: &Path) -> Result<TiledMapData, TiledError> {
+    let json: Value = serde_json::from_str(&read_file(path)?)
+        .map_err(|error| invalid(path, error.to_string()))?;
+    let directory = parent_directory(path);
+
+    if json["infinite"].as_bool() == Some(true) {
+        return Err(unsupported(
+            path,
+            "infinite maps aren't supported, turn off infinite in the map properties",
+        ));
+    }
+
+    let mut tilesets = vec![];
+    for tileset in json["tilesets"].as_array().into_iter().flatten() {
+        let first_gid = get_json_u32(tileset, "firstgid", path)?;
+        let tileset = match tileset["source"].as_str() {
+            Some(source) => load_external_tileset(&directory.join(source), first_gid)?,
+            None => parse_tmj_tileset(tileset, first_gid, &directory, path)?,
+        };
+        tilesets.push(tileset);
+    }
+
+    let mut layers = vec![];
+    parse_tmj_layers(&json["layers"], [0.0, 0.0], &mut layers, path)?;
+
+    Ok(TiledMapData {
+        width: get_json_u32(&json, "width", path)?,
+        height: get_json_u32(&json, "height", path)?,
+        tile_width: get_json_u32(&json, "tilewidth", path)?,
+        tile_height: get_json_u32(&json, "tileheight", path)?,
+        tilesets,
+        layers,
+        path: path.to_path_buf(),
+    })
+}
+
+fn get_json_u32(json: &Value, name: &str, path: &Path) -> Result<u32, TiledError> {
+    json[name]
+        .as_u64()
+        .map(|value| value as u32)
+        .ok_or_else(|| invalid(path, format!("{} is missing or isn't a number", name)))
+}
+
+fn get_json_f32(json: &Value, name: &str) -> f32 {
+    json[name].as_f64().unwrap_or(0.0) as f32
+}
+
+fn parse_tmj_properties(json: &Value) -> Properties {
+    let mut properties = Properties::default();
+    for property in json["properties"].as_array().into_iter().flatten() {
+        let name = property["name"].as_str().unwrap_or_default().to_string();
+        let value = match &property["value"] {
+            Value::String(value) => value.clone(),
+            value => value.to_string(),
+        };
+        properties.insert(name, value);
+    }
+    properties
+}
+
+fn parse_tmj_tileset(
+    json: &Value,
+    first_gid: u32,
+    directory: &Path,
+    path: &Path,
+) -> Result<TiledTilesetData, TiledError> {
+    let image = json["image"].as_str().ok_or_else(|| {
+        unsupported(
+            path,
+            "tilesets made from a collection of images aren't supported",
+        )
+    })?;
+
+    let mut tile_properties = FxHashMap::default();
+    for tile in json["tiles"].as_array().into_iter().flatten() {
+        tile_properties.insert(get_json_u32(tile, "id", path)?, parse_tmj_properties(tile));
+    }
+
+    Ok(TiledTilesetData {
+        first_gid,
+        image: directory.join(image),
+        tile_width: get_json_u32(json, "tilewidth", path)?,
+        tile_height: get_json_u32(json, "tileheight", path)?,
+        columns: get_json_u32(json, "columns", path)?,
+        margin: json["margin"].as_u64().unwrap_or(0) as u32,
+        spacing: json["spacing"].as_u64().unwrap_or(0) as u32,
+        properties: parse_tmj_properties(json),
+        tile_properties,
+        directory: directory.to_path_buf(),
+        path: path.to_path_buf(),
+    })
+}
+
+fn parse_tmj_layers(
+    json: &Value,
+    parent_offset: [f32; 2],
+    layers: &mut Vec<TiledLayerData>,
+    path: &Path,
+) -> Result<(), TiledError> {
+    for layer in json.as_array().into_iter().flatten() {
+        if layer["visible"].as_bool() == Some(false) {
+            continue;
+        }
+
+        let offset = [
+            parent_offset[0] + get_json_f32(layer, "offsetx"),
+            parent_offset[1] + get_json_f32(layer, "offsety"),
+        ];
+
+        match layer["type"].as_str() {
+            Some("tilelayer") => {
+                let data = match &layer["data"] {
+                    Value::Array(gids) => gids
+                        .iter()
+                        .map(|gid| {
+                            gid.as_u64()
+                                .map(|gid| gid as u32)
+                                .ok_or_else(|| invalid(path, "a tile isn't a number"))
+                        })
+                        .collect::<Result<Vec<u32>, TiledError>>()?,
+                    Value::String(text) => decode_tile_data(
+                        text,
+                        layer["encoding"].as_str(),
+                        layer["compression"].as_str(),
+                        path,
+                    )?,
+                    _ => {
+                        return Err(unsupported(
+                            path,
+                            "a tile layer has no data, infinite maps aren't supported",
+                        ))
+                    }
+                };
+
+                layers.push(TiledLayerData::Tiles {
+                    name: layer["name"].as_str().unwrap_or_default().to_string(),
+                    width: get_json_u32(layer, "width", path)?,
+                    height: get_json_u32(layer, "height", path)?,
+                    data,
+                    offset,
+                    properties: parse_tmj_properties(layer),
+                });
+            }
+            Some("objectgroup") => {
+                let mut objects = vec![];
+                for object in layer["objects"].as_array().into_iter().flatten() {
+                    let width = get_json_f32(object, "width");
+                    let height = get_json_f32(object, "height");
+                    let is_shape = |name: &str| !object[name].is_null() && object[name] != false;
+
+                    let shape = if object["point"].as_bool() == Some(true) {
+                        ObjectShape::Point
+                    } else if is_shape("ellipse")
+                        || is_shape("polygon")
+                        || is_shape("polyline")
+                        || is_shape("text")
+                        || is_shape("gid")
+                        || width == 0.0
+                        || height == 0.0
+                    {
+                        ObjectShape::Other
+                    } else {
+                        ObjectShape::Rectangle
+                    };
+
+                    objects.push(TiledObjectData {
+                        x: get_json_f32(object, "x"),
+                        y: get_json_f32(object, "y"),
+                        width,
+                        height,
+                        shape,
+                        properties: parse_tmj_properties(object),
+                    });
+                }
+
+                layers.push(TiledLayerData::Objects {
+                    objects,
+                    offset,
+                    properties: parse_tmj_properties(layer),
+                });
+            }
+            Some("group") => parse_tmj_layers(&layer["layers"], offset, layers, path)?,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    const TILES: [u32; 4] = [1, 2, 0, 0x80000003];
+
+    fn encode_base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut text = String::new();
+        for chunk in bytes.chunks(3) {
+            let buffer = chunk.iter().enumerate().fold(0u32, |buffer, (i, byte)| {
+                buffer | (*byte as u32) << (16 - i * 8)
+            });
+            for i in 0..=chunk.len() {
+                text.push(ALPHABET[(buffer >> (18 - i * 6)) as usize & 63] as char);
+            }
+        }
+        text
+    }
+
+    fn tile_bytes() -> Vec<u8> {
+        TILES.iter().flat_map(|tile| tile.to_le_bytes()).collect()
+    }
+
+    fn test_tileset(margin: u32, spacing: u32) -> TiledTilesetData {
+        TiledTilesetData {
+            first_gid: 1,
+            image: PathBuf::new(),
+            tile_width: 8,
+            tile_height: 8,
+            columns: 2,
+            margin,
+            spacing,
+            properties: Properties::default(),
+            tile_properties: FxHashMap::default(),
+            directory: PathBuf::new(),
+            path: PathBuf::new(),
+        }
+    }
+
+    #[test]
+    fn decodes_csv_and_base64() {
+        let path = Path::new("map.tmx");
+        let csv = decode_tile_data("1,2,\n0,2147483651", Some("csv"), None, path).unwrap();
+        let base64 =
+            decode_tile_data(&encode_base64(&tile_bytes()), Some("base64"), None, path).unwrap();
+
+        assert_eq!(csv, TILES);
+        assert_eq!(base64, TILES);
+    }
+
+    #[test]
+    fn decodes_compressed_base64() {
+        let path = Path::new("map.tmx");
+
+        let mut zlib = flate2::write::ZlibEncoder::new(vec![], flate2::Compression::default());
+        zlib.write_all(&tile_bytes()).unwrap();
+        let zlib = encode_base64(&zlib.finish().unwrap());
+
+        let mut gzip = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        gzip.write_all(&tile_bytes()).unwrap();
+        let gzip = encode_base64(&gzip.finish().unwrap());
+
+        assert_eq!(
+            decode_tile_data(&zlib, Some("base64"), Some("zlib"), path).unwrap(),
+            TILES
+        );
+        assert_eq!(
+            decode_tile_data(&gzip, Some("base64"), Some("gzip"), path).unwrap(),
+            TILES
+        );
+    }
+
+    #[test]
+    fn unsupported_tile_data_is_an_error() {
+        let path = Path::new("map.tmx");
+
+        assert!(matches!(
+            decode_tile_data("", Some("base64"), Some("zstd"), path),
+            Err(TiledError::Unsupported(..))
+        ));
+        assert!(matches!(
+            decode_tile_data("1,a", Some("csv"), None, path),
+            Err(TiledError::Invalid(..))
+        ));
+    }
+
+    #[test]
+    fn finds_tiles_with_margin_and_spacing() {
+        let tileset = test_tileset(0, 0);
+        assert_eq!(get_tile_at_pixel(&tileset, 0, 0), Some(0));
+        assert_eq!(get_tile_at_pixel(&tileset, 8, 7), Some(1));
+        assert_eq!(get_tile_at_pixel(&tileset, 3, 8), Some(2));
+
+        // 2 pixels around the edge and 1 between the tiles
+        let tileset = test_tileset(2, 1);
+        assert_eq!(get_tile_at_pixel(&tileset, 1, 5), None);
+        assert_eq!(get_tile_at_pixel(&tileset, 2, 2), Some(0));
+        assert_eq!(get_tile_at_pixel(&tileset, 9, 9), Some(0));
+        assert_eq!(get_tile_at_pixel(&tileset, 10, 2), None);
+        assert_eq!(get_tile_at_pixel(&tileset, 11, 2), Some(1));
+        assert_eq!(get_tile_at_pixel(&tileset, 11, 11), Some(3));
+        assert_eq!(get_tile_at_pixel(&tileset, 20, 2), None);
+    }
+
+    #[test]
+    fn reads_properties() {
+        let path = Path::new("map.tmx");
+        let mut properties = Properties::default();
+        properties.insert("height".to_string(), "0.25".to_string());
+        properties.insert("color".to_string(), "#ff00ff80".to_string());
+        properties.insert("lights".to_string(), "nope".to_string());
+
+        assert_eq!(
+            get_f32_property(&properties, "height", path).unwrap(),
+            Some(0.25)
+        );
+        assert_eq!(
+            get_f32_property(&properties, "missing", path).unwrap(),
+            None
+        );
+        assert_eq!(
+            get_color_property(&properties, "color", path).unwrap(),
+            Some([0.0, 1.0, 128.0 / 255.0])
+        );
+        assert!(get_bool_property(&properties, "lights", path).is_err());
+    }
+}
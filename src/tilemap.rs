@@ -0,0 +1,494 @@
+use std::cell::RefCell;
+
+use glium::uniform;
+use glium::DrawParameters;
+use glium::Surface;
+use rustc_hash::FxHashSet;
+
+use crate::primitives::{
+    new_albedo_texture, new_non_albedo_texture, new_normal_texture, NormalInput, TextureInput,
+};
+use crate::Drawable;
+use crate::LumenpyxProgram;
+use crate::TextureHandle;
+use crate::Transform;
+use crate::DEFAULT_BEHAVIOR;
+use crate::DEFAULT_BLEND;
+use crate::FULL_SCREEN_QUAD;
+
+const TILEMAP_SHADER_FRAG: &str = include_str!("../shaders/primitives/tilemap.frag");
+const TILEMAP_SHADER_VERT: &str = include_str!("../shaders/primitives/tilemap.vert");
+
+/// The number of tiles on each side of a chunk,
+/// when a tile is changed only the chunk it is in is sent to the gpu again
+pub const CHUNK_SIZE: u32 = 16;
+
+/// The sheets the tiles are cut out of.
+/// The tiles are counted left to right, top to bottom, starting at 0.
+/// Every sheet needs to be the same size, the same way as the frames of `Animation::new_from_spritesheet`
+#[derive(Debug, Clone, Copy)]
+pub struct Tileset {
+    albedo_texture: TextureHandle,
+    height_texture: TextureHandle,
+    roughness_texture: TextureHandle,
+    normal_texture: TextureHandle,
+    /// in pixels
+    tile_size: [u32; 2],
+    /// in pixels
+    sheet_size: [u32; 2],
+    /// the pixels around the edge of the sheets
+    margin: u32,
+    /// the pixels between the tiles
+    spacing: u32,
+    columns: u32,
+    rows: u32,
+}
+
+impl Tileset {
+    /// tile size is the width and height of one tile in pixels
+    pub fn new(
+        albedo: TextureInput,
+        height: TextureInput,
+        roughness: TextureInput,
+        normal: NormalInput,
+        tile_size: [u32; 2],
+        program: &mut LumenpyxProgram,
+    ) -> Self {
+        let albedo_handle = match albedo {
+            TextureInput::TextureHandle(handle) => handle,
+            TextureInput::Texture(texture) => {
+                program.add_not_named_texture(new_albedo_texture(program, texture))
+            }
+        };
+
+        let height_handle = match height {
+            TextureInput::TextureHandle(handle) => handle,
+            TextureInput::Texture(texture) => {
+                let albedo_texture = program.get_texture_from_handle(&albedo_handle).unwrap();
+                let height_texture = new_non_albedo_texture(program, texture, albedo_texture);
+                program.add_not_named_texture(height_texture)
+            }
+        };
+
+        let roughness_handle = match roughness {
+            TextureInput::TextureHandle(handle) => handle,
+            TextureInput::Texture(texture) => {
+                let albedo_texture = program.get_texture_from_handle(&albedo_handle).unwrap();
+                let roughness_texture = new_non_albedo_texture(program, texture, albedo_texture);
+                program.add_not_named_texture(roughness_texture)
+            }
+        };
+
+        let normal_handle = match normal {
+            NormalInput::TextureHandle(handle) => handle,
+            NormalInput::Normal(normal) => {
+                let albedo_texture = program.get_texture_from_handle(&albedo_handle).unwrap();
+                let height_texture = program.get_texture_from_handle(&height_handle).unwrap();
+                let normal_texture =
+                    new_normal_texture(program, normal, height_texture, albedo_texture);
+                program.add_not_named_texture(normal_texture)
+            }
+        };
+
+        if tile_size[0] == 0 || tile_size[1] == 0 {
+            panic!("The tile size of a tileset cannot be 0");
+        }
+
+        let albedo_texture = program.get_texture_from_handle(&albedo_handle).unwrap();
+        let sheet_size = [albedo_texture.width(), albedo_texture.height()];
+
+        for handle in [&height_handle, &roughness_handle, &normal_handle] {
+            let texture = program.get_texture_from_handle(handle).unwrap();
+            if texture.dimensions() != albedo_texture.dimensions() {
+                panic!("The sheets of a tileset must be the same size");
+            }
+        }
+
+        let mut tileset = Self {
+            albedo_texture: albedo_handle,
+            height_texture: height_handle,
+            roughness_texture: roughness_handle,
+            normal_texture: normal_handle,
+            tile_size,
+            sheet_size,
+            margin: 0,
+            spacing: 0,
+            columns: 0,
+            rows: 0,
+        };
+        tileset.set_spacing(0, 0);
+
+        tileset
+    }
+
+    /// Set the pixels around the edge of the sheets and the pixels between the tiles, both are 0 by default
+    pub fn set_spacing(&mut self, margin: u32, spacing: u32) {
+        self.margin = margin;
+        self.spacing = spacing;
+
+        // the last tile in a row or column doesn't have spacing after it
+        let count =
+            |sheet: u32, tile: u32| (sheet.saturating_sub(margin * 2) + spacing) / (tile + spacing);
+        self.columns = count(self.sheet_size[0], self.tile_size[0]);
+        self.rows = count(self.sheet_size[1], self.tile_size[1]);
+    }
+
+    pub fn get_margin(&self) -> u32 {
+        self.margin
+    }
+
+    pub fn get_spacing(&self) -> u32 {
+        self.spacing
+    }
+
+    pub fn get_tile_size(&self) -> [u32; 2] {
+        self.tile_size
+    }
+
+    /// The number of tiles in the tileset
+    pub fn get_tile_count(&self) -> u32 {
+        self.columns * self.rows
+    }
+
+    /// returns the texture handles for albedo, height, roughness, and normal textures in that order
+    pub fn get_texture_handles(
+        &self,
+    ) -> (TextureHandle, TextureHandle, TextureHandle, TextureHandle) {
+        (
+            self.albedo_texture,
+            self.height_texture,
+            self.roughness_texture,
+            self.normal_texture,
+        )
+    }
+
+    pub fn free_all_textures(&self, program: &mut LumenpyxProgram) {
+        program.remove_texture(&self.albedo_texture);
+        program.remove_texture(&self.height_texture);
+        program.remove_texture(&self.roughness_texture);
+        program.remove_texture(&self.normal_texture);
+    }
+}
+
+fn check_tile(tileset: &Tileset, tile: u32) {
+    if tile >= tileset.get_tile_count() {
+        panic!(
+            "Tile {} is outside of the tileset, it only has {} tiles",
+            tile,
+            tileset.get_tile_count()
+        );
+    }
+}
+
+/// A grid of tiles drawn in one pass per channel instead of a sprite per tile
+pub struct Tilemap {
+    tileset: Tileset,
+    /// the tile indices row by row, the first row is the top of the map
+    tiles: Vec<Option<u32>>,
+    width: u32,
+    height: u32,
+    /// one texel per tile, sent to the shader to look up the tiles
+    tile_texture: glium::texture::UnsignedTexture2d,
+    /// the chunks that have been edited since they were last sent to the gpu
+    dirty_chunks: RefCell<FxHashSet<[u32; 2]>>,
+    pub transform: Transform,
+    shadow_strength: f32,
+}
+
+impl Tilemap {
+    /// The tiles are given row by row, the first row is the top of the map.
+    /// None is an empty tile, every row must be the same length
+    pub fn new(
+        tileset: Tileset,
+        tiles: Vec<Vec<Option<u32>>>,
+        transform: Transform,
+        program: &LumenpyxProgram,
+    ) -> Self {
+        let height = tiles.len() as u32;
+        let width = tiles.first().map(|row| row.len()).unwrap_or(0) as u32;
+
+        if width == 0 || height == 0 {
+            panic!("A tilemap must have at least one tile");
+        }
+        if tiles.iter().any(|row| row.len() as u32 != width) {
+            panic!("Every row of a tilemap must be the same length");
+        }
+
+        let tiles: Vec<Option<u32>> = tiles.into_iter().flatten().collect();
+        for tile in tiles.iter().flatten() {
+            check_tile(&tileset, *tile);
+        }
+
+        let tile_texture = glium::texture::UnsignedTexture2d::empty_with_format(
+            &program.display,
+            glium::texture::UncompressedUintFormat::U32,
+            glium::texture::MipmapsOption::NoMipmap,
+            width,
+            height,
+        )
+        .expect("Failed to create tilemap texture");
+
+        let tilemap = Self {
+            tileset,
+            tiles,
+            width,
+            height,
+            tile_texture,
+            dirty_chunks: RefCell::new(FxHashSet::default()),
+            transform,
+            shadow_strength: 0.5,
+        };
+
+        // send the whole map the first time
+        for chunk_y in 0..height.div_ceil(CHUNK_SIZE) {
+            for chunk_x in 0..width.div_ceil(CHUNK_SIZE) {
+                tilemap.write_chunk([chunk_x, chunk_y], program);
+            }
+        }
+
+        tilemap
+    }
+
+    /// Set the tile at x, y, (0, 0) is the top left of the map.
+    /// None clears the tile, the change is drawn next frame.
+    /// Panics if the tile isn't in the tileset
+    pub fn set_tile(&mut self, x: u32, y: u32, tile: Option<u32>) {
+        if x >= self.width || y >= self.height {
+            panic!(
+                "Tile ({}, {}) is outside of the tilemap ({}, {})",
+                x, y, self.width, self.height
+            );
+        }
+        if let Some(tile) = tile {
+            check_tile(&self.tileset, tile);
+        }
+
+        self.tiles[(y * self.width + x) as usize] = tile;
+        self.dirty_chunks
+            .borrow_mut()
+            .insert([x / CHUNK_SIZE, y / CHUNK_SIZE]);
+    }
+
+    /// Get the tile at x, y, (0, 0) is the top left of the map
+    pub fn get_tile(&self, x: u32, y: u32) -> Option<u32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        self.tiles[(y * self.width + x) as usize]
+    }
+
+    /// Get the size of the map in tiles
+    pub fn get_size(&self) -> [u32; 2] {
+        [self.width, self.height]
+    }
+
+    pub fn get_tileset(&self) -> &Tileset {
+        &self.tileset
+    }
+
+    /// Swap the tileset, the tile indices stay the same.
+    /// Panics if the new tileset doesn't have enough tiles for the ones already placed
+    pub fn set_tileset(&mut self, tileset: Tileset) {
+        for tile in self.tiles.iter().flatten() {
+            check_tile(&tileset, *tile);
+        }
+
+        self.tileset = tileset;
+    }
+
+    pub fn set_shadow_strength(&mut self, strength: f32) {
+        self.shadow_strength = strength;
+    }
+
+    /// send one chunk of the tiles to the tile texture
+    fn write_chunk(&self, chunk: [u32; 2], program: &LumenpyxProgram) {
+        let left = chunk[0] * CHUNK_SIZE;
+        let top = chunk[1] * CHUNK_SIZE;
+        let chunk_width = CHUNK_SIZE.min(self.width - left);
+        let chunk_height = CHUNK_SIZE.min(self.height - top);
+
+        let mut data = Vec::with_capacity((chunk_width * chunk_height) as usize);
+        for y in top..top + chunk_height {
+            for x in left..left + chunk_width {
+                // 0 is empty, so every tile is moved up by one
+                let tile = match self.tiles[(y * self.width + x) as usize] {
+                    Some(tile) => tile + 1,
+                    None => 0,
+                };
+                data.push(tile);
+            }
+        }
+
+        // unsigned textures can't be written to directly, so the chunk goes through a pixel buffer
+        let pixel_buffer: glium::texture::pixel_buffer::PixelBuffer<u32> =
+            glium::texture::pixel_buffer::PixelBuffer::new_empty(&program.display, data.len());
+        pixel_buffer.write(&data);
+
+        self.tile_texture.main_level().raw_upload_from_pixel_buffer(
+            pixel_buffer.as_slice(),
+            left..left + chunk_width,
+            top..top + chunk_height,
+            0..1,
+        );
+    }
+
+    /// scale the transform to the size of the map in pixels
+    fn get_tilemap_matrix(
+        &self,
+        transform: &Transform,
+        framebuffer: &glium::framebuffer::SimpleFrameBuffer,
+    ) -> [[f32; 4]; 4] {
+        let width = (self.width * self.tileset.tile_size[0]) as f32;
+        let height = (self.height * self.tileset.tile_size[1]) as f32;
+        let mut transform = transform.clone();
+
+        let smallest_dimension =
+            (framebuffer.get_dimensions().1 as f32).min(framebuffer.get_dimensions().0 as f32);
+        transform.set_scale(
+            transform.get_scale()[0] * width / smallest_dimension,
+            transform.get_scale()[1] * height / smallest_dimension,
+            transform.get_scale()[2],
+        );
+
+        transform.get_matrix()
+    }
+
+    fn draw_channel(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+        sheet: &TextureHandle,
+    ) {
+        let display = &program.display;
+        let indices = &program.indices;
+
+        let shader = program
+            .get_shader("tilemap_shader")
+            .expect("Failed to get tilemap shader");
+
+        let sheet = program
+            .get_texture_from_handle(sheet)
+            .expect("Failed to get tileset texture");
+
+        let uniforms = uniform! {
+            matrix: self.get_tilemap_matrix(transform, framebuffer),
+            tiles: glium::uniforms::Sampler(&self.tile_texture, DEFAULT_BEHAVIOR),
+            tileset: glium::uniforms::Sampler(sheet, DEFAULT_BEHAVIOR),
+            tile_size: [self.tileset.tile_size[0] as i32, self.tileset.tile_size[1] as i32],
+            columns: self.tileset.columns as i32,
+            margin: self.tileset.margin as i32,
+            spacing: self.tileset.spacing as i32,
+        };
+
+        let shape = FULL_SCREEN_QUAD;
+
+        let vertex_buffer =
+            glium::VertexBuffer::new(display, &shape).expect("Failed to create vertex buffer");
+
+        framebuffer
+            .draw(
+                &vertex_buffer,
+                indices,
+                shader,
+                &uniforms,
+                &DrawParameters {
+                    blend: DEFAULT_BLEND,
+                    ..Default::default()
+                },
+            )
+            .expect("Failed to draw tilemap");
+    }
+}
+
+impl Drawable for Tilemap {
+    fn draw_albedo(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        albedo_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.draw_channel(
+            program,
+            transform,
+            albedo_framebuffer,
+            &self.tileset.albedo_texture,
+        );
+    }
+
+    fn draw_height(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        height_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.draw_channel(
+            program,
+            transform,
+            height_framebuffer,
+            &self.tileset.height_texture,
+        );
+    }
+
+    fn draw_roughness(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        roughness_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.draw_channel(
+            program,
+            transform,
+            roughness_framebuffer,
+            &self.tileset.roughness_texture,
+        );
+    }
+
+    fn draw_normal(
+        &self,
+        program: &LumenpyxProgram,
+        transform: &Transform,
+        normal_framebuffer: &mut glium::framebuffer::SimpleFrameBuffer,
+    ) {
+        self.draw_channel(
+            program,
+            transform,
+            normal_framebuffer,
+            &self.tileset.normal_texture,
+        );
+    }
+
+    fn try_load_shaders(&self, program: &mut LumenpyxProgram) {
+        // this is called once a frame before drawing, so the edited chunks are sent here
+        for chunk in self.dirty_chunks.borrow_mut().drain() {
+            self.write_chunk(chunk, program);
+        }
+
+        if program.get_shader("tilemap_shader").is_some() {
+            return;
+        }
+
+        let shader = glium::Program::from_source(
+            &program.display,
+            TILEMAP_SHADER_VERT,
+            TILEMAP_SHADER_FRAG,
+            None,
+        )
+        .expect("Failed to create tilemap shader");
+
+        program.add_shader(shader, "tilemap_shader");
+    }
+
+    fn get_transform(&self) -> Transform {
+        self.transform
+    }
+
+    fn get_recieve_shadows_strength(&self) -> f32 {
+        self.shadow_strength
+    }
+
+    fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+}
@@ -0,0 +1,449 @@
+use std::f32::consts::PI;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::animation::AnimationTimeElapsed;
+use crate::clock::Clock;
+use crate::lights::{AreaLight, DirectionalLight, PointLight};
+use crate::Transform;
+
+/// How a tween moves between two values over time
+#[derive(Clone, Copy)]
+pub enum Easing {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    ExpoIn,
+    ExpoOut,
+    /// Pulls back a little before moving
+    BackIn,
+    /// Goes a little past the end before settling
+    BackOut,
+    /// Springs past the end and wobbles back
+    ElasticOut,
+    /// Bounces on the end like a ball
+    BounceOut,
+    /// Takes how far through the tween it is from 0.0 to 1.0 and returns how far between the values to be
+    Custom(fn(f32) -> f32),
+}
+
+impl Easing {
+    /// Takes how far through the tween it is from 0.0 to 1.0 and returns how far between the values to be
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::QuadInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                }
+            }
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::SineIn => 1.0 - (t * PI / 2.0).cos(),
+            Easing::SineOut => (t * PI / 2.0).sin(),
+            Easing::SineInOut => -((t * PI).cos() - 1.0) / 2.0,
+            Easing::ExpoIn => {
+                if t == 0.0 {
+                    0.0
+                } else {
+                    2.0_f32.powf(10.0 * t - 10.0)
+                }
+            }
+            Easing::ExpoOut => {
+                if t == 1.0 {
+                    1.0
+                } else {
+                    1.0 - 2.0_f32.powf(-10.0 * t)
+                }
+            }
+            Easing::BackIn => {
+                let c1 = 1.70158;
+                let c3 = c1 + 1.0;
+                c3 * t * t * t - c1 * t * t
+            }
+            Easing::BackOut => {
+                let c1 = 1.70158;
+                let c3 = c1 + 1.0;
+                1.0 + c3 * (t - 1.0).powi(3) + c1 * (t - 1.0).powi(2)
+            }
+            Easing::ElasticOut => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    let c4 = (2.0 * PI) / 3.0;
+                    2.0_f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * c4).sin() + 1.0
+                }
+            }
+            Easing::BounceOut => bounce_out(t),
+            Easing::Custom(function) => function(t),
+        }
+    }
+}
+
+fn bounce_out(t: f32) -> f32 {
+    let n1 = 7.5625;
+    let d1 = 2.75;
+    if t < 1.0 / d1 {
+        n1 * t * t
+    } else if t < 2.0 / d1 {
+        let t = t - 1.5 / d1;
+        n1 * t * t + 0.75
+    } else if t < 2.5 / d1 {
+        let t = t - 2.25 / d1;
+        n1 * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / d1;
+        n1 * t * t + 0.984375
+    }
+}
+
+/// A value that can be tweened
+pub trait Tweenable: Copy {
+    /// Get the value t of the way from a to b, t can be outside 0.0 - 1.0 for easings that overshoot
+    fn lerp(a: Self, b: Self, t: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn lerp(a: Self, b: Self, t: f32) -> Self {
+        a + (b - a) * t
+    }
+}
+
+impl<const N: usize> Tweenable for [f32; N] {
+    fn lerp(a: Self, b: Self, t: f32) -> Self {
+        let mut result = a;
+        for (value, (a, b)) in result.iter_mut().zip(a.iter().zip(b.iter())) {
+            *value = <f32 as Tweenable>::lerp(*a, *b, t);
+        }
+        result
+    }
+}
+
+impl Tweenable for Transform {
+    fn lerp(a: Self, b: Self, t: f32) -> Self {
+        a.interpolate(&b, t)
+    }
+}
+
+/// How many times a tween plays
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TweenRepeat {
+    Once,
+    /// Play this many times in total, Times(0) plays once the same as Times(1)
+    Times(u32),
+    Forever,
+}
+
+/// one step of a tween, from the end of the step before it
+#[derive(Clone, Copy)]
+struct TweenStep<V: Tweenable> {
+    to: V,
+    duration: Duration,
+    easing: Easing,
+}
+
+/// A value that moves from one value to another over time.
+/// Every update, get the value or apply it to something, e.g. `tween.apply(&mut light, PointLight::set_intensity)`.
+/// The functions at the bottom of this module can be used for the setters that take more than one value
+#[derive(Clone)]
+pub struct Tween<V: Tweenable> {
+    from: V,
+    steps: Vec<TweenStep<V>>,
+    delay: Duration,
+    repeat: TweenRepeat,
+    /// if true every other time it plays it plays backwards
+    yoyo: bool,
+    time: AnimationTimeElapsed,
+}
+
+impl<V: Tweenable> Tween<V> {
+    /// Make a tween from one value to another, it starts now on the clock, usually `program.get_clock()`
+    pub fn new(from: V, to: V, duration: Duration, easing: Easing, clock: Rc<dyn Clock>) -> Self {
+        Self {
+            from,
+            steps: vec![TweenStep {
+                to,
+                duration,
+                easing,
+            }],
+            delay: Duration::ZERO,
+            repeat: TweenRepeat::Once,
+            yoyo: false,
+            time: AnimationTimeElapsed::from_clock(clock),
+        }
+    }
+
+    /// Add another step that starts where the last step ended
+    pub fn then(mut self, to: V, duration: Duration, easing: Easing) -> Self {
+        self.steps.push(TweenStep {
+            to,
+            duration,
+            easing,
+        });
+        self
+    }
+
+    /// Wait this long before starting, the value is the from value until then
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat: TweenRepeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Play backwards every other time the tween plays, only does anything if it repeats
+    pub fn with_yoyo(mut self, yoyo: bool) -> Self {
+        self.yoyo = yoyo;
+        self
+    }
+
+    /// Play the tween from the clock starting now
+    pub fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.time = AnimationTimeElapsed::from_clock(clock);
+    }
+
+    pub fn set_time(&mut self, time: AnimationTimeElapsed) {
+        self.time = time;
+    }
+
+    /// Start the tween over, using the same clock it was using
+    pub fn restart(&mut self) {
+        self.time = match &self.time {
+            AnimationTimeElapsed::Clock(clock, _) => {
+                AnimationTimeElapsed::from_clock(clock.clone())
+            }
+            _ => Instant::now().into(),
+        };
+    }
+
+    /// How long the tween takes to play once, without the delay
+    pub fn get_duration(&self) -> Duration {
+        self.steps.iter().map(|step| step.duration).sum()
+    }
+
+    /// Whether the tween has played as many times as it repeats
+    pub fn is_finished(&self) -> bool {
+        match self.get_times() {
+            Some(times) => self.get_elapsed() >= self.get_duration().as_secs_f32() * times as f32,
+            None => false,
+        }
+    }
+
+    /// The value of the tween right now
+    pub fn get_value(&self) -> V {
+        let duration = self.get_duration().as_secs_f32();
+        let elapsed = self.get_elapsed();
+        if duration == 0.0 {
+            return self.value_at(0.0, true);
+        }
+
+        // which time through the tween we're on, and how far into it
+        let mut pass = (elapsed / duration) as u32;
+        let mut time = elapsed % duration;
+        if let Some(times) = self.get_times() {
+            if pass >= times {
+                pass = times - 1;
+                time = duration;
+            }
+        }
+
+        if self.yoyo && pass % 2 == 1 {
+            time = duration - time;
+        }
+
+        self.value_at(time, false)
+    }
+
+    /// Set something to the value of the tween, e.g. `tween.apply(&mut transform, Transform::set_x)`
+    pub fn apply<T>(&self, target: &mut T, setter: impl FnOnce(&mut T, V)) {
+        setter(target, self.get_value());
+    }
+
+    /// how many times the tween plays, None if it plays forever
+    fn get_times(&self) -> Option<u32> {
+        match self.repeat {
+            TweenRepeat::Once => Some(1),
+            TweenRepeat::Times(times) => Some(times.max(1)),
+            TweenRepeat::Forever => None,
+        }
+    }
+
+    /// how long it's been since the delay ended in seconds, 0.0 before then
+    fn get_elapsed(&self) -> f32 {
+        (self.time.as_secs_f32() - self.delay.as_secs_f32()).max(0.0)
+    }
+
+    /// the value at the time into a single play through of the tween
+    fn value_at(&self, time: f32, at_end: bool) -> V {
+        let mut start_value = self.from;
+        let mut start_time = 0.0;
+        for step in &self.steps {
+            let step_duration = step.duration.as_secs_f32();
+            if !at_end && time < start_time + step_duration {
+                let t = (time - start_time) / step_duration;
+                return V::lerp(start_value, step.to, step.easing.apply(t));
+            }
+
+            start_value = step.to;
+            start_time += step_duration;
+        }
+
+        start_value
+    }
+}
+
+/// Move the transform to the position, for use with `Tween::apply`
+pub fn transform_position(transform: &mut Transform, position: [f32; 3]) {
+    transform.set_x(position[0]);
+    transform.set_y(position[1]);
+    transform.set_z(position[2]);
+}
+
+/// Set the scale of the transform, for use with `Tween::apply`
+pub fn transform_scale(transform: &mut Transform, scale: [f32; 3]) {
+    transform.set_scale(scale[0], scale[1], scale[2]);
+}
+
+/// Set the position of the light, for use with `Tween::apply`
+pub fn point_light_position(light: &mut PointLight, position: [f32; 3]) {
+    light.set_position(position[0], position[1], position[2]);
+}
+
+/// Set the color of the light, for use with `Tween::apply`
+pub fn point_light_color(light: &mut PointLight, color: [f32; 3]) {
+    light.set_color(color[0], color[1], color[2]);
+}
+
+/// Set the position of the light, for use with `Tween::apply`
+pub fn area_light_position(light: &mut AreaLight, position: [f32; 3]) {
+    light.set_position(position[0], position[1], position[2]);
+}
+
+/// Set the color of the light, for use with `Tween::apply`
+pub fn area_light_color(light: &mut AreaLight, color: [f32; 3]) {
+    light.set_color(color[0], color[1], color[2]);
+}
+
+/// Set the direction of the light, for use with `Tween::apply`
+pub fn directional_light_direction(light: &mut DirectionalLight, direction: [f32; 3]) {
+    light.set_direction(direction[0], direction[1], direction[2]);
+}
+
+/// Set the color of the light, for use with `Tween::apply`
+pub fn directional_light_color(light: &mut DirectionalLight, color: [f32; 3]) {
+    light.set_color(color[0], color[1], color[2]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{FixedStepClock, ManualClock};
+
+    #[test]
+    fn tween_follows_a_fixed_step_clock() {
+        let clock = Rc::new(FixedStepClock::new(Duration::from_millis(25)));
+        let tween = Tween::new(
+            0.0,
+            100.0,
+            Duration::from_millis(100),
+            Easing::Linear,
+            clock.clone(),
+        );
+
+        let mut values = vec![];
+        for _ in 0..6 {
+            values.push(tween.get_value());
+            clock.tick();
+        }
+        assert_eq!(values, vec![0.0, 25.0, 50.0, 75.0, 100.0, 100.0]);
+        assert!(tween.is_finished());
+    }
+
+    #[test]
+    fn tween_waits_for_the_delay() {
+        let clock = Rc::new(ManualClock::new());
+        let tween = Tween::new(
+            0.0,
+            10.0,
+            Duration::from_millis(100),
+            Easing::Linear,
+            clock.clone(),
+        )
+        .with_delay(Duration::from_millis(50));
+
+        clock.set_time(Duration::from_millis(50));
+        assert_eq!(tween.get_value(), 0.0);
+        clock.set_time(Duration::from_millis(100));
+        assert_eq!(tween.get_value(), 5.0);
+    }
+
+    #[test]
+    fn repeat_times_zero_plays_once() {
+        let clock = Rc::new(ManualClock::new());
+        let tween = |repeat| {
+            Tween::new(
+                0.0,
+                10.0,
+                Duration::from_millis(100),
+                Easing::Linear,
+                clock.clone(),
+            )
+            .with_repeat(repeat)
+        };
+        let (zero, once) = (tween(TweenRepeat::Times(0)), tween(TweenRepeat::Once));
+
+        for time in [0, 50, 99, 100, 150] {
+            clock.set_time(Duration::from_millis(time));
+            assert_eq!(zero.get_value(), once.get_value());
+            assert_eq!(zero.is_finished(), once.is_finished());
+        }
+        assert!(zero.is_finished());
+        assert_eq!(zero.get_value(), 10.0);
+    }
+
+    #[test]
+    fn repeat_times_finishes_on_the_last_pass() {
+        let clock = Rc::new(ManualClock::new());
+        let tween = Tween::new(
+            0.0,
+            10.0,
+            Duration::from_millis(100),
+            Easing::Linear,
+            clock.clone(),
+        )
+        .with_repeat(TweenRepeat::Times(3))
+        .with_yoyo(true);
+
+        // the second pass goes backwards
+        clock.set_time(Duration::from_millis(125));
+        assert!((tween.get_value() - 7.5).abs() < 1e-4);
+        assert!(!tween.is_finished());
+
+        // the third pass goes forwards again and stays at the end
+        clock.set_time(Duration::from_millis(300));
+        assert!(tween.is_finished());
+        assert_eq!(tween.get_value(), 10.0);
+    }
+}
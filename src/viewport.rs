@@ -0,0 +1,197 @@
+use glium::Surface;
+
+use crate::drawable_object::Drawable;
+use crate::lights::LightDrawable;
+use crate::shaders::{draw_image_to_rect, get_letterbox_scales};
+use crate::Camera;
+use crate::LumenpyxProgram;
+use crate::DEFAULT_BEHAVIOR;
+
+/// A camera drawn into part of the window, e.g. one half of the screen for split-screen or a minimap in a corner.
+/// Every viewport has its own textures, so they can have different resolutions
+pub struct Viewport {
+    /// the name of the textures of the viewport, every viewport drawn in the same frame needs its own name
+    name: String,
+    pub camera: Camera,
+    /// x, y, width, height from 0.0 to 1.0 of the window, with 0.0, 0.0 at the bottom left
+    rect: [f32; 4],
+    resolution: [u32; 2],
+}
+
+impl Viewport {
+    /// Make a viewport drawn into the rect of the window, the rect is x, y, width, height from 0.0 to 1.0
+    /// with 0.0, 0.0 at the bottom left. The resolution is how many pixels the camera sees,
+    /// give it the same aspect ratio as the rect or the image will be stretched
+    pub fn new(name: &str, camera: Camera, rect: [f32; 4], resolution: [u32; 2]) -> Self {
+        Self {
+            name: name.to_string(),
+            camera,
+            rect,
+            resolution,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_rect(&mut self, rect: [f32; 4]) {
+        self.rect = rect;
+    }
+
+    pub fn get_rect(&self) -> [f32; 4] {
+        self.rect
+    }
+
+    pub fn set_resolution(&mut self, resolution: [u32; 2]) {
+        self.resolution = resolution;
+    }
+
+    pub fn get_resolution(&self) -> [u32; 2] {
+        self.resolution
+    }
+
+    /// the rect the viewport is drawn to in the window as left, bottom, right, top in -1 to 1,
+    /// the scales are the part of the window the letterboxed image covers
+    fn get_window_rect(&self, scales: [f32; 2]) -> [f32; 4] {
+        let left = -scales[0] + self.rect[0] * 2.0 * scales[0];
+        let bottom = -scales[1] + self.rect[1] * 2.0 * scales[1];
+        let right = left + self.rect[2] * 2.0 * scales[0];
+        let top = bottom + self.rect[3] * 2.0 * scales[1];
+        [left, bottom, right, top]
+    }
+
+    /// turn a position in physical pixels on the window into pixels of the resolution of the viewport
+    /// from its bottom left, None if the position isn't in the viewport
+    pub(crate) fn window_to_viewport_pixel(
+        &self,
+        window_size: (u32, u32),
+        dimensions: [u32; 2],
+        physical_position: [f32; 2],
+    ) -> Option<[f32; 2]> {
+        let scales = get_letterbox_scales(window_size, dimensions);
+        let [left, bottom, right, top] = self.get_window_rect(scales);
+
+        // -1 to 1 with y going up, the same as the rect
+        let position = [
+            physical_position[0] / window_size.0 as f32 * 2.0 - 1.0,
+            1.0 - physical_position[1] / window_size.1 as f32 * 2.0,
+        ];
+        if position[0] < left || position[0] > right || position[1] < bottom || position[1] > top {
+            return None;
+        }
+
+        Some([
+            (position[0] - left) / (right - left) * self.resolution[0] as f32,
+            (position[1] - bottom) / (top - bottom) * self.resolution[1] as f32,
+        ])
+    }
+}
+
+/// A viewport with the lights and drawables to draw in it
+pub type ViewportScene<'a> = (
+    &'a Viewport,
+    Vec<&'a dyn LightDrawable>,
+    Vec<&'a dyn Drawable>,
+);
+
+/// Draw every viewport with its own lights and drawables, then put them all in the window.
+/// Viewports later in the list are drawn on top, so a minimap should go last.
+/// Lights that can't reach anything the camera of a viewport sees aren't drawn in that viewport.
+/// Use `screen_to_world_in_viewport` and `pick_in_viewport` to find what is under the mouse in a viewport
+pub fn draw_viewports(viewports: Vec<ViewportScene>, program: &mut LumenpyxProgram) {
+    let mut finished_textures = vec![];
+    for (viewport, lights, drawables) in viewports {
+        program.begin_viewport(&viewport.name, viewport.resolution);
+        crate::prepare_scene(&lights, &drawables, program);
+        let finished_texture_name = crate::draw_scene(lights, drawables, program, &viewport.camera);
+        finished_textures.push((
+            program.get_pipeline_texture_name(finished_texture_name),
+            viewport,
+            viewport.camera.get_subpixel_offset(),
+        ));
+    }
+    program.end_viewport();
+
+    let mut target = program.display.draw();
+    target.clear_color(0.0, 0.0, 0.0, 0.0);
+
+    // the viewports share the letterboxed part of the window the normal camera would be drawn to
+    let scales = get_letterbox_scales(target.get_dimensions(), program.get_dimensions());
+    for (texture_name, viewport, offset) in finished_textures {
+        let texture = program
+            .cache
+            .get_texture(&texture_name)
+            .expect("Failed to get finished viewport texture");
+
+        draw_image_to_rect(
+            &mut target,
+            glium::uniforms::Sampler(texture, DEFAULT_BEHAVIOR),
+            program,
+            viewport.get_window_rect(scales),
+            offset,
+        );
+    }
+
+    target
+        .finish()
+        .expect("Failed to finish drawing to the screen");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_screen() -> (Viewport, Viewport) {
+        let left = Viewport::new(
+            "left",
+            Camera::new([0.0, 0.0, 1.0]),
+            [0.0, 0.0, 0.5, 1.0],
+            [80, 90],
+        );
+        let right = Viewport::new(
+            "right",
+            Camera::new([0.0, 0.0, 1.0]),
+            [0.5, 0.0, 0.5, 1.0],
+            [80, 90],
+        );
+        (left, right)
+    }
+
+    #[test]
+    fn window_positions_land_in_one_viewport() {
+        let (left, right) = split_screen();
+        let window_size = (1600, 900);
+
+        // the middle of the right half is the middle of the right viewport
+        assert_eq!(
+            right.window_to_viewport_pixel(window_size, [160, 90], [1200.0, 450.0]),
+            Some([40.0, 45.0])
+        );
+        assert_eq!(
+            left.window_to_viewport_pixel(window_size, [160, 90], [1200.0, 450.0]),
+            None
+        );
+
+        // the top left of the window is the top left of the left viewport
+        assert_eq!(
+            left.window_to_viewport_pixel(window_size, [160, 90], [0.0, 0.0]),
+            Some([0.0, 90.0])
+        );
+    }
+
+    #[test]
+    fn black_bars_are_not_in_a_viewport() {
+        let (left, _) = split_screen();
+        // a square window has bars above and below the 16:9 image
+        let window_size = (1600, 1600);
+        assert_eq!(
+            left.window_to_viewport_pixel(window_size, [160, 90], [400.0, 100.0]),
+            None
+        );
+        assert_eq!(
+            left.window_to_viewport_pixel(window_size, [160, 90], [400.0, 800.0]),
+            Some([40.0, 45.0])
+        );
+    }
+}